@@ -0,0 +1,76 @@
+//! Sets cfgs for features of newer compilers:
+//!
+//! * `mbox_reverse_eq` on rustc 1.41 or above, whose orphan rules allow `impl PartialEq<MBox<[U]>>
+//!   for [T]` and similar.
+//! * `mbox_const_mutex` on rustc 1.63 or above, where `Mutex::new()` can initialize a static.
+//! * `mbox_core_error` on rustc 1.81 or above, where `core::error::Error` is available.
+//!
+//! Also generates the C header of the `capi` module when both the `capi` and `cbindgen` features
+//! are enabled. The header is written to `$OUT_DIR/mbox.h`, or to `$MBOX_HEADER` if it is set.
+
+use std::env;
+use std::process::Command;
+
+/// The cfgs to set, and the minimum minor version of rustc enabling each.
+const VERSION_CFGS: &[(&str, u32)] = &[
+    ("mbox_reverse_eq", 41),
+    ("mbox_const_mutex", 63),
+    ("mbox_core_error", 81),
+];
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    if let Some(minor) = rustc_minor_version() {
+        for &(cfg, min_minor) in VERSION_CFGS {
+            // `rustc-check-cfg` is only understood since 1.80, older versions warn about it.
+            if minor >= 80 {
+                println!("cargo:rustc-check-cfg=cfg({})", cfg);
+            }
+            if minor >= min_minor {
+                println!("cargo:rustc-cfg={}", cfg);
+            }
+        }
+    }
+    #[cfg(feature = "cbindgen")]
+    generate_header();
+}
+
+/// Returns the minor version of the compiler, e.g. 81 for `rustc 1.81.0 (eeb90cda1 2024-09-04)`.
+fn rustc_minor_version() -> Option<u32> {
+    let rustc = env::var_os("RUSTC")?;
+    let output = Command::new(rustc).arg("--version").output().ok()?;
+    let version = String::from_utf8(output.stdout).ok()?;
+    version
+        .split_whitespace()
+        .nth(1)?
+        .split('.')
+        .nth(1)?
+        .parse()
+        .ok()
+}
+
+#[cfg(feature = "cbindgen")]
+fn generate_header() {
+    use std::path::{Path, PathBuf};
+
+    println!("cargo:rerun-if-changed=src/capi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-env-changed=MBOX_HEADER");
+    if env::var_os("CARGO_FEATURE_CAPI").is_none() {
+        return;
+    }
+
+    let crate_dir = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR").unwrap());
+    let header = match env::var_os("MBOX_HEADER") {
+        Some(path) => PathBuf::from(path),
+        None => Path::new(&env::var_os("OUT_DIR").unwrap()).join("mbox.h"),
+    };
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml"))
+        .expect("cannot read cbindgen.toml");
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(crate_dir.join("src/capi.rs"))
+        .generate()
+        .expect("cannot generate the C header")
+        .write_to_file(header);
+}
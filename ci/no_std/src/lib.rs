@@ -0,0 +1,25 @@
+//! Links `mbox` into a `#![no_std]` library without any global allocator, so that the build fails
+//! if `mbox` ever depends on `std` or `alloc` when its `std` feature is disabled.
+
+#![no_std]
+
+use core::panic::PanicInfo;
+
+use mbox::{MArray, MBox, MString};
+
+#[panic_handler]
+fn panic(_: &PanicInfo) -> ! {
+    // SAFETY: `abort()` has no preconditions.
+    unsafe { libc::abort() }
+}
+
+/// Exercises the allocation paths, returning the total length of the created values.
+#[no_mangle]
+pub extern "C" fn mbox_no_std_check() -> usize {
+    let mut slice = MBox::from_slice(&[1u32, 2, 3]);
+    slice.extend_from_slice(&[4, 5]);
+    let string = MString::from("no_std");
+    let array = MArray::from_slice(&[1u8, 2]);
+    let boxed = MBox::new(0u64);
+    slice.len() + string.len() + array.len() + *boxed as usize
+}
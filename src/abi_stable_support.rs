@@ -0,0 +1,287 @@
+//! `abi_stable` support.
+//!
+//! `MBox` itself has no stable layout, so it cannot appear in an `abi_stable` interface. This
+//! module provides `#[repr(C)]` mirror types implementing `StableAbi`, which own their memory just
+//! like the originals and convert from and into them without copying. Since every side of the
+//! boundary releases the memory with the same C `free()`, a box allocated by a plugin can be
+//! dropped by the host and vice versa.
+//!
+//! ```
+//! use abi_stable::StableAbi;
+//! use mbox::abi_stable_support::RMString;
+//! use mbox::MString;
+//!
+//! fn greet() -> RMString {
+//!     MString::from("hello").into()
+//! }
+//!
+//! let _ = <RMString as StableAbi>::LAYOUT;
+//! assert_eq!(&*greet(), "hello");
+//! assert_eq!(MString::from(greet()).as_bytes_with_sentinel(), b"hello\0");
+//! ```
+
+use abi_stable::StableAbi;
+
+use std::fmt::{Debug, Formatter, Result as FormatResult};
+use std::marker::PhantomData;
+use std::mem::forget;
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+use std::slice::{from_raw_parts, from_raw_parts_mut};
+use std::str::from_utf8_unchecked;
+
+use crate::mbox::MBox;
+use crate::sentinel::MString;
+
+/// The FFI-safe counterpart of `MBox<T>`.
+#[derive(StableAbi)]
+#[repr(C)]
+pub struct RMBox<T> {
+    ptr: NonNull<T>,
+    marker: PhantomData<T>,
+}
+
+// SAFETY: `RMBox<T>` uniquely owns its content, like `MBox<T>`.
+unsafe impl<T: Send> Send for RMBox<T> {}
+// SAFETY: `RMBox<T>` uniquely owns its content, like `MBox<T>`.
+unsafe impl<T: Sync> Sync for RMBox<T> {}
+
+impl<T> RMBox<T> {
+    /// Converts back into an `MBox<T>`.
+    pub fn into_mbox(self) -> MBox<T> {
+        let ptr = self.ptr;
+        forget(self);
+        // SAFETY: the pointer is obtained from `MBox::into_non_null_raw()`.
+        unsafe { MBox::from_non_null_raw(ptr) }
+    }
+}
+
+impl<T> From<MBox<T>> for RMBox<T> {
+    fn from(boxed: MBox<T>) -> Self {
+        Self {
+            ptr: MBox::into_non_null_raw(boxed),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> From<RMBox<T>> for MBox<T> {
+    fn from(boxed: RMBox<T>) -> Self {
+        boxed.into_mbox()
+    }
+}
+
+impl<T> Deref for RMBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the pointer is owned by `self` and initialized.
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T> DerefMut for RMBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the pointer is owned by `self` and initialized.
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T> Drop for RMBox<T> {
+    fn drop(&mut self) {
+        // SAFETY: the pointer is obtained from `MBox::into_non_null_raw()`, and never used again.
+        unsafe { drop(MBox::from_non_null_raw(self.ptr)) };
+    }
+}
+
+impl<T: Debug> Debug for RMBox<T> {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        (**self).fmt(formatter)
+    }
+}
+
+/// The FFI-safe counterpart of `MBox<[T]>`.
+#[derive(StableAbi)]
+#[repr(C)]
+pub struct RMBoxSlice<T> {
+    ptr: NonNull<T>,
+    len: usize,
+    marker: PhantomData<T>,
+}
+
+// SAFETY: `RMBoxSlice<T>` uniquely owns its items, like `MBox<[T]>`.
+unsafe impl<T: Send> Send for RMBoxSlice<T> {}
+// SAFETY: `RMBoxSlice<T>` uniquely owns its items, like `MBox<[T]>`.
+unsafe impl<T: Sync> Sync for RMBoxSlice<T> {}
+
+impl<T> RMBoxSlice<T> {
+    /// Converts back into an `MBox<[T]>`.
+    pub fn into_mbox(self) -> MBox<[T]> {
+        let (ptr, len) = (self.ptr, self.len);
+        forget(self);
+        // SAFETY: the parts are obtained from `MBox::into_raw_parts()`.
+        unsafe { MBox::from_raw_parts(ptr.as_ptr(), len) }
+    }
+}
+
+impl<T> From<MBox<[T]>> for RMBoxSlice<T> {
+    fn from(boxed: MBox<[T]>) -> Self {
+        let (ptr, len) = boxed.into_raw_parts();
+        Self {
+            // SAFETY: the pointer of a box is never null.
+            ptr: unsafe { NonNull::new_unchecked(ptr) },
+            len,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> From<RMBoxSlice<T>> for MBox<[T]> {
+    fn from(boxed: RMBoxSlice<T>) -> Self {
+        boxed.into_mbox()
+    }
+}
+
+impl<T> Deref for RMBoxSlice<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        // SAFETY: the items are owned by `self` and initialized.
+        unsafe { from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T> DerefMut for RMBoxSlice<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        // SAFETY: the items are owned by `self` and initialized.
+        unsafe { from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T> Drop for RMBoxSlice<T> {
+    fn drop(&mut self) {
+        // SAFETY: the parts are obtained from `MBox::into_raw_parts()`, and never used again.
+        unsafe { drop(MBox::from_raw_parts(self.ptr.as_ptr(), self.len)) };
+    }
+}
+
+impl<T: Debug> Debug for RMBoxSlice<T> {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        (**self).fmt(formatter)
+    }
+}
+
+/// The FFI-safe counterpart of `MString`.
+///
+/// The string is always null-terminated. `len` counts the terminating `'\0'`.
+#[derive(StableAbi)]
+#[repr(C)]
+pub struct RMString {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+// SAFETY: `RMString` uniquely owns its content, like `MString`.
+unsafe impl Send for RMString {}
+// SAFETY: `RMString` uniquely owns its content, like `MString`.
+unsafe impl Sync for RMString {}
+
+impl RMString {
+    /// Obtains the raw bytes including the sentinel.
+    pub fn as_bytes_with_sentinel(&self) -> &[u8] {
+        // SAFETY: the bytes are owned by `self` and initialized.
+        unsafe { from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+    /// Converts back into an `MString`.
+    pub fn into_mstring(self) -> MString {
+        let (ptr, len) = (self.ptr, self.len);
+        forget(self);
+        // SAFETY: the parts are obtained from a null-terminated `MString`.
+        unsafe {
+            MString::from_mbox_with_sentinel_unchecked(MBox::from_raw_utf8_parts_unchecked(
+                ptr.as_ptr(),
+                len,
+            ))
+        }
+    }
+}
+
+impl From<MString> for RMString {
+    fn from(string: MString) -> Self {
+        let (ptr, len) = string
+            .into_mbox_with_sentinel()
+            .into_bytes()
+            .into_raw_parts();
+        Self {
+            // SAFETY: the pointer of a box is never null.
+            ptr: unsafe { NonNull::new_unchecked(ptr) },
+            len,
+        }
+    }
+}
+
+impl From<RMString> for MString {
+    fn from(string: RMString) -> Self {
+        string.into_mstring()
+    }
+}
+
+impl Deref for RMString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        let bytes = self.as_bytes_with_sentinel();
+        // SAFETY: the content is obtained from an `MString`, which is valid UTF-8.
+        unsafe { from_utf8_unchecked(&bytes[..bytes.len() - 1]) }
+    }
+}
+
+impl Drop for RMString {
+    fn drop(&mut self) {
+        // SAFETY: the parts are obtained from `MBox::into_raw_parts()`, and never used again.
+        unsafe { drop(MBox::from_raw_parts(self.ptr.as_ptr(), self.len)) };
+    }
+}
+
+impl Debug for RMString {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        (**self).fmt(formatter)
+    }
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_abi_stable() {
+    use crate::test_util::DropCounter;
+
+    let _ = <RMBox<u32> as StableAbi>::LAYOUT;
+    let _ = <RMBoxSlice<u32> as StableAbi>::LAYOUT;
+    let _ = <RMString as StableAbi>::LAYOUT;
+
+    let mut boxed = RMBox::from(MBox::new(1u32));
+    *boxed += 1;
+    assert_eq!(*boxed.into_mbox(), 2);
+
+    let mut slice = RMBoxSlice::from(MBox::from_slice(&[1u16, 2, 3]));
+    slice[0] = 4;
+    assert_eq!(&*slice.into_mbox(), &[4, 2, 3]);
+    assert!(RMBoxSlice::from(MBox::<[u8]>::new_empty()).is_empty());
+
+    let string = RMString::from(MString::from("abc"));
+    assert_eq!(&*string, "abc");
+    assert_eq!(string.as_bytes_with_sentinel(), b"abc\0");
+    assert_eq!(MString::from(string), "abc");
+    let string = RMString::from(MString::new_empty());
+    assert_eq!(string.as_bytes_with_sentinel(), b"\0");
+    assert_eq!(&*string, "");
+
+    let counter = DropCounter::default();
+    drop(RMBox::from(MBox::new(counter.clone())));
+    counter.assert_eq(1);
+    drop(RMBoxSlice::from(MBox::from_slice(&[
+        counter.clone(),
+        counter.clone(),
+    ])));
+    counter.assert_eq(5);
+}
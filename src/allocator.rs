@@ -0,0 +1,338 @@
+//! A `std::alloc::Allocator` backed by `malloc()` (nightly only).
+//!
+//! `Malloc` allocates through the same paths as `MBox`, so an `MBox<[T]>` can be turned into a
+//! `Vec<T, Malloc>` and back without copying the items. This lets malloc'd buffers move between C
+//! code and allocator-aware std collections freely. An `MString` converts into its bytes in a
+//! `Vec<u8, Malloc>`, which can be grown like a string builder before being turned back.
+//!
+//! ```
+//! #![feature(allocator_api)]
+//! use mbox::allocator::Malloc;
+//! use mbox::{MBox, MString};
+//!
+//! let mut vec = MBox::from_slice(&[1u8, 2, 3]).into_vec_in();
+//! vec.push(4);
+//! assert_eq!(&*MBox::<[u8]>::from(vec), &[1, 2, 3, 4]);
+//!
+//! let mut builder = MString::from("hello").into_vec_in();
+//! builder.extend_from_slice(b", world");
+//! let string = MString::from_utf8_vec_in(builder).unwrap();
+//! assert_eq!(string.as_bytes_with_sentinel(), b"hello, world\0");
+//! ```
+//!
+//! Not available on Windows, whose `malloc()` cannot align beyond its defaults.
+
+use std::alloc::{AllocError as StdAllocError, Allocator, Layout};
+use std::mem::size_of;
+use std::ptr::{copy_nonoverlapping, write_bytes, NonNull};
+
+use crate::error::FromUtf8Error;
+use crate::internal::{
+    from_utf8, gen_free, try_gen_calloc, try_gen_malloc, try_gen_realloc, EmptySlice,
+};
+use crate::mbox::MBox;
+use crate::sentinel::MString;
+
+/// An allocator using `malloc()` and `free()`, compatible with the allocations of `MBox`.
+///
+/// Blocks may be aligned up to 4096 bytes. Zero-sized requests do not allocate.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Malloc;
+
+/// Declares the unit types of each supported alignment.
+macro_rules! aligned_units {
+    ($($name:ident $align:literal)+) => {
+        $(
+            #[repr(C, align($align))]
+            struct $name([u8; $align]);
+        )+
+    };
+}
+
+aligned_units! {
+    A1 1 A2 2 A4 4 A8 8 A16 16 A32 32 A64 64 A128 128 A256 256 A512 512 A1024 1024 A2048 2048
+    A4096 4096
+}
+
+/// Calls `$func::<U>(...)`, where `U` is the unit type of the alignment `$align`, or evaluates
+/// `$unsupported` if the alignment is too large.
+macro_rules! with_unit {
+    ($align:expr, $unsupported:expr, $func:ident($($arg:expr),*)) => {
+        match $align {
+            1 => $func::<A1>($($arg),*),
+            2 => $func::<A2>($($arg),*),
+            4 => $func::<A4>($($arg),*),
+            8 => $func::<A8>($($arg),*),
+            16 => $func::<A16>($($arg),*),
+            32 => $func::<A32>($($arg),*),
+            64 => $func::<A64>($($arg),*),
+            128 => $func::<A128>($($arg),*),
+            256 => $func::<A256>($($arg),*),
+            512 => $func::<A512>($($arg),*),
+            1024 => $func::<A1024>($($arg),*),
+            2048 => $func::<A2048>($($arg),*),
+            4096 => $func::<A4096>($($arg),*),
+            _ => $unsupported,
+        }
+    };
+}
+
+/// Allocates a block for `size` bytes in units of `U`.
+fn allocate_units<U>(size: usize, zeroed: bool) -> Result<NonNull<[u8]>, StdAllocError> {
+    let count = size.div_ceil(size_of::<U>());
+    let ptr = if zeroed {
+        try_gen_calloc::<U>(count)
+    } else {
+        try_gen_malloc::<U>(count)
+    };
+    let ptr = ptr.map_err(|_| StdAllocError)?;
+    Ok(NonNull::slice_from_raw_parts(
+        ptr.cast(),
+        count * size_of::<U>(),
+    ))
+}
+
+/// Frees a block allocated in units of `U`.
+unsafe fn free_units<U>(ptr: NonNull<u8>) {
+    gen_free(ptr.cast::<U>())
+}
+
+/// Reallocates a block in units of `U` from `old_size` to `new_size` bytes.
+unsafe fn realloc_units<U>(
+    ptr: NonNull<u8>,
+    old_size: usize,
+    new_size: usize,
+) -> Result<NonNull<[u8]>, StdAllocError> {
+    let old_count = old_size.div_ceil(size_of::<U>());
+    let new_count = new_size.div_ceil(size_of::<U>());
+    let ptr = try_gen_realloc(ptr.cast::<U>(), old_count, new_count).map_err(|_| StdAllocError)?;
+    Ok(NonNull::slice_from_raw_parts(
+        ptr.cast(),
+        new_count * size_of::<U>(),
+    ))
+}
+
+impl Malloc {
+    fn allocate_impl(&self, layout: Layout, zeroed: bool) -> Result<NonNull<[u8]>, StdAllocError> {
+        if layout.size() == 0 {
+            // SAFETY: the alignment is never 0.
+            let dangling = unsafe { NonNull::new_unchecked(layout.align() as *mut u8) };
+            return Ok(NonNull::slice_from_raw_parts(dangling, 0));
+        }
+        with_unit!(
+            layout.align(),
+            Err(StdAllocError),
+            allocate_units(layout.size(), zeroed)
+        )
+    }
+
+    /// Resizes a block, moving it to a new block if the alignment is changed.
+    unsafe fn resize(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+        zeroed: bool,
+    ) -> Result<NonNull<[u8]>, StdAllocError> {
+        let (old_size, new_size) = (old_layout.size(), new_layout.size());
+        if old_size == 0 || new_size == 0 || old_layout.align() != new_layout.align() {
+            let new_ptr = self.allocate_impl(new_layout, zeroed)?;
+            // both blocks are valid for the smaller size, and are distinct allocations.
+            copy_nonoverlapping(
+                ptr.as_ptr(),
+                new_ptr.cast().as_ptr(),
+                old_size.min(new_size),
+            );
+            self.deallocate(ptr, old_layout);
+            return Ok(new_ptr);
+        }
+        // the block is allocated by `Malloc` with the same alignment.
+        let new_ptr = with_unit!(
+            new_layout.align(),
+            Err(StdAllocError),
+            realloc_units(ptr, old_size, new_size)
+        )?;
+        if zeroed && new_size > old_size {
+            write_bytes(
+                new_ptr.cast::<u8>().as_ptr().add(old_size),
+                0,
+                new_size - old_size,
+            );
+        }
+        Ok(new_ptr)
+    }
+}
+
+unsafe impl Allocator for Malloc {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, StdAllocError> {
+        self.allocate_impl(layout, false)
+    }
+
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, StdAllocError> {
+        self.allocate_impl(layout, true)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() != 0 {
+            // the block is allocated by `Malloc`, so its alignment is always supported.
+            with_unit!(layout.align(), unreachable!(), free_units(ptr))
+        }
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, StdAllocError> {
+        self.resize(ptr, old_layout, new_layout, false)
+    }
+
+    unsafe fn grow_zeroed(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, StdAllocError> {
+        self.resize(ptr, old_layout, new_layout, true)
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, StdAllocError> {
+        self.resize(ptr, old_layout, new_layout, false)
+    }
+}
+
+impl<T> MBox<[T]> {
+    /// Converts into a `Vec` using the `Malloc` allocator, without copying the items. The capacity
+    /// of the result equals the length.
+    pub fn into_vec_in(self) -> Vec<T, Malloc> {
+        let (ptr, len) = self.into_raw_parts();
+        // SAFETY: the block is allocated by `malloc()` for `len` items of `T`, which matches the
+        // layout `Malloc` expects. An empty or zero-sized block is released here instead, since
+        // a `Vec` never frees those.
+        unsafe {
+            if len == 0 || size_of::<T>() == 0 {
+                gen_free(NonNull::new_unchecked(ptr));
+                let mut vec = Vec::new_in(Malloc);
+                vec.set_len(len);
+                return vec;
+            }
+            Vec::from_raw_parts_in(ptr, len, len, Malloc)
+        }
+    }
+}
+
+impl<T> From<Vec<T, Malloc>> for MBox<[T]> {
+    /// Converts a `Vec` using the `Malloc` allocator, after shrinking it to fit. The items are not
+    /// copied.
+    fn from(mut vec: Vec<T, Malloc>) -> Self {
+        vec.shrink_to_fit();
+        let (ptr, len, cap, _) = vec.into_raw_parts_with_alloc();
+        // SAFETY: the block is allocated by `Malloc` for `len` items. A `Vec` without an
+        // allocation holds a dangling pointer, which is the same as `EmptySlice::<T>::PTR`.
+        unsafe {
+            if cap == 0 || size_of::<T>() == 0 {
+                return MBox::from_raw_parts((EmptySlice::<T>::PTR) as *mut T, len);
+            }
+            MBox::from_raw_parts(ptr, len)
+        }
+    }
+}
+
+impl MString {
+    /// Converts into the bytes of the string in a `Vec` using the `Malloc` allocator, without
+    /// copying. The sentinel is removed, but its space is kept as spare capacity.
+    pub fn into_vec_in(self) -> Vec<u8, Malloc> {
+        let mut vec = self.into_mbox_with_sentinel().into_bytes().into_vec_in();
+        vec.pop();
+        vec
+    }
+
+    /// Converts the bytes in a `Vec` using the `Malloc` allocator into a string, appending the
+    /// sentinel. The bytes are not copied unless the `Vec` needs to grow for the sentinel. Errors
+    /// with `FromUtf8Error` if the bytes are not in valid UTF-8.
+    pub fn from_utf8_vec_in(mut vec: Vec<u8, Malloc>) -> Result<MString, FromUtf8Error> {
+        if let Err(error) = from_utf8(&vec) {
+            return Err(FromUtf8Error {
+                bytes: MBox::from(vec),
+                error,
+            });
+        }
+        vec.push(0);
+        let bytes = MBox::from(vec);
+        // SAFETY: the bytes are valid UTF-8, followed by the sentinel.
+        unsafe {
+            Ok(MString::from_mbox_with_sentinel_unchecked(
+                MBox::from_utf8_unchecked(bytes),
+            ))
+        }
+    }
+}
+
+#[test]
+fn test_vec_in() {
+    let mut vec = MBox::from_slice(&[1u32, 2, 3]).into_vec_in();
+    assert_eq!(vec.capacity(), 3);
+    vec.extend_from_slice(&[4, 5]);
+    vec.remove(0);
+    assert_eq!(&*MBox::<[u32]>::from(vec), &[2, 3, 4, 5]);
+
+    let vec = MBox::<[u64]>::new_empty().into_vec_in();
+    assert!(vec.is_empty());
+    assert!(MBox::<[u64]>::from(vec).is_empty());
+    let vec = MBox::from_slice(&[(), ()]).into_vec_in();
+    assert_eq!(vec.len(), 2);
+    assert_eq!(MBox::<[()]>::from(vec).len(), 2);
+
+    let mut vec = Vec::with_capacity_in(16, Malloc);
+    vec.push(7u8);
+    let boxed = MBox::<[u8]>::from(vec);
+    assert_eq!(&*boxed, &[7]);
+}
+
+#[test]
+fn test_malloc_allocator() {
+    let layout = Layout::from_size_align(100, 256).unwrap();
+    let block = Malloc.allocate_zeroed(layout).unwrap();
+    assert_eq!(block.cast::<u8>().as_ptr() as usize % 256, 0);
+    assert!(block.len() >= 100);
+    unsafe {
+        assert!(block.as_ref().iter().all(|b| *b == 0));
+        let new_layout = Layout::from_size_align(300, 256).unwrap();
+        let block = Malloc
+            .grow_zeroed(block.cast(), layout, new_layout)
+            .unwrap();
+        assert!(block.as_ref()[..300].iter().all(|b| *b == 0));
+        let block = Malloc.shrink(block.cast(), new_layout, layout).unwrap();
+        Malloc.deallocate(block.cast(), layout);
+    }
+    assert!(Malloc
+        .allocate(Layout::from_size_align(8, 8192).unwrap())
+        .is_err());
+    let empty = Malloc.allocate(Layout::new::<()>()).unwrap();
+    unsafe { Malloc.deallocate(empty.cast(), Layout::new::<()>()) };
+}
+
+#[test]
+fn test_string_vec_in() {
+    let mut builder = MString::from("abc").into_vec_in();
+    assert_eq!(builder.capacity(), 4);
+    builder.push(b'd');
+    let string = MString::from_utf8_vec_in(builder).unwrap();
+    assert_eq!(string.as_bytes_with_sentinel(), b"abcd\0");
+
+    let builder = MString::new_empty().into_vec_in();
+    assert!(builder.is_empty());
+    let string = MString::from_utf8_vec_in(builder).unwrap();
+    assert_eq!(string.as_bytes_with_sentinel(), b"\0");
+
+    let mut builder = Vec::new_in(Malloc);
+    builder.extend_from_slice(b"\xff");
+    let error = MString::from_utf8_vec_in(builder).unwrap_err();
+    assert_eq!(&*error.into_bytes(), b"\xff");
+}
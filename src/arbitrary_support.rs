@@ -0,0 +1,92 @@
+//! `arbitrary` support.
+
+use arbitrary::{size_hint, Arbitrary, MaxRecursionReached, Result, Unstructured};
+
+use crate::mbox::MBox;
+use crate::sentinel::MString;
+
+impl<'a, T: Arbitrary<'a>> Arbitrary<'a> for MBox<T> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        T::arbitrary(u).map(MBox::new)
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        Self::try_size_hint(depth).unwrap_or_default()
+    }
+
+    fn try_size_hint(depth: usize) -> Result<(usize, Option<usize>), MaxRecursionReached> {
+        size_hint::try_recursion_guard(depth, T::try_size_hint)
+    }
+}
+
+impl<'a, T: Arbitrary<'a>> Arbitrary<'a> for MBox<[T]> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        u.arbitrary_iter()?.collect()
+    }
+
+    fn arbitrary_take_rest(u: Unstructured<'a>) -> Result<Self> {
+        u.arbitrary_take_rest_iter()?.collect()
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        (0, None)
+    }
+}
+
+impl<'a> Arbitrary<'a> for MBox<str> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        <&str>::arbitrary(u).map(MBox::from)
+    }
+
+    fn arbitrary_take_rest(u: Unstructured<'a>) -> Result<Self> {
+        <&str>::arbitrary_take_rest(u).map(MBox::from)
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        <&str>::size_hint(depth)
+    }
+}
+
+/// Truncates the string at the first NUL, so the result is a valid C string.
+fn until_nul(string: &str) -> &str {
+    string.split('\0').next().unwrap_or_default()
+}
+
+impl<'a> Arbitrary<'a> for MString {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        <&str>::arbitrary(u).map(|s| MString::from(until_nul(s)))
+    }
+
+    fn arbitrary_take_rest(u: Unstructured<'a>) -> Result<Self> {
+        <&str>::arbitrary_take_rest(u).map(|s| MString::from(until_nul(s)))
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        <&str>::size_hint(depth)
+    }
+}
+
+#[test]
+fn test_arbitrary() {
+    let data = b"\x05abc\x00def\x01\x02\x03\x04\x05\x06\x07\x08\x09";
+    let mut u = Unstructured::new(data);
+    let single = MBox::<u8>::arbitrary(&mut u).unwrap();
+    let slice = MBox::<[u8]>::arbitrary(&mut u).unwrap();
+    let string = MBox::<str>::arbitrary(&mut u).unwrap();
+    let mstring = MString::arbitrary(&mut u).unwrap();
+    assert_eq!(*single, 5);
+    assert!(slice.len() <= data.len());
+    assert!(string.len() <= data.len());
+    assert!(!mstring.contains('\0'));
+}
+
+#[test]
+fn test_arbitrary_take_rest() {
+    let slice = MBox::<[u8]>::arbitrary_take_rest(Unstructured::new(b"abc")).unwrap();
+    assert!(slice.len() <= 3);
+    let string = MBox::<str>::arbitrary_take_rest(Unstructured::new(b"abc")).unwrap();
+    assert_eq!(&*string, "abc");
+    let string = MString::arbitrary_take_rest(Unstructured::new(b"ab\0cd")).unwrap();
+    assert_eq!(string, "ab");
+    assert_eq!(string.as_bytes_with_sentinel(), b"ab\0");
+}
@@ -0,0 +1,192 @@
+//! An atomic slot holding an optional `MBox`.
+//!
+//! `AtomicMBox<T>` lets a thread publish a freshly allocated box, e.g. data parsed by C code, while
+//! other threads take or replace it, without a mutex. Only owned boxes ever come out of the slot,
+//! never references, so whoever swaps a box out is free to drop it. Every operation uses
+//! `Ordering::AcqRel`, so the content of a box is always visible to the thread taking it out.
+//!
+//! ```
+//! use mbox::atomic::AtomicMBox;
+//! use mbox::MBox;
+//!
+//! static LATEST: AtomicMBox<u8> = AtomicMBox::empty();
+//!
+//! LATEST.store(Some(MBox::new(1)));
+//! let old = LATEST.swap(Some(MBox::new(2)));
+//! assert_eq!(old.map(|b| *b), Some(1));
+//! assert_eq!(LATEST.take().map(|b| *b), Some(2));
+//! ```
+
+use std::fmt::{Debug, Formatter, Result as FormatResult};
+use std::marker::PhantomData;
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+use crate::mbox::MBox;
+
+/// An `Option<MBox<T>>` which can be swapped atomically.
+pub struct AtomicMBox<T> {
+    ptr: AtomicPtr<T>,
+    marker: PhantomData<MBox<T>>,
+}
+
+// SAFETY: the slot owns the box, like `MBox<T>`.
+unsafe impl<T: Send> Send for AtomicMBox<T> {}
+// SAFETY: sharing the slot only moves owned boxes between threads, like `Mutex<MBox<T>>`.
+unsafe impl<T: Send> Sync for AtomicMBox<T> {}
+
+/// Converts an optional box into a possibly null pointer.
+fn into_ptr<T>(value: Option<MBox<T>>) -> *mut T {
+    value.map_or(null_mut(), MBox::into_raw)
+}
+
+/// Converts a possibly null pointer taken out of the slot back into an optional box.
+///
+/// # Safety
+///
+/// The pointer must have been returned by `into_ptr()`, and be owned by the caller.
+unsafe fn from_ptr<T>(ptr: *mut T) -> Option<MBox<T>> {
+    if ptr.is_null() {
+        None
+    } else {
+        Some(MBox::from_raw(ptr))
+    }
+}
+
+impl<T> AtomicMBox<T> {
+    /// Creates a slot holding `value`.
+    pub fn new(value: Option<MBox<T>>) -> Self {
+        Self {
+            ptr: AtomicPtr::new(into_ptr(value)),
+            marker: PhantomData,
+        }
+    }
+
+    /// Creates an empty slot. This can be used to initialize a static.
+    pub const fn empty() -> Self {
+        Self {
+            ptr: AtomicPtr::new(null_mut()),
+            marker: PhantomData,
+        }
+    }
+
+    /// Stores `value` into the slot, and returns the previous content.
+    pub fn swap(&self, value: Option<MBox<T>>) -> Option<MBox<T>> {
+        // the release half publishes the content of `value`, and the acquire half makes the
+        // content of the previous box visible before it is used or dropped.
+        let ptr = self.ptr.swap(into_ptr(value), Ordering::AcqRel);
+        // SAFETY: the pointer is swapped out of the slot, so its ownership is transferred to us.
+        unsafe { from_ptr(ptr) }
+    }
+
+    /// Takes the content out of the slot, leaving it empty.
+    pub fn take(&self) -> Option<MBox<T>> {
+        self.swap(None)
+    }
+
+    /// Stores `value` into the slot, dropping the previous content.
+    pub fn store(&self, value: Option<MBox<T>>) {
+        self.swap(value);
+    }
+
+    /// Returns a mutable reference to the content. This is safe because the mutable reference
+    /// guarantees no other threads access the slot concurrently.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        // SAFETY: a non-null pointer is owned by the slot, which is borrowed mutably.
+        unsafe { self.ptr.get_mut().as_mut() }
+    }
+
+    /// Consumes the slot and returns its content.
+    pub fn into_inner(mut self) -> Option<MBox<T>> {
+        let ptr = std::mem::replace(self.ptr.get_mut(), null_mut());
+        // SAFETY: the pointer is removed from the slot, so its ownership is transferred to us.
+        unsafe { from_ptr(ptr) }
+    }
+}
+
+impl<T> Default for AtomicMBox<T> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<T> From<MBox<T>> for AtomicMBox<T> {
+    fn from(value: MBox<T>) -> Self {
+        Self::new(Some(value))
+    }
+}
+
+impl<T> Drop for AtomicMBox<T> {
+    fn drop(&mut self) {
+        // SAFETY: the slot owns the pointer, and is never used again.
+        unsafe { drop(from_ptr(*self.ptr.get_mut())) };
+    }
+}
+
+impl<T> Debug for AtomicMBox<T> {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        formatter
+            .debug_tuple("AtomicMBox")
+            .field(&self.ptr.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_atomic_mbox() {
+    use crate::test_util::DropCounter;
+
+    let counter = DropCounter::default();
+    let mut slot = AtomicMBox::default();
+    assert!(slot.get_mut().is_none());
+    assert!(slot.swap(Some(MBox::new(counter.clone()))).is_none());
+    assert!(slot.get_mut().is_some());
+    slot.store(Some(MBox::new(counter.clone())));
+    counter.assert_eq(1);
+
+    let taken = slot.take();
+    assert!(taken.is_some());
+    assert!(slot.take().is_none());
+    drop(taken);
+    counter.assert_eq(2);
+
+    slot.store(Some(MBox::new(counter.clone())));
+    drop(slot);
+    counter.assert_eq(3);
+
+    let slot = AtomicMBox::from(MBox::new(counter.clone()));
+    let inner = slot.into_inner();
+    counter.assert_eq(3);
+    drop(inner);
+    counter.assert_eq(4);
+}
+
+#[cfg(all(feature = "std", not(windows)))]
+#[test]
+fn test_atomic_mbox_threads() {
+    use std::sync::Arc;
+    use std::thread;
+
+    let slot = Arc::new(AtomicMBox::new(Some(MBox::new(0usize))));
+    let threads = (1..=4)
+        .map(|i| {
+            let slot = Arc::clone(&slot);
+            thread::spawn(move || {
+                let mut sum = 0;
+                for j in 0..100 {
+                    if let Some(old) = slot.swap(Some(MBox::new(i * 1000 + j))) {
+                        sum += *old;
+                    }
+                }
+                sum
+            })
+        })
+        .collect::<Vec<_>>();
+    let sum: usize = threads.into_iter().map(|t| t.join().unwrap()).sum();
+    let last = *slot.take().unwrap();
+    let expected: usize = (1..=4)
+        .map(|i| (0..100).map(|j| i * 1000 + j).sum::<usize>())
+        .sum();
+    assert_eq!(sum + last, expected);
+}
@@ -0,0 +1,80 @@
+//! `bytes` support.
+//!
+//! A cursor over an `MBox<[u8]>` is a `Buf`, and an `MSliceBuilder<u8>` is a `BufMut` growing with
+//! `realloc()`, so codecs written against the `bytes` traits can read from and write into malloc'd
+//! buffers directly.
+
+use bytes::buf::UninitSlice;
+#[cfg(feature = "std")]
+use bytes::Buf;
+use bytes::BufMut;
+
+#[cfg(feature = "std")]
+use std::borrow::Borrow;
+#[cfg(feature = "std")]
+use std::cmp::min;
+#[cfg(feature = "std")]
+use std::io::BufRead;
+
+#[cfg(feature = "std")]
+use crate::cursor::MCursor;
+#[cfg(feature = "std")]
+use crate::mbox::MBox;
+use crate::mbox::MSliceBuilder;
+
+#[cfg(feature = "std")]
+impl<B: Borrow<MBox<[u8]>>> Buf for MCursor<B> {
+    fn remaining(&self) -> usize {
+        let len = self.get_ref().borrow().len();
+        // a position beyond the end has nothing remaining.
+        len - min(self.position(), len as u64) as usize
+    }
+
+    fn chunk(&self) -> &[u8] {
+        let data: &[u8] = self.get_ref().borrow();
+        &data[data.len() - self.remaining()..]
+    }
+
+    fn advance(&mut self, cnt: usize) {
+        assert!(cnt <= self.remaining(), "cannot advance past the end");
+        self.consume(cnt);
+    }
+}
+
+unsafe impl BufMut for MSliceBuilder<u8> {
+    fn remaining_mut(&self) -> usize {
+        isize::MAX as usize - self.len()
+    }
+
+    unsafe fn advance_mut(&mut self, cnt: usize) {
+        self.commit_spare(cnt);
+    }
+
+    fn chunk_mut(&mut self) -> &mut UninitSlice {
+        self.spare_capacity().into()
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_buf() {
+    let mut cursor = MCursor::new(MBox::from_slice(b"\x00\x01\x02\x03abc"));
+    assert_eq!(cursor.get_u32(), 0x0001_0203);
+    assert_eq!(cursor.remaining(), 3);
+    assert_eq!(cursor.chunk(), b"abc");
+    cursor.advance(3);
+    assert!(!cursor.has_remaining());
+
+    cursor.set_position(100);
+    assert_eq!(cursor.remaining(), 0);
+    assert_eq!(cursor.chunk(), b"");
+}
+
+#[test]
+fn test_buf_mut() {
+    let mut builder = MSliceBuilder::<u8>::with_capacity(2);
+    builder.put_u16(0x1234);
+    builder.put_slice(b"hello, world");
+    builder.put_bytes(b'!', 3);
+    assert_eq!(&*builder.into_mboxed_slice(), b"\x12\x34hello, world!!!");
+}
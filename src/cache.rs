@@ -0,0 +1,149 @@
+//! Thread-local cache of small freed blocks.
+//!
+//! When the `thread_local_cache` feature is enabled, blocks of at most `MAX_CACHED_SIZE` bytes
+//! released by `gen_free()` are kept in a per-thread free list grouped by size class, and reused by
+//! `gen_malloc()`. The blocks are still genuine `malloc()` blocks, so a pointer obtained from the
+//! cache can be passed to C's `free()` as usual.
+//!
+//! The size of a block is found by `malloc_usable_size()` or similar. On platforms without such a
+//! function, nothing is cached.
+
+use libc::c_void;
+
+use std::cell::RefCell;
+use std::ptr::null_mut;
+
+use crate::internal::{free_raw, usable_size};
+
+/// The size of each size class, in bytes.
+const SIZE_CLASSES: [usize; 5] = [16, 32, 64, 128, 256];
+
+/// The largest block size which would be cached.
+const MAX_CACHED_SIZE: usize = 256;
+
+/// The maximum number of blocks kept in each size class.
+const BLOCKS_PER_CLASS: usize = 32;
+
+/// The alignment guaranteed by the cached blocks. Requests of larger alignment bypass the cache.
+const MAX_CACHED_ALIGN: usize = 16;
+
+struct FreeList {
+    blocks: [*mut c_void; BLOCKS_PER_CLASS],
+    len: usize,
+}
+
+struct Cache {
+    lists: [FreeList; 5],
+}
+
+impl Drop for Cache {
+    fn drop(&mut self) {
+        for list in &self.lists {
+            for &block in &list.blocks[..list.len] {
+                // SAFETY: the blocks in the cache are obtained from `malloc()` and owned by the
+                // cache.
+                unsafe { free_raw(block) };
+            }
+        }
+    }
+}
+
+const EMPTY_FREE_LIST: FreeList = FreeList {
+    blocks: [null_mut(); BLOCKS_PER_CLASS],
+    len: 0,
+};
+
+thread_local! {
+    static CACHE: RefCell<Cache> = RefCell::new(Cache {
+        lists: [EMPTY_FREE_LIST; 5],
+    });
+}
+
+/// Takes a cached block which can hold `size` bytes aligned to `align`.
+pub fn take(size: usize, align: usize) -> Option<*mut c_void> {
+    if size > MAX_CACHED_SIZE || align > MAX_CACHED_ALIGN {
+        return None;
+    }
+    let class = SIZE_CLASSES.iter().position(|&class| class >= size)?;
+    CACHE
+        .try_with(|cache| {
+            let mut cache = cache.try_borrow_mut().ok()?;
+            let list = &mut cache.lists[class];
+            while list.len > 0 {
+                list.len -= 1;
+                let block = list.blocks[list.len];
+                if block as usize % align == 0 {
+                    return Some(block);
+                }
+                // SAFETY: a block not satisfying the alignment is simply released.
+                unsafe { free_raw(block) };
+            }
+            None
+        })
+        .ok()?
+}
+
+/// Puts a freed block into the cache. Returns `false` if the block is not cached, in which case
+/// the caller should `free()` it.
+///
+/// # Safety
+///
+/// The `ptr` must be obtained from `malloc()` or similar C functions. Its ownership is transferred
+/// to the cache if this function returns `true`.
+pub unsafe fn give(ptr: *mut c_void) -> bool {
+    let size = usable_size(ptr);
+    if size > MAX_CACHED_SIZE {
+        return false;
+    }
+    let class = match SIZE_CLASSES.iter().rposition(|&class| class <= size) {
+        Some(class) => class,
+        None => return false,
+    };
+    CACHE
+        .try_with(|cache| match cache.try_borrow_mut() {
+            Ok(mut cache) => {
+                let list = &mut cache.lists[class];
+                if list.len < BLOCKS_PER_CLASS {
+                    list.blocks[list.len] = ptr;
+                    list.len += 1;
+                    true
+                } else {
+                    false
+                }
+            }
+            Err(_) => false,
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+use crate::mbox::MBox;
+
+// the canaries change the block sizes, which may fall into different size classes, and the
+// quarantine delays the blocks from entering the cache.
+#[cfg(all(
+    not(miri),
+    not(feature = "canary"),
+    not(feature = "quarantine"),
+    any(all(target_os = "linux", target_env = "gnu"), target_os = "macos")
+))]
+#[test]
+fn test_reuse_cached_block() {
+    let first = MBox::new(1u64);
+    let first_ptr = MBox::as_ptr(&first) as usize;
+    drop(first);
+    let second = MBox::new(2u32);
+    assert_eq!(MBox::as_ptr(&second) as usize, first_ptr);
+    assert_eq!(*second, 2);
+
+    // a cached block can still be released by `free()` directly.
+    unsafe { libc::free(MBox::into_raw(second) as *mut c_void) };
+}
+
+#[test]
+fn test_bypass_cache() {
+    assert!(take(MAX_CACHED_SIZE + 1, 1).is_none());
+    assert!(take(8, MAX_CACHED_ALIGN * 2).is_none());
+    let large = MBox::<[u8]>::from_elem(0, 4096);
+    assert_eq!(large.len(), 4096);
+}
@@ -0,0 +1,157 @@
+//! Call-site tracking of live allocations, for leak diagnostics.
+//!
+//! When the `track_call_sites` feature is enabled, every block allocated by this crate is recorded
+//! in a global table until it is freed, together with:
+//!
+//! * a `Backtrace` of the allocation, captured only if enabled by the `RUST_LIB_BACKTRACE` or
+//!   `RUST_BACKTRACE` environment variables, since capturing is slow;
+//! * the call site of `into_raw()`, `into_non_null_raw()` or `leak()` if the block has escaped
+//!   from its `MBox`, which is found with `#[track_caller]` and is always cheap. It is cleared when
+//!   the pointer comes back through `from_raw()`.
+//!
+//! `dump()` prints the outstanding blocks, so a block handed to C code which never came back can be
+//! traced to where it escaped. Blocks passed to C's `free()` cannot be noticed, and stay in the
+//! table.
+//!
+//! ```
+//! # #[cfg(feature = "track_call_sites")] {
+//! use mbox::call_sites;
+//! use mbox::MBox;
+//!
+//! let raw = MBox::into_raw(MBox::new(1u32));
+//! // prints "0x…: escaped from its MBox at src/main.rs:6:11" among other blocks.
+//! call_sites::dump(std::io::stderr()).unwrap();
+//! # drop(unsafe { MBox::from_raw(raw) });
+//! # }
+//! ```
+
+use libc::c_void;
+
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::panic::Location;
+use std::sync::{Arc, Mutex};
+
+/// An allocation which has not been freed yet.
+#[derive(Clone, Debug)]
+pub struct LiveAllocation {
+    /// The address of the block.
+    pub ptr: usize,
+    /// Where the block escaped from its `MBox` through `into_raw()` or similar, if it is not
+    /// currently owned by an `MBox`.
+    pub escaped_at: Option<&'static Location<'static>>,
+    /// The backtrace of the allocation, which is only captured when enabled by the environment.
+    pub backtrace: Arc<Backtrace>,
+}
+
+/// The live blocks, keyed by their address.
+static BLOCKS: Mutex<Option<HashMap<usize, LiveAllocation>>> = Mutex::new(None);
+
+fn with_blocks<R>(f: impl FnOnce(&mut HashMap<usize, LiveAllocation>) -> R) -> R {
+    // the table is still consistent even if another thread panicked while holding the lock.
+    let mut blocks = BLOCKS.lock().unwrap_or_else(|e| e.into_inner());
+    f(blocks.get_or_insert_with(HashMap::new))
+}
+
+/// Records a block which has just been allocated.
+pub(crate) fn allocated(ptr: *mut c_void) {
+    let allocation = LiveAllocation {
+        ptr: ptr as usize,
+        escaped_at: None,
+        backtrace: Arc::new(Backtrace::capture()),
+    };
+    with_blocks(|blocks| blocks.insert(ptr as usize, allocation));
+}
+
+/// Records a block which has been moved by `realloc()`, keeping its call sites.
+pub(crate) fn moved(old_ptr: *mut c_void, new_ptr: *mut c_void) {
+    with_blocks(|blocks| {
+        if let Some(mut allocation) = blocks.remove(&(old_ptr as usize)) {
+            allocation.ptr = new_ptr as usize;
+            blocks.insert(new_ptr as usize, allocation);
+        }
+    });
+}
+
+/// Forgets a block about to be freed.
+pub(crate) fn freed(ptr: *mut c_void) {
+    with_blocks(|blocks| blocks.remove(&(ptr as usize)));
+}
+
+fn set_escaped_at(ptr: *mut c_void, location: Option<&'static Location<'static>>) {
+    with_blocks(|blocks| {
+        if let Some(allocation) = blocks.get_mut(&(ptr as usize)) {
+            allocation.escaped_at = location;
+        }
+    });
+}
+
+/// Records that a block escaped from its `MBox`, at the location of the caller.
+#[track_caller]
+pub(crate) fn escaped(ptr: *mut c_void) {
+    set_escaped_at(ptr, Some(Location::caller()));
+}
+
+/// Records that a block is owned by an `MBox` again.
+pub(crate) fn returned(ptr: *mut c_void) {
+    set_escaped_at(ptr, None);
+}
+
+/// Returns all blocks allocated by this crate which have not been freed yet, in no particular
+/// order.
+pub fn outstanding() -> Vec<LiveAllocation> {
+    with_blocks(|blocks| blocks.values().cloned().collect())
+}
+
+/// Writes a human-readable report of the outstanding blocks, the escaped ones first.
+pub fn dump<W: Write>(mut writer: W) -> io::Result<()> {
+    let mut allocations = outstanding();
+    allocations.sort_by_key(|a| (a.escaped_at.is_none(), a.ptr));
+    writeln!(writer, "{} outstanding allocations", allocations.len())?;
+    for allocation in allocations {
+        match allocation.escaped_at {
+            Some(location) => writeln!(
+                writer,
+                "{:#x}: escaped from its MBox at {}",
+                allocation.ptr, location
+            )?,
+            None => writeln!(writer, "{:#x}: owned by an MBox", allocation.ptr)?,
+        }
+        if allocation.backtrace.status() == BacktraceStatus::Captured {
+            writeln!(writer, "allocated at:\n{}", allocation.backtrace)?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_call_sites() {
+    use crate::mbox::MBox;
+
+    let find = |ptr| outstanding().into_iter().find(|a| a.ptr == ptr);
+
+    let boxed = MBox::from_slice(b"abc");
+    let ptr = boxed.as_ptr() as usize;
+    assert!(find(ptr).unwrap().escaped_at.is_none());
+
+    let line = line!() + 1;
+    let raw = MBox::into_raw(boxed);
+    let location = find(ptr).unwrap().escaped_at.unwrap();
+    assert_eq!(location.file(), file!());
+    assert_eq!(location.line(), line);
+
+    let mut report = Vec::new();
+    dump(&mut report).unwrap();
+    let report = String::from_utf8(report).unwrap();
+    let expected = format!("{:#x}: escaped from its MBox at {}", ptr, location);
+    assert!(report.contains(&expected));
+
+    // SAFETY: `raw` comes from `into_raw()`.
+    let mut boxed = unsafe { MBox::from_raw(raw) };
+    assert!(find(ptr).unwrap().escaped_at.is_none());
+
+    // the allocation keeps being tracked when it is moved by `realloc()`.
+    boxed.extend_from_slice(&[0; 1000]);
+    assert!(find(boxed.as_ptr() as usize).is_some());
+}
@@ -0,0 +1,93 @@
+//! Canaries detecting writes past the end of allocations.
+//!
+//! When the `canary` feature is enabled, every block is allocated with `CANARY_LEN` extra bytes,
+//! which are filled with a known pattern right after the requested size. The pattern is verified
+//! when the block is reallocated or freed, and a mismatch means somebody (usually C code) wrote
+//! past the end of the buffer.
+//!
+//! The block still starts at the pointer returned by `malloc()`, so it remains compatible with C's
+//! `free()`. The requested size of each block is remembered in a global table to locate the
+//! canary. Blocks not allocated by this crate are not in the table and are never checked.
+
+use libc::c_void;
+
+use std::collections::HashMap;
+use std::slice::from_raw_parts;
+use std::sync::Mutex;
+
+/// The number of extra bytes allocated after each block.
+pub const CANARY_LEN: usize = 16;
+
+/// The pattern written after each block.
+const CANARY: [u8; CANARY_LEN] =
+    *b"\xCA\xFE\xF0\x0D\xCA\xFE\xF0\x0D\xCA\xFE\xF0\x0D\xCA\xFE\xF0\x0D";
+
+/// The requested size of the live blocks, keyed by their address.
+static SIZES: Mutex<Option<HashMap<usize, usize>>> = Mutex::new(None);
+
+fn with_sizes<R>(f: impl FnOnce(&mut HashMap<usize, usize>) -> R) -> R {
+    // the table is still consistent even if another thread panicked while holding the lock.
+    let mut sizes = SIZES.lock().unwrap_or_else(|e| e.into_inner());
+    f(sizes.get_or_insert_with(HashMap::new))
+}
+
+/// Writes the canary after the first `size` bytes of the block, and remembers the size.
+///
+/// # Safety
+///
+/// The block must be valid for writing `size + CANARY_LEN` bytes.
+pub unsafe fn arm(ptr: *mut c_void, size: usize) {
+    let canary = (ptr as *mut u8).add(size);
+    canary.copy_from_nonoverlapping(CANARY.as_ptr(), CANARY_LEN);
+    with_sizes(|sizes| sizes.insert(ptr as usize, size));
+}
+
+/// Verifies the canary of a block about to be reallocated or freed, and forgets its size.
+///
+/// Returns the size the block was armed with, or `None` if it has no canary.
+///
+/// # Safety
+///
+/// The block must be still allocated.
+///
+/// # Panics
+///
+/// Panics if the canary has been overwritten.
+pub unsafe fn check(ptr: *mut c_void) -> Option<usize> {
+    let size = with_sizes(|sizes| sizes.remove(&(ptr as usize)))?;
+    let canary = from_raw_parts((ptr as *const u8).add(size), CANARY_LEN);
+    if canary != CANARY {
+        panic!(
+            "heap corruption detected: the {}-byte block at {:p} was written past its end",
+            size, ptr
+        );
+    }
+    Some(size)
+}
+
+#[test]
+fn test_canary() {
+    let mut slice = crate::mbox::MBox::from_slice(b"abc");
+    // SAFETY: the canary bytes are allocated.
+    unsafe { assert_eq!(from_raw_parts(slice.as_ptr().add(3), CANARY_LEN), CANARY) };
+    slice.extend_from_slice(b"defghijklmnopqrstuvwxyz");
+    slice.truncate(2);
+    assert_eq!(&*slice, b"ab");
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_canary_overflow() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let slice = crate::mbox::MBox::from_slice(b"abc");
+    let ptr = slice.as_ptr() as *mut u8;
+    // simulates an off-by-one write by C code, which lands in the canary.
+    // SAFETY: the canary bytes are allocated.
+    unsafe { *ptr.add(3) = 0 };
+    let error = catch_unwind(AssertUnwindSafe(|| drop(slice))).unwrap_err();
+    let message = error.downcast_ref::<String>().unwrap();
+    assert!(message.starts_with("heap corruption detected: the 3-byte block at "));
+    // SAFETY: the block has been forgotten by the canary table.
+    unsafe { libc::free(ptr as *mut c_void) };
+}
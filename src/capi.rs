@@ -0,0 +1,175 @@
+//! C API.
+//!
+//! C code linked with this crate can create and free buffers through the functions here, instead
+//! of calling `malloc()` and `free()` directly. The buffers thus go through the same checks as an
+//! `MBox` (e.g. `canary` or `track_ownership`), and can be passed to and from Rust in either
+//! direction. A header for these functions is generated into `OUT_DIR` by the build script when
+//! the `cbindgen` feature is enabled.
+//!
+//! ```c
+//! #include "mbox.h"
+//!
+//! MBoxBytes bytes = mbox_bytes_new(16);
+//! if (bytes.ptr != NULL) {
+//!     bytes.ptr[0] = 1;
+//!     mbox_bytes_free(bytes);
+//! }
+//! ```
+
+use libc::{c_char, strlen};
+
+use std::ptr::null_mut;
+use std::slice::from_raw_parts;
+use std::str::from_utf8;
+
+use crate::mbox::MBox;
+use crate::sentinel::MString;
+
+/// An owned byte buffer handed across the C API. It must be released by `mbox_bytes_free()`.
+///
+/// When allocation fails, `ptr` is null and `len` is 0.
+#[repr(C)]
+#[derive(Debug)]
+pub struct MBoxBytes {
+    /// The start of the buffer.
+    pub ptr: *mut u8,
+    /// The length of the buffer in bytes.
+    pub len: usize,
+}
+
+impl MBoxBytes {
+    /// The handle returned when allocation fails.
+    const NULL: MBoxBytes = MBoxBytes {
+        ptr: null_mut(),
+        len: 0,
+    };
+
+    /// Converts back into an `MBox<[u8]>`.
+    ///
+    /// # Safety
+    ///
+    /// The handle must be obtained from this module or from `MBoxBytes::from()`, must not be null,
+    /// and must not be used after this function returns.
+    pub unsafe fn into_mbox(self) -> MBox<[u8]> {
+        MBox::from_raw_parts(self.ptr, self.len)
+    }
+}
+
+impl From<MBox<[u8]>> for MBoxBytes {
+    fn from(boxed: MBox<[u8]>) -> Self {
+        let (ptr, len) = boxed.into_raw_parts();
+        Self { ptr, len }
+    }
+}
+
+/// Allocates a zeroed buffer of `len` bytes. Returns a null handle if the memory cannot be
+/// allocated.
+#[no_mangle]
+pub extern "C" fn mbox_bytes_new(len: usize) -> MBoxBytes {
+    match MBox::<[u8]>::try_new_zeroed_slice(len) {
+        // SAFETY: zero is a valid `u8`.
+        Ok(slice) => unsafe { slice.assume_init() }.into(),
+        Err(_) => MBoxBytes::NULL,
+    }
+}
+
+/// Allocates a buffer holding a copy of the `len` bytes at `data`. Returns a null handle if the
+/// memory cannot be allocated.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes. It may be null if `len` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn mbox_bytes_from_copy(data: *const u8, len: usize) -> MBoxBytes {
+    if len == 0 {
+        return MBox::<[u8]>::new_empty().into();
+    }
+    match MBox::try_from_slice(from_raw_parts(data, len)) {
+        Ok(slice) => slice.into(),
+        Err(_) => MBoxBytes::NULL,
+    }
+}
+
+/// Frees a buffer. Does nothing if the handle is null.
+///
+/// # Safety
+///
+/// The handle must be obtained from this module, and must not be used after this function
+/// returns.
+#[no_mangle]
+pub unsafe extern "C" fn mbox_bytes_free(bytes: MBoxBytes) {
+    if !bytes.ptr.is_null() {
+        drop(bytes.into_mbox());
+    }
+}
+
+/// Allocates a copy of the null-terminated string `string`. Returns null if `string` is null or
+/// not in valid UTF-8, or if the memory cannot be allocated.
+///
+/// # Safety
+///
+/// `string` must be null or point to a null-terminated string.
+// `c_char` from libc, see the MSRV note in lib.rs.
+#[allow(clippy::incompatible_msrv)]
+#[no_mangle]
+pub unsafe extern "C" fn mbox_string_new(string: *const c_char) -> *mut c_char {
+    if string.is_null() {
+        return null_mut();
+    }
+    let bytes = from_raw_parts(string as *const u8, strlen(string));
+    match from_utf8(bytes).map(MString::try_from_str) {
+        Ok(Ok(string)) => mbox_string_into_raw(string),
+        _ => null_mut(),
+    }
+}
+
+/// Frees a string. Does nothing if `string` is null.
+///
+/// # Safety
+///
+/// `string` must be null, or be obtained from this module or from `mbox_string_into_raw()`. It
+/// must not be used after this function returns.
+// `c_char` from libc, see the MSRV note in lib.rs.
+#[allow(clippy::incompatible_msrv)]
+#[no_mangle]
+pub unsafe extern "C" fn mbox_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(MString::from_raw_unchecked(string));
+    }
+}
+
+/// Converts an `MString` into a null-terminated string to be released by `mbox_string_free()`.
+// `c_char` from libc, see the MSRV note in lib.rs.
+#[allow(clippy::incompatible_msrv)]
+pub fn mbox_string_into_raw(string: MString) -> *mut c_char {
+    MBox::into_raw(string.into_mbox_with_sentinel()) as *mut u8 as *mut c_char
+}
+
+#[test]
+fn test_capi() {
+    unsafe {
+        let bytes = mbox_bytes_new(4);
+        assert_eq!(from_raw_parts(bytes.ptr, bytes.len), &[0; 4]);
+        mbox_bytes_free(bytes);
+
+        let bytes = mbox_bytes_from_copy(b"abc".as_ptr(), 3);
+        *bytes.ptr = b'x';
+        assert_eq!(&*bytes.into_mbox(), b"xbc");
+
+        let bytes = mbox_bytes_from_copy(null_mut(), 0);
+        assert_eq!(bytes.len, 0);
+        assert!(!bytes.ptr.is_null());
+        mbox_bytes_free(bytes);
+        mbox_bytes_free(MBoxBytes::NULL);
+        mbox_bytes_free(MBox::from_slice(&[1u8, 2]).into());
+
+        let string = mbox_string_new(b"hello\0".as_ptr() as *const c_char);
+        assert_eq!(strlen(string), 5);
+        assert_eq!(&*MString::from_raw_unchecked(string), "hello");
+        assert!(mbox_string_new(b"\xff\0".as_ptr() as *const c_char).is_null());
+        assert!(mbox_string_new(null_mut()).is_null());
+        mbox_string_free(null_mut());
+        mbox_string_free(mbox_string_into_raw(MString::new_empty()));
+        mbox_string_free(mbox_string_into_raw(MString::from("world")));
+    }
+}
@@ -0,0 +1,103 @@
+//! `tokio-util` codec support.
+//!
+//! `tokio_util::codec::FramedRead` accumulates the incoming bytes in a `BytesMut`, which is
+//! hard-coded in the `Decoder` trait. `MBoxCodec` wraps another codec, and copies every decoded
+//! frame into an `MBox<[u8]>` as soon as it is complete, so the frames handed to the application
+//! can be passed on to C code without further conversion.
+//!
+//! ```
+//! use mbox::codec::MBoxCodec;
+//! use tokio_util::bytes::BytesMut;
+//! use tokio_util::codec::{Decoder, LinesCodec};
+//!
+//! let mut codec = MBoxCodec::new(LinesCodec::new());
+//! let mut src = BytesMut::from(&b"hello\nworld"[..]);
+//! assert_eq!(&*codec.decode(&mut src).unwrap().unwrap(), b"hello");
+//! assert_eq!(codec.decode(&mut src).unwrap(), None);
+//! ```
+
+use tokio_util::bytes::BytesMut;
+use tokio_util::codec::{BytesCodec, Decoder, Encoder};
+
+use crate::mbox::MBox;
+
+/// A codec which decodes frames using the codec `D`, and returns them as `MBox<[u8]>`.
+///
+/// Encoding is forwarded to `D` unchanged. The default `BytesCodec` returns all bytes received so
+/// far as a single frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MBoxCodec<D = BytesCodec>(D);
+
+impl<D> MBoxCodec<D> {
+    /// Wraps the codec `inner`.
+    pub fn new(inner: D) -> Self {
+        MBoxCodec(inner)
+    }
+
+    /// Returns a reference to the wrapped codec.
+    pub fn get_ref(&self) -> &D {
+        &self.0
+    }
+
+    /// Returns a mutable reference to the wrapped codec.
+    pub fn get_mut(&mut self) -> &mut D {
+        &mut self.0
+    }
+
+    /// Returns the wrapped codec.
+    pub fn into_inner(self) -> D {
+        self.0
+    }
+}
+
+impl<D: Decoder> Decoder for MBoxCodec<D>
+where
+    D::Item: AsRef<[u8]>,
+{
+    type Item = MBox<[u8]>;
+    type Error = D::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<MBox<[u8]>>, D::Error> {
+        Ok(self
+            .0
+            .decode(src)?
+            .map(|frame| MBox::from_slice(frame.as_ref())))
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<MBox<[u8]>>, D::Error> {
+        Ok(self
+            .0
+            .decode_eof(src)?
+            .map(|frame| MBox::from_slice(frame.as_ref())))
+    }
+}
+
+impl<I, D: Encoder<I>> Encoder<I> for MBoxCodec<D> {
+    type Error = D::Error;
+
+    fn encode(&mut self, item: I, dst: &mut BytesMut) -> Result<(), D::Error> {
+        self.0.encode(item, dst)
+    }
+}
+
+#[test]
+fn test_mbox_codec() {
+    use tokio_util::bytes::{BufMut, Bytes};
+    use tokio_util::codec::LengthDelimitedCodec;
+
+    let mut codec = MBoxCodec::new(LengthDelimitedCodec::new());
+    let mut buf = BytesMut::new();
+    codec.encode(Bytes::from_static(b"abc"), &mut buf).unwrap();
+    codec.encode(Bytes::from_static(b""), &mut buf).unwrap();
+    buf.put_slice(b"\0\0\0\x05de");
+
+    assert_eq!(&*codec.decode(&mut buf).unwrap().unwrap(), b"abc");
+    assert!(codec.decode(&mut buf).unwrap().unwrap().is_empty());
+    assert_eq!(codec.decode(&mut buf).unwrap(), None);
+    assert!(codec.decode_eof(&mut buf).is_err());
+
+    let mut codec = MBoxCodec::<BytesCodec>::default();
+    let mut buf = BytesMut::from(&b"raw"[..]);
+    assert_eq!(&*codec.decode(&mut buf).unwrap().unwrap(), b"raw");
+    assert_eq!(codec.decode_eof(&mut buf).unwrap(), None);
+}
@@ -0,0 +1,568 @@
+//! I/O over malloc-backed byte buffers.
+//!
+//! `MCursor` is the `MBox<[u8]>` counterpart of `std::io::Cursor<Vec<u8>>`. It implements `Read`,
+//! `BufRead`, `Seek` and `Write`, so a buffer can be filled or consumed by any I/O API, and then
+//! handed to C code without copying. The cursor may own the box, or borrow it as `&mut MBox<[u8]>`.
+//!
+//! ```
+//! use mbox::cursor::MCursor;
+//! use mbox::MBox;
+//! use std::io::Write;
+//!
+//! let mut cursor = MCursor::new(MBox::<[u8]>::default());
+//! write!(cursor, "{} + {} = {}", 1, 2, 3).unwrap();
+//! assert_eq!(&*cursor.into_inner(), b"1 + 2 = 3");
+//! ```
+//!
+//! To read the data of a socket or a file directly into a new buffer, use `read_exact_to_mbox()` or
+//! `read_to_end_mbox()`. Several buffers can be written at once with `write_all_vectored()`, without
+//! concatenating them first. `MReadBuf` lends the uninitialized tail of a buffer to readers, while
+//! tracking which bytes are already initialized.
+
+use std::borrow::{Borrow, BorrowMut};
+use std::cmp::min;
+use std::convert::TryFrom;
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::io::{self, BufRead, IoSlice, Read, Seek, SeekFrom, Write};
+use std::mem::MaybeUninit;
+
+use crate::mbox::MBox;
+#[cfg(test)]
+use crate::sentinel::MString;
+
+/// A cursor over an `MBox<[u8]>`, which is either owned or borrowed mutably.
+///
+/// Writing past the end grows the buffer using `realloc()`. Like `Cursor<Vec<u8>>`, when the
+/// position is beyond the end, the gap is filled with zeros first.
+#[derive(Clone, Debug)]
+pub struct MCursor<B = MBox<[u8]>> {
+    inner: B,
+    pos: u64,
+}
+
+impl<B> MCursor<B> {
+    /// Creates a new cursor at the start of the buffer.
+    pub fn new(inner: B) -> Self {
+        Self { inner, pos: 0 }
+    }
+
+    /// Consumes the cursor and returns the buffer.
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+
+    /// Obtains a reference to the buffer.
+    pub fn get_ref(&self) -> &B {
+        &self.inner
+    }
+
+    /// Obtains a mutable reference to the buffer.
+    pub fn get_mut(&mut self) -> &mut B {
+        &mut self.inner
+    }
+
+    /// Returns the current position.
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// Sets the current position, which may be beyond the end.
+    pub fn set_position(&mut self, pos: u64) {
+        self.pos = pos;
+    }
+}
+
+impl<B: Borrow<MBox<[u8]>>> BufRead for MCursor<B> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let data: &[u8] = self.inner.borrow();
+        // a position beyond the end reads nothing.
+        let start = min(self.pos, data.len() as u64) as usize;
+        Ok(&data[start..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt as u64;
+    }
+}
+
+impl<B: Borrow<MBox<[u8]>>> Read for MCursor<B> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.fill_buf()?.read(buf)?;
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl<B: Borrow<MBox<[u8]>>> Seek for MCursor<B> {
+    fn seek(&mut self, style: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match style {
+            SeekFrom::Start(pos) => {
+                self.pos = pos;
+                return Ok(pos);
+            }
+            SeekFrom::End(offset) => (self.inner.borrow().len() as u64, offset),
+            SeekFrom::Current(offset) => (self.pos, offset),
+        };
+        let pos = if offset >= 0 {
+            base.checked_add(offset as u64)
+        } else {
+            base.checked_sub(offset.wrapping_neg() as u64)
+        };
+        match pos {
+            Some(pos) => {
+                self.pos = pos;
+                Ok(pos)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+impl<B: BorrowMut<MBox<[u8]>>> Write for MCursor<B> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "cursor position too large");
+        let pos = usize::try_from(self.pos).map_err(|_| too_large())?;
+        let end = pos.checked_add(buf.len()).ok_or_else(too_large)?;
+
+        let data = self.inner.borrow_mut();
+        if pos > data.len() {
+            data.resize(pos, 0);
+        }
+        // overwrite the existing bytes, and append the rest in a single `realloc()`.
+        let (head, tail) = buf.split_at(min(buf.len(), data.len() - pos));
+        data[pos..pos + head.len()].copy_from_slice(head);
+        if !tail.is_empty() {
+            data.extend_from_slice(tail);
+        }
+        self.pos = end as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reads exactly `len` bytes from `reader` into a new buffer.
+///
+/// Errors like `Read::read_exact()`, in which case the partially read buffer is discarded.
+pub fn read_exact_to_mbox<R: Read>(mut reader: R, len: usize) -> io::Result<MBox<[u8]>> {
+    // SAFETY: the bytes are initialized to zeros by `calloc()`.
+    let mut buffer = unsafe { MBox::<[u8]>::new_zeroed_slice(len).assume_init() };
+    reader.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Reads all bytes until EOF from `reader` into a new buffer.
+///
+/// The buffer grows using `realloc()` by doubling, and is shrunk to the exact length at the end.
+/// Errors like `Read::read_to_end()`, in which case the partially read buffer is discarded.
+pub fn read_to_end_mbox<R: Read>(reader: R) -> io::Result<MBox<[u8]>> {
+    read_to_end_sized(reader, 0)
+}
+
+/// Same as `read_to_end_mbox()`, but starts with a buffer of `size_hint` bytes. When exactly
+/// `size_hint` bytes are available, the buffer is never reallocated.
+pub(crate) fn read_to_end_sized<R: Read>(
+    mut reader: R,
+    size_hint: usize,
+) -> io::Result<MBox<[u8]>> {
+    // SAFETY: the bytes are initialized to zeros by `calloc()`.
+    let mut buffer = unsafe { MBox::<[u8]>::new_zeroed_slice(size_hint).assume_init() };
+    let mut len = 0;
+    loop {
+        let result = if len == buffer.len() {
+            // probe for EOF before growing, so a buffer of the right size is kept as is.
+            let mut probe = [0; 32];
+            reader.read(&mut probe).map(|n| {
+                if n != 0 {
+                    buffer.extend_from_slice(&probe[..n]);
+                    let new_len = buffer
+                        .len()
+                        .checked_mul(2)
+                        .expect("memory overflow")
+                        .max(32);
+                    buffer.resize(new_len, 0);
+                }
+                n
+            })
+        } else {
+            reader.read(&mut buffer[len..])
+        };
+        match result {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    buffer.truncate(len);
+    Ok(buffer)
+}
+
+/// Collects the buffers into an array of `IoSlice`s, to be passed to `Write::write_vectored()`.
+pub fn io_slices<'a, B: AsRef<[u8]>>(buffers: &'a [B]) -> MBox<[IoSlice<'a>]> {
+    buffers.iter().map(|b| IoSlice::new(b.as_ref())).collect()
+}
+
+/// The maximum number of buffers passed to a single `Write::write_vectored()` call. This is the
+/// smallest `IOV_MAX` allowed by POSIX.
+const MAX_IO_SLICES: usize = 16;
+
+/// Writes all the buffers in order using `Write::write_vectored()`, i.e. `writev()` for files and
+/// sockets, retrying until everything is written.
+///
+/// Errors like `Write::write_all()`.
+pub fn write_all_vectored<W: Write + ?Sized, B: AsRef<[u8]>>(
+    writer: &mut W,
+    buffers: &[B],
+) -> io::Result<()> {
+    // the position of the first unwritten byte.
+    let mut index = 0;
+    let mut offset = 0;
+    loop {
+        while index < buffers.len() && offset == buffers[index].as_ref().len() {
+            index += 1;
+            offset = 0;
+        }
+        if index == buffers.len() {
+            return Ok(());
+        }
+
+        let rest = &buffers[index..min(buffers.len(), index + MAX_IO_SLICES)];
+        // `IoSlice` is not `Copy` on the minimum supported Rust version, so the array can't be
+        // built with a repeat expression.
+        let e = IoSlice::new;
+        #[rustfmt::skip]
+        let mut slices: [IoSlice<'_>; MAX_IO_SLICES] = [
+            e(&[]), e(&[]), e(&[]), e(&[]), e(&[]), e(&[]), e(&[]), e(&[]),
+            e(&[]), e(&[]), e(&[]), e(&[]), e(&[]), e(&[]), e(&[]), e(&[]),
+        ];
+        for (slice, buffer) in slices.iter_mut().zip(rest) {
+            *slice = IoSlice::new(buffer.as_ref());
+        }
+        slices[0] = IoSlice::new(&buffers[index].as_ref()[offset..]);
+
+        match writer.write_vectored(&slices[..rest.len()]) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "failed to write whole buffer",
+                ));
+            }
+            Ok(mut written) => {
+                while written > 0 {
+                    let remaining = buffers[index].as_ref().len() - offset;
+                    if written < remaining {
+                        offset += written;
+                        break;
+                    }
+                    written -= remaining;
+                    index += 1;
+                    offset = 0;
+                }
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// A malloc'd byte buffer which is filled by readers without being zeroed on every read.
+///
+/// This is a stable equivalent of `std::io::BorrowedBuf` which owns its `MBox<[MaybeUninit<u8>]>`.
+/// It tracks the filled prefix, and how far beyond it the bytes are already initialized.
+/// `read_from()` only zeroes the unfilled tail once, so reading repeatedly into a cleared buffer
+/// does not initialize it again. Code writing into uninitialized memory, e.g. a C function, can use
+/// `unfilled_mut()` and `advance()` to skip the zeroing entirely.
+pub struct MReadBuf {
+    buf: MBox<[MaybeUninit<u8>]>,
+    filled: usize,
+    init: usize,
+}
+
+impl MReadBuf {
+    /// Wraps an uninitialized buffer, which starts empty.
+    pub fn new(buf: MBox<[MaybeUninit<u8>]>) -> Self {
+        Self {
+            buf,
+            filled: 0,
+            init: 0,
+        }
+    }
+
+    /// Allocates an uninitialized buffer of `capacity` bytes.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::new(MBox::new_uninit_slice(capacity))
+    }
+
+    /// Returns the total size of the buffer.
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Returns the number of filled bytes.
+    pub fn len(&self) -> usize {
+        self.filled
+    }
+
+    /// Returns whether no bytes are filled.
+    pub fn is_empty(&self) -> bool {
+        self.filled == 0
+    }
+
+    /// Returns the number of initialized bytes, which is at least `len()`.
+    pub fn init_len(&self) -> usize {
+        self.init
+    }
+
+    /// Returns the filled bytes.
+    pub fn filled(&self) -> &[u8] {
+        // SAFETY: the filled bytes are initialized.
+        unsafe { &*(&self.buf[..self.filled] as *const [MaybeUninit<u8>] as *const [u8]) }
+    }
+
+    /// Returns the filled bytes mutably.
+    pub fn filled_mut(&mut self) -> &mut [u8] {
+        // SAFETY: the filled bytes are initialized.
+        unsafe { &mut *(&mut self.buf[..self.filled] as *mut [MaybeUninit<u8>] as *mut [u8]) }
+    }
+
+    /// Empties the buffer. The bytes stay initialized, so they are not zeroed again by the next
+    /// read.
+    pub fn clear(&mut self) {
+        self.filled = 0;
+    }
+
+    /// Returns the unfilled tail of the buffer, which may be uninitialized.
+    ///
+    /// # Safety
+    ///
+    /// The caller must not write uninitialized bytes into the slice, as its first
+    /// `init_len() - len()` bytes are considered initialized.
+    pub unsafe fn unfilled_mut(&mut self) -> &mut [MaybeUninit<u8>] {
+        &mut self.buf[self.filled..]
+    }
+
+    /// Marks the first `n` bytes of the unfilled tail as filled.
+    ///
+    /// # Safety
+    ///
+    /// The first `n` bytes of `unfilled_mut()` must have been initialized.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` exceeds the length of the unfilled tail.
+    pub unsafe fn advance(&mut self, n: usize) {
+        assert!(n <= self.capacity() - self.filled, "advance out of bounds");
+        self.filled += n;
+        self.init = self.init.max(self.filled);
+    }
+
+    /// Reads once from `reader` into the unfilled tail, and returns the number of bytes read.
+    ///
+    /// The uninitialized part of the tail is zeroed first, since `Read::read()` only accepts an
+    /// initialized buffer. This happens at most once over the lifetime of the buffer.
+    pub fn read_from<R: Read + ?Sized>(&mut self, reader: &mut R) -> io::Result<usize> {
+        let capacity = self.capacity();
+        for byte in &mut self.buf[self.init..] {
+            *byte = MaybeUninit::new(0);
+        }
+        self.init = capacity;
+        // SAFETY: the whole tail has just been initialized.
+        let unfilled =
+            unsafe { &mut *(&mut self.buf[self.filled..] as *mut [MaybeUninit<u8>] as *mut [u8]) };
+        let n = reader.read(unfilled)?;
+        assert!(
+            n <= capacity - self.filled,
+            "reader returned too many bytes"
+        );
+        self.filled += n;
+        Ok(n)
+    }
+
+    /// Returns the filled bytes, shrinking the allocation to them using `realloc()`.
+    pub fn into_filled(self) -> MBox<[u8]> {
+        let mut buf = self.buf;
+        buf.truncate(self.filled);
+        // SAFETY: the filled bytes are initialized.
+        unsafe { buf.assume_init() }
+    }
+
+    /// Returns the whole underlying buffer.
+    pub fn into_inner(self) -> MBox<[MaybeUninit<u8>]> {
+        self.buf
+    }
+}
+
+impl Debug for MReadBuf {
+    fn fmt(&self, formatter: &mut Formatter) -> FmtResult {
+        formatter
+            .debug_struct("MReadBuf")
+            .field("filled", &self.filled())
+            .field("init", &self.init)
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+#[test]
+fn test_cursor_read() {
+    let mut cursor = MCursor::new(MBox::from_slice(b"hello\nworld"));
+    let mut line = String::new();
+    cursor.read_line(&mut line).unwrap();
+    assert_eq!(line, "hello\n");
+    assert_eq!(cursor.position(), 6);
+
+    let mut buf = [0; 3];
+    cursor.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"wor");
+    assert_eq!(cursor.seek(SeekFrom::End(-1)).unwrap(), 10);
+    let mut rest = Vec::new();
+    cursor.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, b"d");
+
+    cursor.set_position(100);
+    assert_eq!(cursor.read(&mut buf).unwrap(), 0);
+    assert!(cursor.seek(SeekFrom::Current(-101)).is_err());
+    assert_eq!(cursor.position(), 100);
+}
+
+#[test]
+fn test_cursor_write() {
+    let mut boxed = MBox::from_slice(b"abcdef");
+    {
+        let mut cursor = MCursor::new(&mut boxed);
+        cursor.seek(SeekFrom::Start(4)).unwrap();
+        cursor.write_all(b"EFGH").unwrap();
+        assert_eq!(cursor.position(), 8);
+        cursor.seek(SeekFrom::Current(2)).unwrap();
+        cursor.write_all(b"!").unwrap();
+        cursor.set_position(0);
+        cursor.write_all(b"A").unwrap();
+        cursor.flush().unwrap();
+    }
+    assert_eq!(&*boxed, b"AbcdEFGH\0\0!");
+}
+
+/// A reader returning at most 7 bytes at a time, and being interrupted before each read.
+#[cfg(test)]
+struct Trickle<'a> {
+    data: &'a [u8],
+    interrupted: bool,
+}
+
+#[cfg(test)]
+impl<'a> Read for Trickle<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.interrupted = !self.interrupted;
+        if self.interrupted {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted"));
+        }
+        let len = min(min(buf.len(), 7), self.data.len());
+        buf[..len].copy_from_slice(&self.data[..len]);
+        self.data = &self.data[len..];
+        Ok(len)
+    }
+}
+
+#[test]
+fn test_read_to_mbox() {
+    let data = (0..100).collect::<Vec<u8>>();
+    let trickle = || Trickle {
+        data: &data,
+        interrupted: false,
+    };
+
+    assert_eq!(&*read_to_end_mbox(trickle()).unwrap(), &*data);
+    assert_eq!(&*read_to_end_mbox(io::empty()).unwrap(), b"");
+    assert_eq!(&*read_exact_to_mbox(trickle(), 40).unwrap(), &data[..40]);
+    assert_eq!(&*read_exact_to_mbox(io::empty(), 0).unwrap(), b"");
+
+    let error = read_exact_to_mbox(trickle(), 101).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+}
+
+/// A writer accepting at most 5 bytes per call.
+#[cfg(test)]
+struct Narrow(Vec<u8>);
+
+#[cfg(test)]
+impl Write for Narrow {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = min(buf.len(), 5);
+        self.0.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_write_all_vectored() {
+    let parts = (0..40u8)
+        .map(|i| MBox::from_slice(&[i; 3][..(i % 4) as usize]))
+        .collect::<Vec<_>>();
+    let expected = parts.concat();
+
+    let mut output = Vec::new();
+    let written = output.write_vectored(&io_slices(&parts)).unwrap();
+    assert_eq!(written, expected.len());
+    assert_eq!(output, expected);
+
+    let mut narrow = Narrow(Vec::new());
+    write_all_vectored(&mut narrow, &parts).unwrap();
+    assert_eq!(narrow.0, expected);
+
+    let mut cursor = MCursor::new(MBox::<[u8]>::default());
+    write_all_vectored(&mut cursor, &[MString::from("ab"), MString::from("cd")]).unwrap();
+    assert_eq!(&*cursor.into_inner(), b"abcd");
+
+    let mut full = [0u8; 3];
+    let error = write_all_vectored(&mut &mut full[..], &parts).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::WriteZero);
+}
+
+#[test]
+fn test_read_buf() {
+    let mut buf = MReadBuf::with_capacity(8);
+    assert!(buf.is_empty());
+    assert_eq!(buf.init_len(), 0);
+
+    let mut reader: &[u8] = b"hello world";
+    assert_eq!(buf.read_from(&mut (&mut reader).take(3)).unwrap(), 3);
+    assert_eq!(buf.filled(), b"hel");
+    assert_eq!(buf.init_len(), 8);
+    assert_eq!(buf.read_from(&mut reader).unwrap(), 5);
+    assert_eq!(buf.filled(), b"hello wo");
+    assert_eq!(buf.read_from(&mut reader).unwrap(), 0);
+
+    buf.clear();
+    assert_eq!(buf.init_len(), 8);
+    // SAFETY: the bytes are initialized before advancing.
+    unsafe {
+        let unfilled = buf.unfilled_mut();
+        unfilled[0] = MaybeUninit::new(b'H');
+        buf.advance(1);
+    }
+    buf.filled_mut()[0] = b'J';
+    assert_eq!(buf.read_from(&mut reader).unwrap(), 3);
+    assert_eq!(&*buf.into_filled(), b"Jrld");
+
+    let mut buf = MReadBuf::with_capacity(4);
+    // SAFETY: the bytes are initialized before advancing.
+    unsafe {
+        for byte in &mut buf.unfilled_mut()[..2] {
+            *byte = MaybeUninit::new(1);
+        }
+        buf.advance(2);
+    }
+    assert_eq!(buf.len(), 2);
+    assert_eq!(buf.init_len(), 2);
+    assert_eq!(buf.into_inner().len(), 4);
+}
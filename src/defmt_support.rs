@@ -0,0 +1,114 @@
+//! `defmt` support.
+//!
+//! The values are formatted like their `Display` implementations, so they can be logged over RTT
+//! without converting them into strings first.
+
+use defmt::{Format, Formatter};
+
+use crate::error::{AllocError, FromUtf8Error};
+use crate::mbox::MBox;
+use crate::sentinel::MString;
+
+impl Format for MBox<[u8]> {
+    fn format(&self, formatter: Formatter) {
+        defmt::write!(formatter, "{=[u8]}", &**self)
+    }
+}
+
+impl Format for MBox<str> {
+    fn format(&self, formatter: Formatter) {
+        defmt::write!(formatter, "{=str}", &**self)
+    }
+}
+
+impl Format for MString {
+    fn format(&self, formatter: Formatter) {
+        defmt::write!(formatter, "{=str}", &**self)
+    }
+}
+
+impl Format for AllocError {
+    fn format(&self, formatter: Formatter) {
+        match *self {
+            AllocError::CapacityOverflow => defmt::write!(formatter, "memory overflow"),
+            AllocError::OutOfMemory(size) => {
+                defmt::write!(
+                    formatter,
+                    "memory allocation of {=usize} bytes failed",
+                    size
+                )
+            }
+        }
+    }
+}
+
+impl Format for FromUtf8Error {
+    fn format(&self, formatter: Formatter) {
+        let error = self.utf8_error();
+        match error.error_len() {
+            Some(len) => defmt::write!(
+                formatter,
+                "invalid utf-8 sequence of {=u8} bytes from index {=usize}",
+                len as u8,
+                error.valid_up_to()
+            ),
+            None => defmt::write!(
+                formatter,
+                "incomplete utf-8 byte sequence from index {=usize}",
+                error.valid_up_to()
+            ),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test_logger {
+    use std::sync::Mutex;
+
+    /// The bytes written by `defmt` in the test, which are encoded and cannot be compared with
+    /// text.
+    pub static BYTES: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+    #[defmt::global_logger]
+    struct Logger;
+
+    unsafe impl defmt::Logger for Logger {
+        fn acquire() {}
+        unsafe fn flush() {}
+        unsafe fn release() {}
+        unsafe fn write(bytes: &[u8]) {
+            BYTES.lock().unwrap().extend_from_slice(bytes);
+        }
+    }
+
+    defmt::timestamp!("");
+
+    #[defmt::panic_handler]
+    fn panic() -> ! {
+        panic!("defmt panic")
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_defmt() {
+    use std::convert::TryFrom;
+
+    let len = || test_logger::BYTES.lock().unwrap().len();
+
+    let before = len();
+    defmt::println!("{}", MString::from("abc"));
+    let string_len = len() - before;
+
+    let before = len();
+    defmt::println!("{}", MString::from("abcdef"));
+    assert_eq!(len() - before, string_len + 3);
+
+    let before = len();
+    defmt::println!("{}", MBox::from_slice(b"abc"));
+    assert!(len() - before >= 3);
+
+    defmt::println!("{}", AllocError::OutOfMemory(12));
+    let error = MBox::<str>::try_from(MBox::from_slice(b"\xff")).unwrap_err();
+    defmt::println!("{}", error);
+}
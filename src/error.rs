@@ -0,0 +1,101 @@
+//! Error types.
+//!
+//! All errors implement `Display` and `Error`. Without the `std` feature, `Error` is taken from
+//! `core::error`, which is only implemented when building with Rust 1.81 or above.
+
+#[cfg(any(feature = "std", mbox_core_error))]
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FormatResult};
+use std::str::Utf8Error;
+
+use crate::mbox::MBox;
+
+/// The error returned by fallible allocations such as `MBox::try_new()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AllocError {
+    /// The requested number of bytes does not fit in `usize`.
+    CapacityOverflow,
+    /// `malloc()` returned NULL when requesting this number of bytes.
+    OutOfMemory(usize),
+}
+
+impl Display for AllocError {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        match *self {
+            AllocError::CapacityOverflow => formatter.write_str("memory overflow"),
+            AllocError::OutOfMemory(size) => {
+                write!(formatter, "memory allocation of {} bytes failed", size)
+            }
+        }
+    }
+}
+
+#[cfg(any(feature = "std", mbox_core_error))]
+impl Error for AllocError {}
+
+/// The error returned when converting an `MBox<[u8]>` into an `MBox<str>` fails.
+///
+/// Unlike a plain `Utf8Error`, this error owns the original bytes, so they are not lost.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FromUtf8Error {
+    pub(crate) bytes: MBox<[u8]>,
+    pub(crate) error: Utf8Error,
+}
+
+impl FromUtf8Error {
+    /// Returns the bytes which failed to be converted.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the bytes which failed to be converted, without copying.
+    pub fn into_bytes(self) -> MBox<[u8]> {
+        self.bytes
+    }
+
+    /// Returns the underlying UTF-8 error, which tells where the invalid sequence is.
+    pub fn utf8_error(&self) -> Utf8Error {
+        self.error
+    }
+}
+
+impl Display for FromUtf8Error {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        Display::fmt(&self.error, formatter)
+    }
+}
+
+#[cfg(any(feature = "std", mbox_core_error))]
+impl Error for FromUtf8Error {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+#[cfg(test)]
+use std::convert::TryFrom;
+
+#[test]
+fn test_alloc_error_display() {
+    assert_eq!(
+        mformat!("{}", AllocError::CapacityOverflow),
+        "memory overflow"
+    );
+    assert_eq!(
+        mformat!("{}", AllocError::OutOfMemory(12)),
+        "memory allocation of 12 bytes failed"
+    );
+}
+
+#[test]
+fn test_from_utf8_error() {
+    let error = MBox::<str>::try_from(MBox::from_slice(b"ab\xffc")).unwrap_err();
+    assert_eq!(error.as_bytes(), b"ab\xffc");
+    assert_eq!(error.utf8_error().valid_up_to(), 2);
+    #[cfg(any(feature = "std", mbox_core_error))]
+    assert!(error.source().is_some());
+    assert_eq!(error.into_bytes(), MBox::from_slice(b"ab\xffc"));
+
+    let string = MBox::<str>::try_from(MBox::from_slice(b"abc")).unwrap();
+    assert_eq!(&*string, "abc");
+}
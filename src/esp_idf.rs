@@ -0,0 +1,190 @@
+//! Allocation from ESP-IDF memory regions with specific capabilities.
+//!
+//! ESP32 chips have several memory regions, e.g. internal RAM reachable by DMA and external PSRAM,
+//! and some buffers must be placed in a particular one to be usable by a peripheral. The
+//! constructors here allocate through `heap_caps_aligned_alloc()`, which picks a region having all
+//! the requested capabilities. The result is an ordinary `MBox`, since such blocks can be released
+//! by `free()` on ESP-IDF 5.0 or later.
+//!
+//! ```no_run
+//! use mbox::esp_idf::HeapCaps;
+//! use mbox::MBox;
+//!
+//! // a zeroed DMA-capable buffer for an SPI transfer.
+//! let buffer = MBox::<[u8]>::try_new_zeroed_slice_with_caps(4096, HeapCaps::DMA | HeapCaps::BIT8)
+//!     .expect("out of DMA memory");
+//! ```
+
+use libc::c_void;
+
+use std::mem::{align_of, size_of, MaybeUninit};
+use std::ops::{BitOr, BitOrAssign};
+use std::ptr::{write, NonNull};
+
+use crate::error::AllocError;
+use crate::internal::{allocation_size, finish_alloc};
+use crate::mbox::MBox;
+
+extern "C" {
+    fn heap_caps_aligned_alloc(alignment: usize, size: usize, caps: u32) -> *mut c_void;
+    fn heap_caps_aligned_calloc(alignment: usize, n: usize, size: usize, caps: u32) -> *mut c_void;
+}
+
+/// A set of `MALLOC_CAP_*` flags, which the memory region of an allocation must all support.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct HeapCaps(u32);
+
+impl HeapCaps {
+    /// `MALLOC_CAP_EXEC`: the memory can contain executable code.
+    pub const EXEC: HeapCaps = HeapCaps(1 << 0);
+    /// `MALLOC_CAP_32BIT`: the memory allows 32-bit aligned accesses.
+    pub const BIT32: HeapCaps = HeapCaps(1 << 1);
+    /// `MALLOC_CAP_8BIT`: the memory allows byte accesses.
+    pub const BIT8: HeapCaps = HeapCaps(1 << 2);
+    /// `MALLOC_CAP_DMA`: the memory can be accessed by DMA.
+    pub const DMA: HeapCaps = HeapCaps(1 << 3);
+    /// `MALLOC_CAP_SPIRAM`: the memory is in external SPI RAM.
+    pub const SPIRAM: HeapCaps = HeapCaps(1 << 10);
+    /// `MALLOC_CAP_INTERNAL`: the memory is in internal RAM.
+    pub const INTERNAL: HeapCaps = HeapCaps(1 << 11);
+    /// `MALLOC_CAP_DEFAULT`: the memory can be returned by a plain `malloc()`.
+    pub const DEFAULT: HeapCaps = HeapCaps(1 << 12);
+
+    /// Creates a set from the raw `MALLOC_CAP_*` bits, including those without a constant here.
+    pub const fn from_bits(bits: u32) -> HeapCaps {
+        HeapCaps(bits)
+    }
+
+    /// Returns the raw `MALLOC_CAP_*` bits.
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+}
+
+impl BitOr for HeapCaps {
+    type Output = HeapCaps;
+    fn bitor(self, other: HeapCaps) -> HeapCaps {
+        HeapCaps(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for HeapCaps {
+    fn bitor_assign(&mut self, other: HeapCaps) {
+        self.0 |= other.0;
+    }
+}
+
+/// Allocates `count` items of type `T` in a region having the capabilities `caps`.
+fn try_gen_malloc_with_caps<T>(
+    count: usize,
+    caps: HeapCaps,
+    zeroed: bool,
+) -> Result<NonNull<T>, AllocError> {
+    let allocated_size = allocation_size::<T>(count)?;
+    let align = align_of::<T>().max(size_of::<*mut ()>());
+    // SAFETY: allocating should be safe. The block can be freed by `free()`.
+    unsafe {
+        // ensure the size > 0, the same as `gen_malloc()`.
+        let res = if zeroed {
+            heap_caps_aligned_calloc(align, 1, allocated_size.max(1), caps.0)
+        } else {
+            heap_caps_aligned_alloc(align, allocated_size.max(1), caps.0)
+        };
+        finish_alloc(res, count, allocated_size)
+    }
+}
+
+impl<T> MBox<T> {
+    /// Constructs a new box in a memory region having the capabilities `caps`, and moves `value`
+    /// into it. Returns an `AllocError` if no such region has enough free memory.
+    pub fn try_new_with_caps(value: T, caps: HeapCaps) -> Result<Self, AllocError> {
+        let storage = try_gen_malloc_with_caps(1, caps, false)?;
+        // SAFETY: same as `new()`.
+        unsafe {
+            write(storage.as_ptr(), value);
+            Ok(Self::from_non_null_raw(storage))
+        }
+    }
+}
+
+impl<T> MBox<[T]> {
+    /// Constructs a new boxed slice with uninitialized contents in a memory region having the
+    /// capabilities `caps`. Returns an `AllocError` if no such region has enough free memory.
+    pub fn try_new_uninit_slice_with_caps(
+        len: usize,
+        caps: HeapCaps,
+    ) -> Result<MBox<[MaybeUninit<T>]>, AllocError> {
+        let ptr = try_gen_malloc_with_caps(len, caps, false)?;
+        // SAFETY: `ptr` is allocated to hold `len` items, which are allowed to be uninitialized.
+        unsafe { Ok(MBox::from_raw_parts(ptr.as_ptr(), len)) }
+    }
+
+    /// Constructs a new boxed slice with the contents filled with zeros in a memory region having
+    /// the capabilities `caps`. Returns an `AllocError` if no such region has enough free memory.
+    pub fn try_new_zeroed_slice_with_caps(
+        len: usize,
+        caps: HeapCaps,
+    ) -> Result<MBox<[MaybeUninit<T>]>, AllocError> {
+        let ptr = try_gen_malloc_with_caps(len, caps, true)?;
+        // SAFETY: `ptr` is allocated to hold `len` items, which are allowed to be uninitialized.
+        unsafe { Ok(MBox::from_raw_parts(ptr.as_ptr(), len)) }
+    }
+}
+
+/// Stand-ins for the ESP-IDF heap, so the constructors can be tested on the host. They fail when
+/// `SPIRAM` is requested, as if the chip had no external RAM.
+#[cfg(all(test, unix))]
+mod heap_caps_shim {
+    use super::HeapCaps;
+    use libc::c_void;
+    use std::ptr::{null_mut, write_bytes};
+
+    #[no_mangle]
+    unsafe extern "C" fn heap_caps_aligned_alloc(
+        alignment: usize,
+        size: usize,
+        caps: u32,
+    ) -> *mut c_void {
+        if caps & HeapCaps::SPIRAM.bits() != 0 {
+            return null_mut();
+        }
+        let mut result = null_mut();
+        libc::posix_memalign(&mut result, alignment, size);
+        result
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn heap_caps_aligned_calloc(
+        alignment: usize,
+        n: usize,
+        size: usize,
+        caps: u32,
+    ) -> *mut c_void {
+        let result = heap_caps_aligned_alloc(alignment, n * size, caps);
+        if !result.is_null() {
+            write_bytes(result as *mut u8, 0, n * size);
+        }
+        result
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_heap_caps() {
+    let caps = HeapCaps::DMA | HeapCaps::BIT8;
+    assert_eq!(caps.bits(), 0b1100);
+
+    let boxed = MBox::try_new_with_caps(7u64, caps).unwrap();
+    assert_eq!(*boxed, 7);
+
+    let slice = MBox::<[u32]>::try_new_zeroed_slice_with_caps(16, caps).unwrap();
+    assert_eq!(&*unsafe { slice.assume_init() }, &[0; 16]);
+
+    let slice = MBox::<[u8]>::try_new_uninit_slice_with_caps(16, HeapCaps::INTERNAL).unwrap();
+    assert_eq!(slice.len(), 16);
+
+    assert!(matches!(
+        MBox::<[u8]>::try_new_uninit_slice_with_caps(16, HeapCaps::SPIRAM),
+        Err(AllocError::OutOfMemory(_))
+    ));
+}
@@ -0,0 +1,75 @@
+//! Reading whole files into malloc-backed buffers.
+//!
+//! These mirror `std::fs::read()` and `std::fs::read_to_string()`. The buffer is allocated from the
+//! size in the file's metadata, and the content is read directly into it.
+
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use crate::cursor::read_to_end_sized;
+use crate::internal::from_utf8;
+use crate::mbox::MBox;
+use crate::sentinel::MString;
+
+/// Opens the file at `path` and reads its whole content.
+fn read_sized(path: &Path, extra: usize) -> io::Result<MBox<[u8]>> {
+    let file = File::open(path)?;
+    // the size is only a hint, since the file may change while it is read.
+    let size = file
+        .metadata()
+        .ok()
+        .and_then(|m| usize::try_from(m.len()).ok())
+        .unwrap_or(0);
+    read_to_end_sized(file, size.saturating_add(extra))
+}
+
+/// Reads the whole content of a file into a new buffer.
+pub fn read<P: AsRef<Path>>(path: P) -> io::Result<MBox<[u8]>> {
+    read_sized(path.as_ref(), 0)
+}
+
+/// Reads the whole content of a file into a new null-terminated string. Errors with
+/// `io::ErrorKind::InvalidData` if the content is not valid UTF-8.
+pub fn read_to_mstring<P: AsRef<Path>>(path: P) -> io::Result<MString> {
+    // one more byte is reserved for the sentinel.
+    let mut bytes = read_sized(path.as_ref(), 1)?;
+    if from_utf8(&bytes).is_err() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "stream did not contain valid UTF-8",
+        ));
+    }
+    bytes.extend_from_slice(&[0]);
+    // SAFETY: the content is valid UTF-8, followed by the sentinel.
+    unsafe {
+        Ok(MString::from_mbox_with_sentinel_unchecked(
+            MBox::from_utf8_unchecked(bytes),
+        ))
+    }
+}
+
+#[cfg(not(miri))]
+#[test]
+fn test_read() {
+    let path = std::env::temp_dir().join(format!("mbox-fs-test-{}", std::process::id()));
+    std::fs::write(&path, "hello, wörld").unwrap();
+    assert_eq!(&*read(&path).unwrap(), "hello, wörld".as_bytes());
+    let string = read_to_mstring(&path).unwrap();
+    assert_eq!(string.as_bytes_with_sentinel(), "hello, wörld\0".as_bytes());
+
+    std::fs::write(&path, b"").unwrap();
+    assert_eq!(&*read(&path).unwrap(), b"");
+    assert_eq!(
+        read_to_mstring(&path).unwrap().as_bytes_with_sentinel(),
+        b"\0"
+    );
+
+    std::fs::write(&path, b"\xff").unwrap();
+    let error = read_to_mstring(&path).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(read(&path).unwrap_err().kind(), io::ErrorKind::NotFound);
+}
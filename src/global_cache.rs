@@ -0,0 +1,149 @@
+//! Global cache of freed blocks shared by all threads.
+//!
+//! When the `global_cache` feature is enabled, blocks of at most `MAX_CACHED_SIZE` bytes released
+//! by `gen_free()` are kept in a global free list grouped by size class, and reused by
+//! `gen_malloc()` on any thread. Unlike the thread-local cache, a buffer freed by one thread can
+//! be reused by another, so a service passing fixed-size buffers between threads does not fill
+//! one cache while draining another.
+//!
+//! Each size class is an array of atomic slots. A block is given by storing it into an empty slot
+//! with a compare-exchange, and taken by swapping a slot with NULL, so the cache never locks and
+//! is immune to the ABA problem. Threads start scanning at different slots depending on their
+//! stack address, which spreads them over the array.
+//!
+//! The blocks are still genuine `malloc()` blocks, so a pointer obtained from the cache can be
+//! passed to C's `free()` as usual. The size of a block is found by `malloc_usable_size()` or
+//! similar. On platforms without such a function, nothing is cached.
+
+use libc::c_void;
+
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+use crate::internal::{free_raw, usable_size};
+
+/// The number of size classes, which are the powers of two from 16 bytes to `MAX_CACHED_SIZE`.
+const CLASSES: usize = 13;
+
+/// The largest block size which would be cached.
+const MAX_CACHED_SIZE: usize = 16 << (CLASSES - 1);
+
+/// The maximum number of blocks kept in each size class.
+const SLOTS_PER_CLASS: usize = 32;
+
+/// The alignment guaranteed by the cached blocks. Requests of larger alignment bypass the cache.
+const MAX_CACHED_ALIGN: usize = 16;
+
+struct FreeList {
+    slots: [AtomicPtr<c_void>; SLOTS_PER_CLASS],
+}
+
+// the constants are only used to initialize `CACHE`, as array repeat expressions need them.
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_SLOT: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_FREE_LIST: FreeList = FreeList {
+    slots: [EMPTY_SLOT; SLOTS_PER_CLASS],
+};
+
+static CACHE: [FreeList; CLASSES] = [EMPTY_FREE_LIST; CLASSES];
+
+/// Returns the size of the given size class.
+fn class_size(class: usize) -> usize {
+    16 << class
+}
+
+/// Returns the slot where the current thread starts scanning. Thread stacks are far apart, so the
+/// address of a local variable tells threads apart well enough.
+fn first_slot() -> usize {
+    let marker = 0u8;
+    let address = &marker as *const u8 as usize;
+    (address >> 16).wrapping_mul(0x9E37_79B9) % SLOTS_PER_CLASS
+}
+
+/// Iterates over all slots of a free list, starting at the one assigned to the current thread.
+fn slots(list: &FreeList) -> impl Iterator<Item = &AtomicPtr<c_void>> {
+    let (tail, head) = list.slots.split_at(first_slot());
+    head.iter().chain(tail)
+}
+
+/// Takes a cached block which can hold `size` bytes aligned to `align`.
+pub fn take(size: usize, align: usize) -> Option<*mut c_void> {
+    if size > MAX_CACHED_SIZE || align > MAX_CACHED_ALIGN {
+        return None;
+    }
+    let class = (0..CLASSES).find(|&class| class_size(class) >= size)?;
+    for slot in slots(&CACHE[class]) {
+        if slot.load(Ordering::Relaxed).is_null() {
+            continue;
+        }
+        let block = slot.swap(null_mut(), Ordering::Acquire);
+        if block.is_null() {
+            continue;
+        }
+        if block as usize % align == 0 {
+            return Some(block);
+        }
+        // SAFETY: a block not satisfying the alignment is simply released.
+        unsafe { free_raw(block) };
+    }
+    None
+}
+
+/// Puts a freed block into the cache. Returns `false` if the block is not cached, in which case
+/// the caller should `free()` it.
+///
+/// # Safety
+///
+/// The `ptr` must be obtained from `malloc()` or similar C functions. Its ownership is transferred
+/// to the cache if this function returns `true`.
+pub unsafe fn give(ptr: *mut c_void) -> bool {
+    let size = usable_size(ptr);
+    if size < class_size(0) {
+        return false;
+    }
+    let class = match (0..CLASSES).rev().find(|&class| class_size(class) <= size) {
+        Some(class) => class,
+        None => return false,
+    };
+    slots(&CACHE[class]).any(|slot| {
+        slot.compare_exchange(null_mut(), ptr, Ordering::Release, Ordering::Relaxed)
+            .is_ok()
+    })
+}
+
+#[cfg(test)]
+use crate::mbox::MBox;
+
+// the canaries change the block sizes, which may fall into different size classes, and the
+// quarantine delays the blocks from entering the cache.
+#[cfg(all(
+    feature = "std",
+    not(miri),
+    not(feature = "canary"),
+    not(feature = "quarantine"),
+    any(all(target_os = "linux", target_env = "gnu"), target_os = "macos")
+))]
+#[test]
+fn test_reuse_across_threads() {
+    // the largest class is rarely used by other tests, which could steal the block.
+    let block = MBox::<[u8]>::from_elem(1, MAX_CACHED_SIZE);
+    let block_ptr = MBox::as_ptr(&block) as *const u8 as usize;
+    std::thread::spawn(move || drop(block)).join().unwrap();
+
+    let reused = MBox::<[u8]>::from_elem(2, MAX_CACHED_SIZE);
+    assert_eq!(MBox::as_ptr(&reused) as *const u8 as usize, block_ptr);
+    assert!(reused.iter().all(|&b| b == 2));
+
+    // a cached block can still be released by `free()` directly.
+    unsafe { libc::free(MBox::into_raw(reused) as *mut c_void) };
+}
+
+#[test]
+fn test_bypass_global_cache() {
+    assert!(take(MAX_CACHED_SIZE + 1, 1).is_none());
+    assert!(take(8, MAX_CACHED_ALIGN * 2).is_none());
+    let large = MBox::<[u8]>::from_elem(0, MAX_CACHED_SIZE * 2);
+    assert_eq!(large.len(), MAX_CACHED_SIZE * 2);
+}
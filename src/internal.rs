@@ -1,22 +1,30 @@
-#[cfg(not(feature = "std"))]
-extern crate alloc;
-
 use libc::c_void;
 
-use std::alloc::Layout;
-use std::marker::PhantomData;
-use std::mem::{align_of, size_of};
-use std::ptr::{copy_nonoverlapping, NonNull};
+use core::alloc::Layout;
+use core::marker::PhantomData;
+use core::mem::{align_of, size_of};
+use core::ptr::{copy_nonoverlapping, read_volatile, write_bytes, NonNull};
+use core::str::Utf8Error;
+
+use crate::error::AllocError;
 
-#[cfg(not(feature = "std"))]
-use self::alloc::alloc::handle_alloc_error;
 #[cfg(feature = "std")]
-use std::alloc::handle_alloc_error;
+pub use std::alloc::handle_alloc_error;
+
+/// Aborts the process when an allocation fails.
+///
+/// Without `std`, `alloc::alloc::handle_alloc_error()` is avoided because linking the `alloc` crate
+/// requires a global allocator, which targets with only libc may not have.
+#[cfg(not(feature = "std"))]
+pub fn handle_alloc_error(_: Layout) -> ! {
+    // SAFETY: `abort()` has no preconditions.
+    unsafe { libc::abort() }
+}
 
 #[cfg(feature = "nightly")]
-use std::marker::Unsize;
+use core::marker::Unsize;
 #[cfg(feature = "nightly")]
-use std::ops::CoerceUnsized;
+use core::ops::CoerceUnsized;
 
 //{{{ Unique --------------------------------------------------------------------------------------
 
@@ -55,11 +63,49 @@ impl<T: ?Sized> Unique<T> {
 #[cfg(feature = "nightly")]
 impl<T: ?Sized + Unsize<U>, U: ?Sized> CoerceUnsized<Unique<U>> for Unique<T> {}
 
+/// The dangling pointer held by an empty box from `MBox::<[T]>::new_empty()`. This owns no memory,
+/// and is skipped by `gen_free()` and `gen_realloc()`.
+pub struct EmptySlice<T>(PhantomData<T>);
+
+impl<T> EmptySlice<T> {
+    // (An associated const is needed for targeting Rust 1.36, which forbids unsizing casts in a
+    // `const fn`.)
+    pub const PTR: *mut [T] = NonNull::<[T; 0]>::dangling().as_ptr() as *mut [T];
+}
+
+/// Checks whether `ptr` is the dangling pointer of an empty box, i.e. `EmptySlice::<T>::PTR`.
+fn is_empty_slice<T>(ptr: NonNull<T>) -> bool {
+    ptr == NonNull::dangling()
+}
+
 //}}}
 
 //{{{ gen_malloc ----------------------------------------------------------------------------------
 
-#[cfg(windows)]
+#[cfg(feature = "freertos")]
+extern "C" {
+    fn pvPortMalloc(size: usize) -> *mut c_void;
+    fn vPortFree(ptr: *mut c_void);
+}
+
+/// The alignment guaranteed by `pvPortMalloc()`, which is `portBYTE_ALIGNMENT` of most ports.
+#[cfg(feature = "freertos")]
+const FREERTOS_ALIGNMENT: usize = 8;
+
+#[cfg(feature = "freertos")]
+unsafe fn malloc_aligned<T>(size: usize) -> *mut c_void {
+    if has_header::<T>() {
+        return malloc_with_header(size, align_of::<T>(), pvPortMalloc);
+    }
+    pvPortMalloc(size)
+}
+
+#[cfg(all(miri, feature = "std", not(feature = "freertos")))]
+unsafe fn malloc_aligned<T>(size: usize) -> *mut c_void {
+    crate::miri_heap::malloc(size, align_of::<T>().max(size_of::<*mut ()>()))
+}
+
+#[cfg(all(windows, not(all(miri, feature = "std")), not(feature = "freertos")))]
 unsafe fn malloc_aligned<T>(size: usize) -> *mut c_void {
     struct AlignmentChecker<T>(PhantomData<T>);
     impl<T> AlignmentChecker<T> {
@@ -79,50 +125,534 @@ unsafe fn malloc_aligned<T>(size: usize) -> *mut c_void {
     libc::malloc(size)
 }
 
-#[cfg(all(not(windows), target_os = "android"))]
+#[cfg(all(
+    target_os = "android",
+    not(all(miri, feature = "std")),
+    not(feature = "freertos")
+))]
 unsafe fn malloc_aligned<T>(size: usize) -> *mut c_void {
     libc::memalign(align_of::<T>(), size)
 }
 
-#[cfg(all(not(windows), not(target_os = "android")))]
+#[cfg(all(
+    unix,
+    not(target_os = "android"),
+    not(all(miri, feature = "std")),
+    not(feature = "freertos")
+))]
 unsafe fn malloc_aligned<T>(size: usize) -> *mut c_void {
-    let mut result = std::ptr::null_mut();
+    let mut result = core::ptr::null_mut();
     let align = align_of::<T>().max(size_of::<*mut ()>());
     libc::posix_memalign(&mut result, align, size);
     result
 }
 
+/// On other targets, the libc may not provide `posix_memalign()` nor `memalign()`. `malloc()` is
+/// assumed to align to at least the pointer size, and over-aligned types are allocated with a hidden
+/// header.
+#[cfg(all(
+    not(any(unix, windows)),
+    not(all(miri, feature = "std")),
+    not(feature = "freertos")
+))]
+unsafe fn malloc_aligned<T>(size: usize) -> *mut c_void {
+    if has_header::<T>() {
+        return malloc_with_header(size, align_of::<T>(), libc::malloc);
+    }
+    libc::malloc(size)
+}
+
+/// Allocates `size` bytes aligned to `align` by over-allocating with `malloc`, and storing the
+/// original pointer just before the returned one. The result must be released by passing
+/// `header_base(ptr, align)` to the allocator.
+#[cfg(any(
+    test,
+    feature = "freertos",
+    all(not(any(unix, windows)), not(all(miri, feature = "std")))
+))]
+unsafe fn malloc_with_header(
+    size: usize,
+    align: usize,
+    malloc: unsafe extern "C" fn(usize) -> *mut c_void,
+) -> *mut c_void {
+    let header = size_of::<*mut c_void>();
+    let total = match size.checked_add(header + align - 1) {
+        Some(total) => total,
+        None => return core::ptr::null_mut(),
+    };
+    let base = malloc(total);
+    if base.is_null() {
+        return base;
+    }
+    let res = ((base as usize + header + align - 1) & !(align - 1)) as *mut c_void;
+    (res as *mut *mut c_void).sub(1).write_unaligned(base);
+    res
+}
+
+/// Returns the original pointer of a block allocated by `malloc_with_header()`.
+#[cfg(any(
+    test,
+    feature = "freertos",
+    all(not(any(unix, windows)), not(all(miri, feature = "std")))
+))]
+unsafe fn header_base(ptr: *mut c_void, align: usize) -> *mut c_void {
+    let base = (ptr as *mut *mut c_void).sub(1).read_unaligned();
+    // catches over-aligned blocks adopted from C, which have no header.
+    assert!(
+        (base as usize) < (ptr as usize)
+            && (ptr as usize - base as usize) < size_of::<*mut c_void>() + align,
+        "over-aligned block was not allocated by mbox"
+    );
+    base
+}
+
+/// Returns whether blocks of `T` allocated by `malloc_aligned()` have a hidden header.
+#[cfg(feature = "freertos")]
+fn has_header<T>() -> bool {
+    align_of::<T>() > FREERTOS_ALIGNMENT
+}
+
+#[cfg(all(
+    not(any(unix, windows)),
+    not(all(miri, feature = "std")),
+    not(feature = "freertos")
+))]
+fn has_header<T>() -> bool {
+    align_of::<T>() > size_of::<*mut ()>()
+}
+
+#[cfg(all(
+    any(unix, windows, all(miri, feature = "std")),
+    not(feature = "freertos")
+))]
+#[allow(clippy::extra_unused_type_parameters)]
+fn has_header<T>() -> bool {
+    false
+}
+
+/// Returns the pointer to release to the allocator for a block of `T` allocated by
+/// `malloc_aligned()`, which differs from the block when it has a hidden header.
+#[cfg(any(
+    feature = "freertos",
+    all(not(any(unix, windows)), not(all(miri, feature = "std")))
+))]
+unsafe fn allocation_base<T>(ptr: *mut c_void) -> *mut c_void {
+    if has_header::<T>() {
+        return header_base(ptr, align_of::<T>());
+    }
+    ptr
+}
+
+#[cfg(all(
+    any(unix, windows, all(miri, feature = "std")),
+    not(feature = "freertos")
+))]
+#[allow(clippy::extra_unused_type_parameters)]
+unsafe fn allocation_base<T>(ptr: *mut c_void) -> *mut c_void {
+    ptr
+}
+
+#[test]
+fn test_malloc_with_header() {
+    unsafe {
+        for &align in &[16, 64, 4096] {
+            let ptr = malloc_with_header(100, align, libc::malloc);
+            assert_eq!(ptr as usize % align, 0);
+            std::ptr::write_bytes(ptr as *mut u8, 0xAB, 100);
+            let base = header_base(ptr, align);
+            assert!((base as usize) < (ptr as usize));
+            libc::free(base);
+        }
+    }
+}
+
+#[cfg(feature = "freertos")]
+#[test]
+#[should_panic(expected = "over-aligned block was not allocated by mbox")]
+fn test_free_over_aligned_without_header() {
+    #[repr(C, align(64))]
+    struct A([u8; 64]);
+
+    unsafe {
+        // an aligned block from C, preceded by a word which is not a header. It is leaked.
+        let block = malloc_with_header(128, 64, libc::malloc) as *mut usize;
+        block.add(7).write(0);
+        gen_free(NonNull::new_unchecked(block.add(8) as *mut A));
+    }
+}
+
+// `calloc()` may happen to return an aligned block, which still has no header.
+#[cfg(feature = "freertos")]
+#[test]
+fn test_calloc_over_aligned_with_header() {
+    #[repr(C, align(64))]
+    struct A([u8; 64]);
+
+    unsafe {
+        for count in 1..32 {
+            let ptr = gen_calloc::<A>(count);
+            assert_eq!(ptr.as_ptr() as usize % 64, 0);
+            let bytes = std::slice::from_raw_parts(ptr.as_ptr() as *const u8, count * 64);
+            assert!(bytes.iter().all(|&b| b == 0));
+            gen_free(ptr);
+        }
+    }
+}
+
+/// Allocates `size` bytes filled with zeros. The result may not be aligned for over-aligned types.
+#[cfg(not(any(all(miri, feature = "std"), feature = "freertos")))]
+unsafe fn calloc_raw(size: usize) -> *mut c_void {
+    libc::calloc(size, 1)
+}
+
+#[cfg(all(miri, feature = "std", not(feature = "freertos")))]
+unsafe fn calloc_raw(size: usize) -> *mut c_void {
+    crate::miri_heap::calloc(size)
+}
+
+#[cfg(feature = "freertos")]
+unsafe fn calloc_raw(size: usize) -> *mut c_void {
+    let res = pvPortMalloc(size);
+    if !res.is_null() {
+        write_bytes(res as *mut u8, 0, size);
+    }
+    res
+}
+
+/// Releases a block to the allocator, which is `free()`, or `vPortFree()` with the `freertos`
+/// feature. Under Miri, blocks are allocated through the Rust global allocator instead.
+///
+/// # Safety
+///
+/// The `ptr` must be obtained from the same allocator, and not used afterwards.
+#[cfg(not(any(all(miri, feature = "std"), feature = "freertos")))]
+pub unsafe fn free_raw(ptr: *mut c_void) {
+    libc::free(ptr);
+}
+
+#[cfg(all(miri, feature = "std", not(feature = "freertos")))]
+pub unsafe fn free_raw(ptr: *mut c_void) {
+    crate::miri_heap::free(ptr);
+}
+
+/// Resizes a block to `size` bytes like `realloc()`. The result may not be aligned for
+/// over-aligned types.
+#[cfg(not(any(all(miri, feature = "std"), feature = "freertos")))]
+unsafe fn realloc_raw(ptr: *mut c_void, size: usize) -> *mut c_void {
+    libc::realloc(ptr, size)
+}
+
+#[cfg(all(miri, feature = "std", not(feature = "freertos")))]
+unsafe fn realloc_raw(ptr: *mut c_void, size: usize) -> *mut c_void {
+    crate::miri_heap::realloc(ptr, size)
+}
+
+#[cfg(feature = "freertos")]
+pub unsafe fn free_raw(ptr: *mut c_void) {
+    vPortFree(ptr);
+}
+
+/// Stand-ins for the FreeRTOS heap, so the `freertos` feature can be tested on the host.
+#[cfg(all(test, feature = "freertos"))]
+mod freertos_shim {
+    use libc::c_void;
+
+    #[no_mangle]
+    unsafe extern "C" fn pvPortMalloc(size: usize) -> *mut c_void {
+        libc::malloc(size)
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn vPortFree(ptr: *mut c_void) {
+        libc::free(ptr);
+    }
+}
+
 /// Generic malloc function.
 ///
 /// This function allocates memory capable of storing the array `[T; count]`.
 /// The memory content will not be initialized.
 ///
-/// If `T` is zero-sized or `count == 0`, we will call `malloc(0)` which the C
-/// standard permits returning NULL. In that case, we will *allocate at least 1
-/// byte* to respect the `NonNull` constraint (we cannot use `NonNull::danging()`
+/// If `T` is zero-sized or `count == 0`, we will *allocate at least 1 byte*
+/// instead of calling `malloc(0)`, which the C standard permits returning NULL,
+/// to respect the `NonNull` constraint (we cannot use `NonNull::danging()`
 /// because we allow the result to be passed directly to C's `free()`).
 pub fn gen_malloc<T>(count: usize) -> NonNull<T> {
-    let requested_size = count.checked_mul(size_of::<T>()).expect("memory overflow");
+    match try_gen_malloc(count) {
+        Ok(res) => res,
+        Err(AllocError::CapacityOverflow) => panic!("memory overflow"),
+        Err(AllocError::OutOfMemory(_)) => handle_alloc_error(Layout::new::<T>()),
+    }
+}
+
+/// Generic fallible malloc function.
+///
+/// Same as `gen_malloc()`, but returns an `AllocError` instead of panicking or aborting.
+pub fn try_gen_malloc<T>(count: usize) -> Result<NonNull<T>, AllocError> {
+    let allocated_size = allocation_size::<T>(count)?;
+
+    let mut res = core::ptr::null_mut::<c_void>();
+
+    #[cfg(feature = "thread_local_cache")]
+    {
+        if let Some(block) = crate::cache::take(allocated_size, align_of::<T>()) {
+            res = block;
+        }
+    }
+
+    #[cfg(feature = "global_cache")]
+    {
+        if res.is_null() {
+            if let Some(block) = crate::global_cache::take(allocated_size, align_of::<T>()) {
+                res = block;
+            }
+        }
+    }
 
-    let mut res;
     // SAFETY: allocating should be safe, duh.
     unsafe {
-        res = malloc_aligned::<T>(requested_size);
-        if res.is_null() && requested_size == 0 {
-            res = malloc_aligned::<T>(align_of::<T>());
+        // ensure the size > 0, since the usable size of a `malloc(0)` block may be reported larger
+        // than what can actually be accessed, e.g. under AddressSanitizer.
+        if res.is_null() {
+            res = malloc_aligned::<T>(allocated_size.max(1));
         }
+        finish_alloc(res, count, allocated_size)
+    }
+}
+
+/// Returns the number of bytes to allocate for `count` items of type `T`, including the canary
+/// when the `canary` feature is enabled.
+pub fn allocation_size<T>(count: usize) -> Result<usize, AllocError> {
+    count
+        .checked_mul(size_of::<T>())
+        .and_then(with_canary)
+        .ok_or(AllocError::CapacityOverflow)
+}
+
+/// Records a block which has just been allocated for `count` items of type `T` in the debugging
+/// features, or returns `OutOfMemory` if the allocation returned NULL.
+///
+/// # Safety
+///
+/// `res` must be NULL, or a new block of `allocated_size` bytes computed by `allocation_size()` and
+/// aligned for `T`. It must be possible to free the block with `gen_free()`.
+#[allow(unused_variables)]
+pub unsafe fn finish_alloc<T>(
+    res: *mut c_void,
+    count: usize,
+    allocated_size: usize,
+) -> Result<NonNull<T>, AllocError> {
+    let res = NonNull::new(res as *mut T).ok_or(AllocError::OutOfMemory(allocated_size))?;
+    #[cfg(feature = "canary")]
+    crate::canary::arm(res.as_ptr() as *mut c_void, count * size_of::<T>());
+    #[cfg(feature = "track_ownership")]
+    crate::registry::allocated(res.as_ptr() as *mut c_void);
+    #[cfg(feature = "stats")]
+    crate::stats::record_malloc(res.as_ptr() as *mut c_void);
+    #[cfg(feature = "track_call_sites")]
+    crate::call_sites::allocated(res.as_ptr() as *mut c_void);
+    #[cfg(feature = "tracing")]
+    crate::tracing_support::malloc::<T>(res.as_ptr() as *mut c_void, count);
+    Ok(res)
+}
+
+/// Returns the number of bytes to allocate for `size` usable bytes, including the canary when the
+/// `canary` feature is enabled.
+fn with_canary(size: usize) -> Option<usize> {
+    #[cfg(feature = "canary")]
+    {
+        size.checked_add(crate::canary::CANARY_LEN)
+    }
+    #[cfg(not(feature = "canary"))]
+    {
+        Some(size)
+    }
+}
+
+/// Generic calloc function.
+///
+/// Same as `gen_malloc()`, but the memory content is filled with zeros. Fresh pages from `calloc()`
+/// are usually already zeroed by the OS, so this is cheaper than `malloc()` followed by a fill.
+pub fn gen_calloc<T>(count: usize) -> NonNull<T> {
+    match try_gen_calloc(count) {
+        Ok(res) => res,
+        Err(AllocError::CapacityOverflow) => panic!("memory overflow"),
+        Err(AllocError::OutOfMemory(_)) => handle_alloc_error(Layout::new::<T>()),
+    }
+}
+
+/// Generic fallible calloc function.
+///
+/// Same as `gen_calloc()`, but returns an `AllocError` instead of panicking or aborting.
+pub fn try_gen_calloc<T>(count: usize) -> Result<NonNull<T>, AllocError> {
+    let allocated_size = allocation_size::<T>(count)?;
+
+    // SAFETY: allocating should be safe, duh.
+    unsafe {
+        // blocks with a hidden header are freed through it, so they must never come from
+        // `calloc()`, even when it happens to return an aligned block.
+        let mut res = if has_header::<T>() {
+            core::ptr::null_mut()
+        } else {
+            // ensure the size > 0 to avoid `calloc()` returning a successful NULL.
+            calloc_raw(allocated_size.max(1))
+        };
+
+        // `calloc()` does not support over-aligned types. Fall back to an aligned allocation
+        // followed by an explicit fill.
+        let misaligned = !res.is_null() && res as usize % align_of::<T>() != 0;
+        if misaligned {
+            free_raw(res);
+        }
+        if misaligned || has_header::<T>() {
+            res = malloc_aligned::<T>(allocated_size.max(align_of::<T>()));
+            if !res.is_null() {
+                // no need to do checked_mul() here since it must be <= `allocated_size`.
+                write_bytes(res as *mut u8, 0, count * size_of::<T>());
+            }
+        }
+        finish_alloc(res, count, allocated_size)
     }
-    NonNull::new(res as *mut T).unwrap_or_else(|| handle_alloc_error(Layout::new::<T>()))
 }
 
 /// Generic free function.
 ///
 /// # Safety
 ///
-/// The `ptr` must be obtained from `malloc()` or similar C functions.
+/// The `ptr` must be obtained from `malloc()` or similar C functions, or be `EmptySlice::<T>::PTR`.
 /// The memory content will not be dropped.
 pub unsafe fn gen_free<T>(ptr: NonNull<T>) {
-    libc::free(ptr.as_ptr() as *mut c_void);
+    if is_empty_slice(ptr) {
+        return;
+    }
+
+    #[cfg(feature = "track_ownership")]
+    crate::registry::freed(ptr.as_ptr() as *mut c_void);
+
+    #[cfg(feature = "canary")]
+    crate::canary::check(ptr.as_ptr() as *mut c_void);
+
+    #[cfg(feature = "stats")]
+    crate::stats::record_free(ptr.as_ptr() as *mut c_void);
+
+    #[cfg(feature = "track_call_sites")]
+    crate::call_sites::freed(ptr.as_ptr() as *mut c_void);
+
+    #[cfg(feature = "tracing")]
+    crate::tracing_support::free::<T>(ptr.as_ptr() as *mut c_void);
+
+    release::<T>(ptr.as_ptr() as *mut c_void);
+}
+
+/// Poisons a block of `T` allocated by `malloc_aligned()`, and releases it through the quarantine
+/// and the caches. The debugging features tracking the block must have been updated already.
+///
+/// # Safety
+///
+/// The `ptr` must be obtained from `malloc_aligned::<T>()`, and not used afterwards.
+unsafe fn release<T>(ptr: *mut c_void) {
+    #[cfg(feature = "poison_on_free")]
+    poison(ptr);
+
+    let ptr = allocation_base::<T>(ptr);
+
+    // the block which is actually released is the oldest one in the quarantine.
+    #[cfg(feature = "quarantine")]
+    let ptr = match crate::quarantine::push(ptr) {
+        Some(evicted) => evicted,
+        None => return,
+    };
+
+    #[cfg(feature = "thread_local_cache")]
+    {
+        if crate::cache::give(ptr) {
+            return;
+        }
+    }
+    #[cfg(feature = "global_cache")]
+    {
+        if crate::global_cache::give(ptr) {
+            return;
+        }
+    }
+    free_raw(ptr);
+}
+
+/// The byte written over memory about to be freed when the `poison_on_free` feature is enabled.
+#[cfg(feature = "poison_on_free")]
+pub const POISON_BYTE: u8 = 0xDD;
+
+/// Fills the whole allocation with `POISON_BYTE`, so that dangling pointers read obviously garbage
+/// data after it is freed. Nothing is poisoned if the platform cannot tell the allocation size.
+///
+/// # Safety
+///
+/// The `ptr` must be obtained from `malloc()` or similar C functions, and not used afterwards.
+#[cfg(feature = "poison_on_free")]
+unsafe fn poison(ptr: *mut c_void) {
+    fill_volatile(ptr as *mut u8, POISON_BYTE, usable_size(ptr));
+}
+
+#[cfg(all(
+    feature = "poison_on_free",
+    not(miri),
+    not(feature = "freertos"),
+    any(all(target_os = "linux", target_env = "gnu"), target_os = "macos")
+))]
+#[test]
+fn test_poison() {
+    unsafe {
+        let ptr = gen_malloc::<u8>(100);
+        poison(ptr.as_ptr() as *mut c_void);
+        let block = std::slice::from_raw_parts(ptr.as_ptr(), 100);
+        assert!(block.iter().all(|&b| b == POISON_BYTE));
+        libc::free(ptr.as_ptr() as *mut c_void);
+    }
+}
+
+/// Returns the number of bytes actually usable in the allocation, which may be larger than the
+/// requested size. Returns 0 if the platform cannot tell.
+///
+/// # Safety
+///
+/// The `ptr` must be obtained from `malloc()` or similar C functions.
+#[cfg(all(
+    not(miri),
+    not(feature = "freertos"),
+    any(
+        all(target_os = "linux", any(target_env = "gnu", target_env = "musl")),
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+    )
+))]
+pub unsafe fn usable_size(ptr: *mut c_void) -> usize {
+    libc::malloc_usable_size(ptr as _)
+}
+
+#[cfg(all(
+    not(miri),
+    not(feature = "freertos"),
+    any(target_os = "macos", target_os = "ios")
+))]
+pub unsafe fn usable_size(ptr: *mut c_void) -> usize {
+    libc::malloc_size(ptr)
+}
+
+#[cfg(any(
+    miri,
+    feature = "freertos",
+    not(any(
+        all(target_os = "linux", any(target_env = "gnu", target_env = "musl")),
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "macos",
+        target_os = "ios",
+    ))
+))]
+pub unsafe fn usable_size(_: *mut c_void) -> usize {
+    0
 }
 
 /// Generic realloc function.
@@ -133,141 +663,353 @@ pub unsafe fn gen_free<T>(ptr: NonNull<T>) {
 ///
 /// # Safety
 ///
-/// The `ptr` must be obtained from `malloc()` or similar C functions.
+/// The `ptr` must be obtained from `malloc()` or similar C functions, or be `EmptySlice::<T>::PTR`.
 pub unsafe fn gen_realloc<T>(ptr: NonNull<T>, old_count: usize, new_count: usize) -> NonNull<T> {
+    try_gen_realloc(ptr, old_count, new_count)
+        .unwrap_or_else(|_| handle_alloc_error(Layout::new::<T>()))
+}
+
+/// Generic fallible realloc function.
+///
+/// Same as `gen_realloc()`, but returns an `AllocError` instead of aborting. On error, the block at
+/// `ptr` is left untouched and is still allocated.
+///
+/// # Safety
+///
+/// The `ptr` must be obtained from `malloc()` or similar C functions, or be `EmptySlice::<T>::PTR`.
+pub unsafe fn try_gen_realloc<T>(
+    ptr: NonNull<T>,
+    old_count: usize,
+    new_count: usize,
+) -> Result<NonNull<T>, AllocError> {
     if size_of::<T>() == 0 {
-        return ptr;
+        return Ok(ptr);
+    }
+    // an empty box has nothing to copy or to free.
+    if is_empty_slice(ptr) {
+        return try_gen_malloc(new_count);
     }
 
-    (|| {
-        // ensure `requested_size > 0` to avoid `realloc()` returning a successful NULL.
-        let requested_size = new_count.checked_mul(size_of::<T>())?.max(align_of::<T>());
-        let mut res = libc::realloc(ptr.as_ptr() as *mut c_void, requested_size);
-        if res.is_null() {
-            return None;
+    let new_size = new_count
+        .checked_mul(size_of::<T>())
+        .ok_or(AllocError::CapacityOverflow)?;
+    // ensure `requested_size > 0` to avoid `realloc()` returning a successful NULL.
+    let requested_size = with_canary(new_size)
+        .ok_or(AllocError::CapacityOverflow)?
+        .max(align_of::<T>());
+
+    #[cfg(feature = "track_ownership")]
+    crate::registry::freed(ptr.as_ptr() as *mut c_void);
+
+    #[cfg(feature = "canary")]
+    let old_canary = crate::canary::check(ptr.as_ptr() as *mut c_void);
+
+    #[cfg(feature = "stats")]
+    let old_size = usable_size(ptr.as_ptr() as *mut c_void);
+
+    let res = (|| {
+        // fast path: when growing into the slack the allocator has already given us, there is
+        // no need to call `realloc()` at all. (Shrinking always calls `realloc()` so the memory
+        // can be returned.)
+        if new_count > old_count && usable_size(ptr.as_ptr() as *mut c_void) >= requested_size {
+            return Some(ptr);
         }
 
-        // Most system don't provide an `aligned_realloc`.
-        // If `libc::realloc()` does not give us an aligned pointer,
-        // we have to perform an additional aligned allocation and memcpy over.
-        if res as usize % align_of::<T>() != 0 {
-            let actual_res = malloc_aligned::<T>(requested_size);
-            if actual_res.is_null() {
-                return None;
+        // Most system don't provide an `aligned_realloc`, and `libc::realloc()` only guarantees
+        // the alignment of fundamental types. For over-aligned types, we perform an aligned
+        // allocation and memcpy over instead, so the original block is still intact if the
+        // allocation fails. FreeRTOS has no `realloc()` at all.
+        #[cfg(not(feature = "freertos"))]
+        {
+            if align_of::<T>() <= size_of::<*mut ()>() {
+                let res = realloc_raw(ptr.as_ptr() as *mut c_void, requested_size);
+                return NonNull::new(res as *mut T);
             }
+        }
 
-            // no need to do checked_mul() here since it must be <= `requested_size`.
-            let copy_len = old_count.min(new_count) * size_of::<T>();
-            copy_nonoverlapping(res, actual_res, copy_len);
-            libc::free(res);
-            res = actual_res;
+        let res = malloc_aligned::<T>(requested_size);
+        if res.is_null() {
+            return None;
         }
 
+        // no need to do checked_mul() here since it must be <= `requested_size`.
+        let copy_len = old_count.min(new_count) * size_of::<T>();
+        copy_nonoverlapping(ptr.as_ptr() as *mut c_void, res, copy_len);
+        release::<T>(ptr.as_ptr() as *mut c_void);
         NonNull::new(res as *mut T)
-    })()
-    .unwrap_or_else(|| handle_alloc_error(Layout::new::<T>()))
+    })();
+
+    let res = match res {
+        Some(res) => res,
+        None => {
+            // the block is unchanged, so it is still tracked as before.
+            #[cfg(feature = "track_ownership")]
+            crate::registry::allocated(ptr.as_ptr() as *mut c_void);
+            #[cfg(feature = "canary")]
+            {
+                if let Some(size) = old_canary {
+                    crate::canary::arm(ptr.as_ptr() as *mut c_void, size);
+                }
+            }
+            return Err(AllocError::OutOfMemory(requested_size));
+        }
+    };
+
+    #[cfg(feature = "canary")]
+    crate::canary::arm(res.as_ptr() as *mut c_void, new_size);
+    // the block may have moved, and is not owned by any `MBox` until it is put back into one.
+    #[cfg(feature = "track_ownership")]
+    crate::registry::allocated(res.as_ptr() as *mut c_void);
+    #[cfg(feature = "stats")]
+    crate::stats::record_realloc(old_size, res.as_ptr() as *mut c_void);
+    #[cfg(feature = "track_call_sites")]
+    crate::call_sites::moved(ptr.as_ptr() as *mut c_void, res.as_ptr() as *mut c_void);
+    #[cfg(feature = "tracing")]
+    crate::tracing_support::realloc::<T>(
+        ptr.as_ptr() as *mut c_void,
+        res.as_ptr() as *mut c_void,
+        new_count,
+    );
+    Ok(res)
 }
 
-//}}}
+#[cfg(not(windows))]
+#[test]
+fn test_calloc() {
+    #[repr(C, align(4096))]
+    struct A([u8; 3]);
+
+    unsafe {
+        let ptr = gen_calloc::<u32>(64);
+        assert_eq!(ptr.as_ptr() as usize % align_of::<u32>(), 0);
+        assert!((0..64).all(|i| *ptr.as_ptr().add(i) == 0));
+        gen_free(ptr);
+
+        let ptr = gen_calloc::<A>(2);
+        assert_eq!(ptr.as_ptr() as usize % 4096, 0);
+        assert_eq!((*ptr.as_ptr()).0, [0; 3]);
+        assert_eq!((*ptr.as_ptr().add(1)).0, [0; 3]);
+        gen_free(ptr);
+
+        gen_free(gen_calloc::<u64>(0));
+    }
+}
 
-//{{{ Drop counter --------------------------------------------------------------------------------
+#[cfg(all(
+    not(miri),
+    not(feature = "freertos"),
+    any(all(target_os = "linux", target_env = "gnu"), target_os = "macos")
+))]
+#[test]
+fn test_realloc_into_slack() {
+    unsafe {
+        let ptr = gen_malloc::<u8>(1);
+        let usable = usable_size(ptr.as_ptr() as *mut c_void) - with_canary(0).unwrap();
+        assert!(usable >= 1);
+        // there is no slack under AddressSanitizer, which reports the exact requested size.
+        let grown = if usable > 1 {
+            let grown = gen_realloc(ptr, 1, usable);
+            assert_eq!(grown, ptr);
+            grown
+        } else {
+            ptr
+        };
+        gen_free(grown);
+    }
+}
 
-#[cfg(all(test, not(windows)))]
-#[derive(Clone, Debug, PartialEq, Eq)]
-#[cfg_attr(feature = "std", derive(Default))]
-pub(crate) struct SharedCounter {
-    #[cfg(feature = "std")]
-    counter: std::rc::Rc<std::cell::Cell<usize>>,
+#[cfg(all(
+    feature = "quarantine",
+    not(miri),
+    not(feature = "freertos"),
+    any(all(target_os = "linux", target_env = "gnu"), target_os = "macos")
+))]
+#[test]
+fn test_realloc_over_aligned_poisons_old_block() {
+    #[repr(C, align(64))]
+    struct A([u8; 64]);
 
-    /// A shared, mutable counter on heap.
-    #[cfg(not(feature = "std"))]
-    counter: NonNull<usize>,
+    unsafe {
+        let ptr = gen_malloc::<A>(1);
+        write_bytes(ptr.as_ptr() as *mut u8, 0xAB, 64);
+        // growing far beyond the slack moves an over-aligned block by copying.
+        let grown = gen_realloc(ptr, 1, 1000);
+        assert_ne!(grown, ptr);
+        assert_eq!((*grown.as_ptr()).0, [0xAB; 64]);
+        assert!(crate::quarantine::holds_poisoned(
+            ptr.as_ptr() as *mut c_void
+        ));
+        gen_free(grown);
+    }
 }
 
-#[cfg(all(test, not(windows), not(feature = "std")))]
-impl Default for SharedCounter {
-    fn default() -> Self {
-        let counter = gen_malloc(1);
-        // SAFETY: malloc() returns an uninitialized integer which is then filled in.
-        unsafe {
-            std::ptr::write(counter.as_ptr(), 0);
-            Self { counter }
-        }
+// AddressSanitizer aborts on oversized allocations instead of returning NULL.
+#[cfg(all(
+    not(miri),
+    not(feature = "asan"),
+    any(all(target_os = "linux", target_env = "gnu"), target_os = "macos")
+))]
+#[test]
+fn test_try_gen_realloc_failure() {
+    unsafe {
+        let ptr = gen_malloc::<u8>(3);
+        ptr.as_ptr().copy_from_nonoverlapping(b"abc".as_ptr(), 3);
+        let huge = std::isize::MAX as usize - 4096;
+        assert_eq!(
+            try_gen_realloc(ptr, 3, huge),
+            Err(AllocError::OutOfMemory(huge + with_canary(0).unwrap()))
+        );
+        assert_eq!(
+            try_gen_realloc(
+                NonNull::new_unchecked(ptr.as_ptr() as *mut u16),
+                1,
+                std::usize::MAX
+            ),
+            Err(AllocError::CapacityOverflow)
+        );
+        // the block is still intact and can be used as before.
+        assert_eq!(std::slice::from_raw_parts(ptr.as_ptr(), 3), b"abc");
+        let ptr = try_gen_realloc(ptr, 3, 4).unwrap();
+        assert_eq!(std::slice::from_raw_parts(ptr.as_ptr(), 3), b"abc");
+        gen_free(ptr);
+
+        // over-aligned blocks are moved by a separate allocation.
+        #[repr(C, align(64))]
+        struct A(u8);
+
+        let ptr = gen_malloc::<A>(1);
+        (*ptr.as_ptr()).0 = 7;
+        let ptr = try_gen_realloc(ptr, 1, 100).unwrap();
+        assert_eq!(ptr.as_ptr() as usize % 64, 0);
+        assert_eq!((*ptr.as_ptr()).0, 7);
+        gen_free(ptr);
     }
 }
 
-#[cfg(all(test, not(windows)))]
-impl SharedCounter {
-    /// Gets the counter value.
-    pub(crate) fn get(&self) -> usize {
-        #[cfg(feature = "std")]
-        {
-            self.counter.get()
-        }
-        // SAFETY: `self.counter` is malloc()'ed, initialized and never freed.
-        #[cfg(not(feature = "std"))]
-        unsafe {
-            *self.counter.as_ref()
-        }
+#[cfg(feature = "freertos")]
+#[test]
+fn test_freertos() {
+    unsafe {
+        let ptr = gen_calloc::<u64>(4);
+        assert!((0..4).all(|i| *ptr.as_ptr().add(i) == 0));
+        let ptr = gen_realloc(ptr, 4, 8);
+        assert!((0..4).all(|i| *ptr.as_ptr().add(i) == 0));
+        gen_free(ptr);
     }
+}
 
-    /// Asserts the counter value equals to the input. Panics when different.
-    pub(crate) fn assert_eq(&self, value: usize) {
-        assert_eq!(self.get(), value);
+/// Fills `dst` with `n` copies of the `len` items at `src`.
+///
+/// The first copy comes from `src`, and then the filled region is doubled by `memcpy` until the
+/// whole `len * n` items are written.
+///
+/// # Safety
+///
+/// `src` must be valid for reading `len` items, and `dst` must be valid for writing `len * n`
+/// items. The two regions must not overlap.
+pub unsafe fn copy_repeated<T: Copy>(src: *const T, len: usize, dst: *mut T, n: usize) {
+    let total = len * n;
+    if total == 0 {
+        return;
+    }
+    copy_nonoverlapping(src, dst, len);
+    let mut filled = len;
+    while filled < total {
+        let count = filled.min(total - filled);
+        copy_nonoverlapping(dst, dst.add(filled), count);
+        filled += count;
     }
+}
 
-    /// Increases the counter by 1.
-    fn inc(&self) {
-        #[cfg(feature = "std")]
-        {
-            self.counter.set(self.counter.get() + 1);
-        }
-        // SAFETY: `self.counter` is malloc()'ed, initialized and never freed.
-        // Since `SharedCounter` is not Sync nor Send, we are sure the
-        // modification happens in the single thread, so we don't worry about
-        // the interior mutation.
-        #[cfg(not(feature = "std"))]
-        unsafe {
-            *self.counter.as_ptr() += 1;
-        }
+/// Computes the total length when joining items of the given lengths with a separator of
+/// `separator_len` in between.
+///
+/// # Panics
+///
+/// Panics if the total length overflows.
+pub fn joined_len<I: ExactSizeIterator<Item = usize>>(lens: I, separator_len: usize) -> usize {
+    let separators = lens.len().saturating_sub(1);
+    let mut total = separator_len.checked_mul(separators);
+    for len in lens {
+        total = total.and_then(|total| total.checked_add(len));
     }
+    total.expect("memory overflow")
 }
 
-/// A test structure to count how many times the value has been dropped.
-#[cfg(all(test, not(windows)))]
-#[derive(Clone, Debug, PartialEq, Eq, Default)]
-pub(crate) struct DropCounter(SharedCounter);
+//}}}
+
+//{{{ Constant-time comparison and zeroing --------------------------------------------------------
 
-#[cfg(all(test, not(windows)))]
-impl std::ops::Deref for DropCounter {
-    type Target = SharedCounter;
-    fn deref(&self) -> &Self::Target {
-        &self.0
+/// Checks whether two byte slices are equal, in time depending only on the lengths and not on the
+/// content.
+pub fn ct_eq_bytes(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
     }
+    let mut diff = 0u8;
+    for (x, y) in a.iter().zip(b) {
+        diff |= x ^ y;
+    }
+    // the volatile read prevents the compiler from turning the loop into an early-exit comparison.
+    // SAFETY: `diff` is a valid local variable.
+    unsafe { read_volatile(&diff) == 0 }
+}
+
+#[test]
+fn test_ct_eq_bytes() {
+    assert!(ct_eq_bytes(b"", b""));
+    assert!(ct_eq_bytes(b"secret", b"secret"));
+    assert!(!ct_eq_bytes(b"secret", b"secreT"));
+    assert!(!ct_eq_bytes(b"secret", b"secrets"));
 }
 
-#[cfg(all(test, not(windows)))]
-impl Drop for DropCounter {
-    fn drop(&mut self) {
-        self.0.inc();
+/// Overwrites `len` bytes at `ptr` with `value`, in a way which would not be optimized away even
+/// if the memory is freed right after.
+///
+/// # Safety
+///
+/// `ptr` must be valid for writing `len` bytes.
+#[cfg(any(all(unix, feature = "std"), feature = "poison_on_free"))]
+pub unsafe fn fill_volatile(ptr: *mut u8, value: u8, len: usize) {
+    for i in 0..len {
+        core::ptr::write_volatile(ptr.add(i), value);
     }
+    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
 }
 
-//}}}
+#[cfg(any(all(unix, feature = "std"), feature = "poison_on_free"))]
+#[test]
+fn test_fill_volatile() {
+    let mut secret = *b"secret";
+    unsafe { fill_volatile(secret.as_mut_ptr(), 0, 4) };
+    assert_eq!(&secret, b"\0\0\0\0et");
+}
 
-//{{{ Panic-on-clone ------------------------------------------------------------------------------
+//}}}
 
-/// A test structure which panics when it is cloned.
-#[cfg(test)]
-#[derive(Default)]
-#[repr(C)] // silence the dead code warning, we don't want a ZST here to complicate things.
-pub struct PanicOnClone(u8);
+//{{{ UTF-8 validation ----------------------------------------------------------------------------
 
-#[cfg(test)]
-impl Clone for PanicOnClone {
-    fn clone(&self) -> Self {
-        panic!("panic on clone");
+/// Validates the bytes as UTF-8, same as `std::str::from_utf8()`.
+///
+/// With the `simdutf8` feature, valid input is checked using SIMD. The slower standard validation
+/// only runs on invalid input, to produce the precise `Utf8Error`.
+pub fn from_utf8(bytes: &[u8]) -> Result<&str, Utf8Error> {
+    #[cfg(feature = "simdutf8")]
+    {
+        if let Ok(string) = simdutf8::basic::from_utf8(bytes) {
+            return Ok(string);
+        }
     }
+    core::str::from_utf8(bytes)
+}
+
+#[test]
+fn test_from_utf8() {
+    assert_eq!(from_utf8(b"abc\xe7\x94\xb2"), Ok("abc甲"));
+    let error = from_utf8(b"abc\xe7\x94").unwrap_err();
+    assert_eq!(error.valid_up_to(), 3);
+    assert_eq!(error.error_len(), None);
+    assert_eq!(from_utf8(b"ab\xffc").unwrap_err().error_len(), Some(1));
 }
 
 //}}}
@@ -84,6 +84,52 @@
 //!
 //! When `#![no_std]` is activated, you cannot convert an `MString` into a `std::ffi::CStr`, as the
 //! type simply does not exist 🙂.
+//! The error types in `mbox::error` implement `core::error::Error` instead, but only when built with
+//! Rust 1.81 or above.
+//!
+//! Without `std`, `mbox` does not link to `alloc` either, so it works on targets which only have libc
+//! and no global allocator. Allocation failures then abort the process through `libc::abort()`.
+//!
+//! On targets other than Unix and Windows, whose libc may provide neither `posix_memalign()` nor
+//! `memalign()`, types aligned to more than a pointer are allocated by over-allocating with `malloc()`
+//! and storing the original pointer in a hidden header. Such blocks must not be passed to `free()`
+//! directly, but only released through `MBox`. Conversely, `MBox::from_raw()` must not adopt such a
+//! type allocated by C's `malloc()`, even if it is properly aligned.
+//!
+//! ### FreeRTOS
+//!
+//! Enable the `freertos` feature to allocate through `pvPortMalloc()` and `vPortFree()` instead of
+//! `malloc()` and `free()`, for firmware which standardizes on the FreeRTOS heap. Pointers passed to
+//! `MBox::from_raw()` must then come from `pvPortMalloc()`, and pointers obtained from `into_raw()`
+//! must be released by `vPortFree()`. FreeRTOS has no `realloc()`, so growing always copies into a new
+//! block. Types aligned to more than 8 bytes are allocated with a hidden header, so they must not be
+//! released by `vPortFree()` directly, nor adopted from `pvPortMalloc()`.
+//!
+//! Targets using newlib need no feature: newlib implements `malloc()` and `free()` itself, so the
+//! default backend already allocates from the newlib heap.
+//!
+//! ### ESP-IDF
+//!
+//! Enable the `esp_idf` feature for constructors such as
+//! `MBox::<[u8]>::try_new_zeroed_slice_with_caps(len, HeapCaps::DMA)`, which allocate from a memory
+//! region having the given `MALLOC_CAP_*` capabilities through `heap_caps_aligned_alloc()`. Buffers
+//! for peripherals can then be placed in DMA-capable RAM or external PSRAM while still owned by an
+//! `MBox`. This requires ESP-IDF 5.0 or later, where such blocks can be released by `free()`.
+//!
+//! ### Fallible allocation
+//!
+//! For environments where panics and aborts are not acceptable, e.g. kernels or libraries embedded in
+//! C hosts, the `try_` methods return an `AllocError` when the size overflows or `malloc()` returns
+//! NULL, instead of panicking or aborting:
+//!
+//! * `MBox::try_new()`, `try_new_uninit()` and `try_new_zeroed()`
+//! * `MBox::<[T]>::try_new_uninit_slice()`, `try_new_zeroed_slice()`, `try_from_slice()` and
+//!   `try_extend_from_slice()`
+//! * `MString::try_from_str()` and `try_push_str()`
+//!
+//! When growing fails, the original content is left untouched. These methods do not panic by
+//! themselves, but a `clone()` called by `try_from_slice()` still may, and the debugging features
+//! below still panic when they detect memory errors.
 //!
 //! ### Nightly
 //!
@@ -95,6 +141,249 @@
 //! mbox = { version = "0.7", features = ["nightly"] }
 //! ```
 //!
+//! With `nightly`, `mbox::allocator::Malloc` implements the unstable `Allocator` trait, and
+//! `MBox<[T]>` and `MString` convert to and from `Vec<T, Malloc>` without copying the items.
+//!
+//! ### Serde
+//!
+//! Enable the `serde` feature to implement `Serialize` and `Deserialize` for `MBox<T>`,
+//! `MBox<[T]>`, `MBox<str>` and `MString`. Deserialization writes directly into `malloc`-ed
+//! memory. Use `#[serde(with = "mbox::serde_bytes")]` to (de)serialize an `MBox<[u8]>` as a byte
+//! string rather than a sequence of integers.
+//!
+//! ```toml
+//! [dependencies]
+//! mbox = { version = "0.7", features = ["serde"] }
+//! ```
+//!
+//! ### rkyv
+//!
+//! Enable the `rkyv` feature to implement `Archive`, `Serialize` and `Deserialize` for `MBox<T>`,
+//! `MBox<[T]>`, `MBox<str>` and `MString`. They are archived in the same format as `Box` and
+//! `String`, and are deserialized directly into `malloc`-ed memory.
+//!
+//! ### rayon
+//!
+//! Enable the `rayon` feature to implement `IntoParallelIterator` for `MBox<[T]>` and references to
+//! it. The by-value parallel iterator moves the items out of the `malloc`-ed memory, and frees the
+//! memory once it is consumed. Parallel slice methods like `par_chunks()` work through `Deref`.
+//!
+//! ### SIMD UTF-8 validation
+//!
+//! Enable the `simdutf8` feature to validate UTF-8 using [`simdutf8`](https://crates.io/crates/simdutf8)
+//! when converting bytes or C strings into `MBox<str>` and `MString`. This speeds up the conversion
+//! of large text buffers.
+//!
+//! ### Thread-local cache
+//!
+//! Enable the `thread_local_cache` feature (which implies `std`) to keep a small per-thread cache
+//! of recently freed blocks up to 256 bytes, and reuse them for new allocations. This cuts down
+//! `malloc()`/`free()` round trips for workloads creating and dropping many small `MBox`es. The
+//! cached blocks are ordinary `malloc()` blocks, so they remain compatible with C's `free()`.
+//!
+//! Enable the `global_cache` feature to share a lock-free cache of freed blocks up to 64 KiB
+//! between all threads instead, grouped by power-of-two size classes. Services passing fixed-size
+//! FFI buffers between threads then reuse them without contending on `malloc()`, and without one
+//! thread's cache filling up while another's runs dry. It works without `std`. When both features
+//! are enabled, the thread-local cache is tried first.
+//!
+//! ### Constant-time comparison
+//!
+//! `MBox<[u8]>::ct_eq()` and `MString::ct_eq()` compare secrets in time independent of the content.
+//! Enable the `subtle` feature to also implement `subtle::ConstantTimeEq` for `MBox<[T]>` and
+//! `MString`.
+//!
+//! ### Atomic slot
+//!
+//! `mbox::atomic::AtomicMBox<T>` is an `Option<MBox<T>>` which can be swapped, taken and stored
+//! atomically, so a thread can publish freshly parsed data to readers without a mutex.
+//!
+//! ### Lazy initialization
+//!
+//! `mbox::once::MOnceCell<T>` and `MLazy<T>` allocate their content with `malloc()` on first
+//! access, like `std::cell::OnceCell` and `LazyCell`. `SyncMOnceCell<T>` and `SyncMLazy<T>` are the
+//! thread-safe variants, suitable for global caches whose storage is later handed to C. They never
+//! block: threads racing to initialize the cell all run the initializer, and only the first result
+//! is kept.
+//!
+//! ### Matrices
+//!
+//! `mbox::matrix::MBox2D<T>` stores a `rows × cols` matrix in one contiguous malloc'd block in
+//! row-major order, with row access and iteration, `(row, col)` indexing, and the raw pointer and
+//! stride expected by C image and matrix APIs.
+//!
+//! ### I/O
+//!
+//! With `std`, `mbox::cursor::MCursor` implements `Read`, `BufRead`, `Seek` and `Write` over an owned
+//! or borrowed `MBox<[u8]>`, like `std::io::Cursor<Vec<u8>>`. A malloc'd buffer can then be filled or
+//! consumed by any I/O API without copying it into a `Vec`. `read_exact_to_mbox()` and
+//! `read_to_end_mbox()` in the same module read from any `Read` directly into a new `MBox<[u8]>`, and
+//! `write_all_vectored()` writes several buffers with `writev()` without concatenating them first.
+//! `MReadBuf` is a stable counterpart of `std::io::BorrowedBuf`, which lends the uninitialized tail of
+//! an `MBox<[MaybeUninit<u8>]>` to readers while tracking the initialized prefix.
+//!
+//! Similarly, `mbox::fs::read()` and `mbox::fs::read_to_mstring()` read a whole file into an
+//! `MBox<[u8]>` or an `MString`, like `std::fs::read()` and `std::fs::read_to_string()`.
+//!
+//! ### bytes
+//!
+//! Enable the `bytes` feature to implement `bytes::Buf` for `MCursor`, and `bytes::BufMut` for
+//! `MSliceBuilder<u8>` which grows with `realloc()`, so codecs using the `bytes` traits can read from
+//! and write into malloc'd buffers directly. This requires Rust 1.57 or above.
+//!
+//! ### tokio
+//!
+//! Enable the `tokio` feature (together with `std`) to read from a `tokio::io::AsyncRead` directly
+//! into the uninitialized spare capacity of an `MSliceBuilder<u8>`, through `mbox::tokio_support`.
+//! Large network reads destined for C parsers then avoid zero-filling the buffer first. With the
+//! `bytes` feature, `AsyncReadExt::read_buf()` accepts an `MSliceBuilder<u8>` as well.
+//!
+//! ### tokio-util
+//!
+//! Enable the `tokio-util` feature to wrap a `tokio_util::codec` decoder in `mbox::codec::MBoxCodec`,
+//! which returns every decoded frame as an `MBox<[u8]>`. Framed protocol stacks can then hand the frames
+//! to C processing code without another conversion. Encoding is forwarded to the wrapped codec.
+//!
+//! ### Memory-mapped buffers
+//!
+//! On Unix, `mbox::mmap::MMap` is a byte buffer backed by an anonymous `mmap()` instead of
+//! `malloc()`, for huge buffers which should not fragment the heap. Its memory is released with
+//! `munmap()`, so unlike `MBox` it must not be passed to C's `free()`.
+//!
+//! On Windows, `MMap` is backed by `VirtualAlloc()` instead, and supports `freeze()` as well as large
+//! pages through `MMap::try_new_large_pages()`.
+//!
+//! Files can be mapped into the same ownership model, either read-only with `ReadOnlyMMap::map_file()`
+//! or copy-on-write with `MMap::map_file_copy_on_write()`. `SharedMMap` places the buffer in a `memfd`
+//! or POSIX shared memory object, whose file descriptor can be passed to other processes through the
+//! `unsafe` `SharedMMap::file()`. `SharedMMap::map_file()` maps a file writable, and `flush()` or
+//! `flush_range()` write the changes back with `msync()`. These require the `std` feature. Mapping a
+//! file is `unsafe`, since the file must not be modified or truncated while it is mapped.
+//!
+//! `MMap::freeze()` makes the pages read-only with `mprotect()`, so accidental writes fault
+//! immediately.
+//! `MMap::new_guarded()` places the buffer right before an inaccessible guard page, so overflows
+//! from C code fault immediately instead of corrupting the heap.
+//! `MMap::resize()` grows or shrinks a buffer. On Linux, the pages are moved with `mremap()`, so
+//! even multi-gigabyte buffers are resized without copying their content.
+//! All mappings accept `madvise()` hints through `advise()`, e.g. `Advice::Sequential` for streaming
+//! readers.
+//! On Linux, `MMap::try_new_huge_pages()` requests transparent or explicit huge pages for very large
+//! buffers, and `MMap::bind_to_numa_node()` pins a buffer to a NUMA node, e.g. the one returned by
+//! `mbox::mmap::current_numa_node()`.
+//!
+//! ### Secrets
+//!
+//! On Unix, `mbox::secret::SecretMBox<T>` and `SecretMString` store private keys and passphrases in
+//! pages locked with `mlock()`, so they never hit swap. The content is zeroed before the pages are
+//! unlocked and unmapped on drop. They require the `std` feature.
+//!
+//! Enable the `zeroize` feature to implement `zeroize::Zeroize` for `MBox<T>` and `MString`. The
+//! `ZeroizingMBox<T>` and `ZeroizingMString` aliases wrap them in `zeroize::Zeroizing`, which wipes
+//! the content before it is passed to `free()`.
+//!
+//! ### Statistics
+//!
+//! Enable the `stats` feature to count every `malloc()`, `realloc()` and `free()` performed by this crate
+//! in global atomic counters. `mbox::stats::snapshot()` returns the number of live blocks, the bytes in
+//! use and their peak, and the total number of each operation, ready to be exported to a metrics
+//! system. Sizes are measured with `malloc_usable_size()` or similar, and stay 0 on platforms without
+//! such a function.
+//!
+//! With `std`, `mbox::stats::MemoryGuard` turns the counters into a leak check for downstream test
+//! suites: it panics on drop if the current thread allocated more blocks than it freed since the guard
+//! was created.
+//!
+//! ### defmt
+//!
+//! Enable the `defmt` feature to implement `defmt::Format` for `MString`, `MBox<str>`, `MBox<[u8]>`
+//! and the error types, so they can be logged on embedded targets, e.g. over RTT. This requires Rust
+//! 1.76 or above.
+//!
+//! ### ufmt
+//!
+//! Enable the `ufmt` feature to implement `ufmt::uWrite` and `uDisplay` for `MString`, and
+//! `uDisplay`/`uDebug` for `MBox<T>` and `MArray<T>` when the content implements them, so
+//! messages can be formatted with `uwrite!()` directly into a malloc'd string destined for a C SDK
+//! call. This requires Rust 1.56 or above.
+//!
+//! ### abi_stable
+//!
+//! Enable the `abi_stable` feature to use the `#[repr(C)]` types of `mbox::abi_stable_support`,
+//! which implement `StableAbi` and mirror `MBox<T>`, `MBox<[T]>` and `MString`. They can appear in
+//! `sabi_trait` interfaces between dynamically loaded plugins, and convert from and into the
+//! original boxes without copying. Both sides release the memory with the same C `free()`, so a box
+//! allocated by a plugin can be dropped by the host. This requires Rust 1.61 or above.
+//!
+//! ### C API
+//!
+//! Enable the `capi` feature to export `extern "C"` functions from `mbox::capi`, e.g.
+//! `mbox_bytes_new()`, `mbox_bytes_free()`, `mbox_string_new()` and `mbox_string_free()`, so C code
+//! can create and free buffers owned by this crate. Enable the `cbindgen` feature as well to have
+//! the build script generate the matching header `mbox.h` into `OUT_DIR`, or into the path in the
+//! `MBOX_HEADER` environment variable.
+//!
+//! ### Tracing
+//!
+//! Enable the `tracing` feature to emit a `TRACE` event with the `mbox` target for every `malloc()`,
+//! `realloc()` and `free()` performed by this crate, carrying the address, the size in bytes and the
+//! type name of the block. Allocation behavior can then be correlated with the spans of a request
+//! using any `tracing` subscriber.
+//!
+//! ### Debugging
+//!
+//! Enable the `poison_on_free` feature to fill every block with `0xDD` right before it is freed, so a
+//! use-after-free through a pointer which escaped to C reads obviously garbage data instead of stale
+//! plausible values. The size of the block is found by `malloc_usable_size()` or similar, and
+//! nothing is filled on platforms without such a function.
+//!
+//! Enable the `canary` feature (which implies `std` and Rust 1.63) to allocate 16 extra bytes after
+//! every block, filled with a known pattern which is verified when the block is reallocated or freed.
+//! If C code wrote past the end of a buffer it was handed, the check panics with the address and size
+//! of the block.
+//!
+//! Enable the `track_ownership` feature (which implies `std` and Rust 1.63) to record every live block
+//! in a global table, noting whether it is currently owned by an `MBox`. Calling `from_raw()` twice on
+//! the same pointer then panics with "double ownership detected", and freeing a pointer which was
+//! already freed, or was never allocated nor adopted by this crate, panics with "invalid free
+//! detected". The table is protected by a single mutex, so this is slow and only meant for debugging.
+//!
+//! Enable the `quarantine` feature (which implies `std`, `poison_on_free` and Rust 1.63) to hold the
+//! last 1024 freed blocks in a global queue, filled with poison, before actually freeing them. A dangling
+//! pointer kept by C code then keeps pointing to poison instead of memory reused by another allocation,
+//! even when the use-after-free races with the free on another thread. A block which has been written
+//! while in quarantine panics with "use after free detected" when it is evicted.
+//!
+//! Enable the `track_call_sites` feature (which implies `std` and Rust 1.65) to remember every live
+//! block, where it escaped from its `MBox` through `into_raw()` or `leak()`, and optionally a backtrace
+//! of its allocation when `RUST_LIB_BACKTRACE=1` is set. `mbox::call_sites::dump()` then prints the
+//! outstanding blocks, which tells where the pointers that C code never returned came from.
+//!
+//! Enable the `asan` feature when building with `-Zsanitizer=address`, or the `valgrind` feature (which
+//! requires Rust 1.59) when running under Valgrind, to mark the spare capacity of `MSliceBuilder` as
+//! inaccessible. The tools then report an access past the pushed items precisely, although the whole
+//! buffer comes from a single allocation. The client requests of the `valgrind` feature do nothing
+//! outside of Valgrind.
+//!
+//! Under Miri, `mbox` allocates through the Rust global allocator instead of libc when `std` is
+//! enabled, so Miri checks every access against the exact layout of the block, and downstream tests
+//! using `MBox` can run under `cargo miri test`. Pointers allocated by C code are still freed by libc.
+//!
+//! ### Fuzzing
+//!
+//! Enable the `arbitrary` feature to implement `arbitrary::Arbitrary` for `MBox<T>`, `MBox<[T]>`,
+//! `MBox<str>` and `MString`, so fuzz targets can generate `malloc`-backed inputs directly. The
+//! generated `MString`s never contain interior NULs.
+//!
+//! Similarly, enable the `proptest` feature to implement `proptest::arbitrary::Arbitrary` for these
+//! types, so `any::<MString>()` can be used in property tests.
+//!
+//! ### Test utilities
+//!
+//! Enable the `test_util` feature to use the `DropCounter` and `PanicOnClone` helpers from
+//! `mbox::test_util`, which this crate uses to test that every item is dropped exactly once, also when
+//! a `clone()` panics. Downstream crates can use them to test their own `MBox`-owning wrappers.
+//!
 //! ## Migrating from other crates
 //!
 //! Note that `MBox` does not support custom allocator. If the type requires custom allocation,
@@ -120,20 +409,141 @@
 
 #![cfg_attr(
     feature = "nightly",
-    feature(min_specialization, unsize, coerce_unsized)
+    feature(allocator_api, min_specialization, trusted_len, unsize, coerce_unsized)
 )]
 #![cfg_attr(not(feature = "std"), no_std)]
 
+#[cfg(feature = "abi_stable")]
+extern crate abi_stable;
+#[cfg(feature = "arbitrary")]
+extern crate arbitrary;
+#[cfg(feature = "bytes")]
+extern crate bytes;
 #[cfg(not(feature = "std"))]
 extern crate core as std;
+#[cfg(feature = "defmt")]
+extern crate defmt;
 extern crate libc;
+#[cfg(feature = "proptest")]
+extern crate proptest;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "rkyv")]
+extern crate rkyv;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "simdutf8")]
+extern crate simdutf8;
 #[cfg(feature = "stable_deref_trait")]
 extern crate stable_deref_trait;
+#[cfg(feature = "subtle")]
+extern crate subtle;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+#[cfg(feature = "tokio-util")]
+extern crate tokio_util;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+#[cfg(feature = "ufmt")]
+extern crate ufmt;
+#[cfg(feature = "zeroize")]
+extern crate zeroize;
+
+#[macro_use]
+mod macros;
 
+// The modules of features which need a newer compiler than the MSRV, as documented above, allow
+// `clippy::incompatible_msrv` as a whole. Elsewhere, it is only allowed on items using the C types of
+// libc: recent libc versions re-export them from `core::ffi`, which clippy dates to Rust 1.64, but
+// the older libc used on the MSRV defines them itself.
+#[cfg(all(feature = "abi_stable", feature = "std"))]
+#[allow(clippy::incompatible_msrv)]
+pub mod abi_stable_support;
+#[cfg(all(feature = "nightly", feature = "std", not(windows)))]
+#[allow(clippy::incompatible_msrv)]
+pub mod allocator;
+#[cfg(feature = "arbitrary")]
+mod arbitrary_support;
+pub mod atomic;
+#[cfg(feature = "bytes")]
+#[allow(clippy::incompatible_msrv)]
+mod bytes_support;
+#[cfg(feature = "thread_local_cache")]
+mod cache;
+#[cfg(feature = "track_call_sites")]
+#[allow(clippy::incompatible_msrv)]
+pub mod call_sites;
+#[cfg(feature = "canary")]
+#[allow(clippy::incompatible_msrv)]
+mod canary;
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "tokio-util")]
+#[allow(clippy::incompatible_msrv)]
+pub mod codec;
+#[cfg(feature = "std")]
+pub mod cursor;
+#[cfg(feature = "defmt")]
+#[allow(clippy::incompatible_msrv)]
+mod defmt_support;
+pub mod error;
+#[cfg(feature = "esp_idf")]
+pub mod esp_idf;
 pub mod free;
+#[cfg(feature = "std")]
+pub mod fs;
+#[cfg(feature = "global_cache")]
+mod global_cache;
 mod internal;
+pub mod matrix;
 pub mod mbox;
+#[cfg(all(feature = "std", any(miri, all(test, mbox_const_mutex))))]
+mod miri_heap;
+#[cfg(unix)]
+pub mod mmap;
+#[cfg(windows)]
+#[path = "mmap_windows.rs"]
+pub mod mmap;
+pub mod once;
+#[cfg(feature = "proptest")]
+mod proptest_support;
+#[cfg(feature = "quarantine")]
+#[allow(clippy::incompatible_msrv)]
+mod quarantine;
+#[cfg(feature = "rayon")]
+#[allow(clippy::incompatible_msrv)]
+pub mod rayon_support;
+#[cfg(feature = "track_ownership")]
+#[allow(clippy::incompatible_msrv)]
+mod registry;
+#[cfg(feature = "rkyv")]
+mod rkyv_support;
+mod sanitizer;
+#[cfg(all(unix, feature = "std"))]
+pub mod secret;
 pub mod sentinel;
+#[cfg(feature = "serde")]
+pub mod serde_bytes;
+#[cfg(feature = "serde")]
+mod serde_support;
+#[cfg(feature = "stats")]
+pub mod stats;
+#[cfg(feature = "subtle")]
+mod subtle_support;
+#[cfg(any(test, feature = "test_util"))]
+pub mod test_util;
+#[cfg(all(feature = "tokio", feature = "std"))]
+#[allow(clippy::incompatible_msrv)]
+pub mod tokio_support;
+#[cfg(feature = "tracing")]
+#[allow(clippy::incompatible_msrv)]
+mod tracing_support;
+#[cfg(feature = "ufmt")]
+mod ufmt_support;
+#[cfg(feature = "zeroize")]
+mod zeroize_support;
 
-pub use self::mbox::MBox;
-pub use self::sentinel::{MArray, MString};
+pub use self::mbox::{concat, join, MBox};
+pub use self::sentinel::{concat_str, join_str, MArray, MString};
+#[cfg(feature = "zeroize")]
+pub use self::zeroize_support::{ZeroizingMBox, ZeroizingMString};
@@ -0,0 +1,72 @@
+//! Macros.
+
+/// Creates an `MString` using interpolation of runtime expressions, similar to `format!`.
+///
+/// The string is formatted directly into `malloc`-ed memory, without going through the Rust
+/// allocator.
+///
+/// ```rust
+/// # #[macro_use] extern crate mbox;
+/// # fn main() {
+/// let string = mformat!("{} + {} = {}", 1, 2, 1 + 2);
+/// assert_eq!(&*string, "1 + 2 = 3");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! mformat {
+    ($($arg:tt)*) => {{
+        let mut string = $crate::MString::default();
+        ::core::fmt::Write::write_fmt(&mut string, format_args!($($arg)*))
+            .expect("a formatting trait implementation returned an error");
+        string
+    }};
+}
+
+/// Creates an `MBox<[T]>` containing the arguments, similar to `vec!`.
+///
+/// `mbox![elem; n]` creates a boxed slice of `n` clones of `elem`, and `mbox![a, b, c]` creates a
+/// boxed slice containing the listed items. The items are moved straight into `malloc`-ed memory.
+///
+/// ```rust
+/// # #[macro_use] extern crate mbox;
+/// # fn main() {
+/// let zeros = mbox![0u8; 4];
+/// assert_eq!(&*zeros, &[0, 0, 0, 0]);
+///
+/// let list = mbox![1u8, 2, 3];
+/// assert_eq!(&*list, &[1, 2, 3]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! mbox {
+    () => {
+        <$crate::MBox<[_]> as ::core::default::Default>::default()
+    };
+    ($elem:expr; $n:expr) => {
+        $crate::MBox::<[_]>::from_elem($elem, $n)
+    };
+    ($($x:expr),+ $(,)?) => {{
+        let mut builder = $crate::mbox::MSliceBuilder::with_capacity(
+            <[&str]>::len(&[$(stringify!($x)),+])
+        );
+        $(builder.push($x);)*
+        builder.into_mboxed_slice()
+    }};
+}
+
+/// Creates an `MString` from a string literal (or any `&str` expression).
+///
+/// ```rust
+/// # #[macro_use] extern crate mbox;
+/// # fn main() {
+/// let string = mstr!("hello");
+/// assert_eq!(&*string, "hello");
+/// assert_eq!(string.as_bytes_with_sentinel(), b"hello\0");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! mstr {
+    ($string:expr) => {
+        $crate::MString::from($string)
+    };
+}
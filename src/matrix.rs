@@ -0,0 +1,328 @@
+//! Two-dimensional buffers.
+//!
+//! `MBox2D<T>` stores `rows × cols` items in one contiguous `malloc()`'ed block in row-major order,
+//! which is how images and matrices are usually exchanged with C. The raw accessors give the
+//! pointer and the row stride expected by such APIs.
+//!
+//! ```
+//! use mbox::matrix::MBox2D;
+//!
+//! let mut image = MBox2D::from_elem(0u8, 2, 3);
+//! image[(1, 2)] = 255;
+//! image.row_mut(0).copy_from_slice(&[1, 2, 3]);
+//! assert_eq!(image.as_slice(), &[1, 2, 3, 0, 0, 255]);
+//! assert_eq!(image.stride(), 3);
+//! ```
+
+use std::fmt::{Debug, Formatter, Result as FormatResult};
+use std::iter::FusedIterator;
+use std::mem::{replace, size_of};
+use std::ops::{Index, IndexMut};
+
+use crate::mbox::MBox;
+
+/// Computes the number of items of a `rows × cols` matrix.
+fn area(rows: usize, cols: usize) -> usize {
+    rows.checked_mul(cols).expect("memory overflow")
+}
+
+/// A `malloc`-backed matrix with `rows × cols` items stored contiguously in row-major order.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct MBox2D<T> {
+    data: MBox<[T]>,
+    rows: usize,
+    cols: usize,
+}
+
+impl<T> MBox2D<T> {
+    /// Wraps a boxed slice of `rows × cols` items in row-major order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `data` is not `rows × cols`.
+    pub fn from_mbox(data: MBox<[T]>, rows: usize, cols: usize) -> Self {
+        assert_eq!(data.len(), area(rows, cols), "dimensions do not match");
+        Self { data, rows, cols }
+    }
+
+    /// Creates a matrix by calling `f(row, col)` for every item, in row-major order.
+    pub fn from_fn<F: FnMut(usize, usize) -> T>(rows: usize, cols: usize, mut f: F) -> Self {
+        let data = (0..area(rows, cols))
+            .map(|i| f(i / cols, i % cols))
+            .collect();
+        Self { data, rows, cols }
+    }
+
+    /// Constructs a matrix from a raw pointer to `rows × cols` items.
+    ///
+    /// # Safety
+    ///
+    /// The `ptr` must be allocated via `malloc()`, `calloc()` or similar C functions that is
+    /// expected to be deallocated using `free()`. It must not be null. The content must be already
+    /// initialized, and holds `rows × cols` items without padding between rows. The ownership of
+    /// the items is passed into the result, and thus the pointer should not be used after this
+    /// function returns.
+    pub unsafe fn from_raw_parts(ptr: *mut T, rows: usize, cols: usize) -> Self {
+        Self {
+            data: MBox::from_raw_parts(ptr, area(rows, cols)),
+            rows,
+            cols,
+        }
+    }
+
+    /// Decomposes the matrix into the raw pointer, the number of rows and the number of columns.
+    /// The ownership of the items is passed to the caller.
+    pub fn into_raw_parts(self) -> (*mut T, usize, usize) {
+        let (ptr, _) = self.data.into_raw_parts();
+        (ptr, self.rows, self.cols)
+    }
+
+    /// Converts into the underlying boxed slice in row-major order.
+    pub fn into_mbox(self) -> MBox<[T]> {
+        self.data
+    }
+
+    /// Returns the number of rows.
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Returns the number of columns.
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Returns the number of items between the starts of two adjacent rows. Since rows are never
+    /// padded, this is always the number of columns.
+    pub fn stride(&self) -> usize {
+        self.cols
+    }
+
+    /// Returns the number of bytes between the starts of two adjacent rows.
+    pub fn byte_stride(&self) -> usize {
+        self.cols * size_of::<T>()
+    }
+
+    /// Returns a raw pointer to the first item.
+    pub fn as_ptr(&self) -> *const T {
+        self.data.as_ptr()
+    }
+
+    /// Returns a mutable raw pointer to the first item.
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        self.data.as_mut_ptr()
+    }
+
+    /// Returns all items in row-major order.
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+
+    /// Returns all items in row-major order, mutably.
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.data
+    }
+
+    /// Returns the item at (`row`, `col`), or `None` if it is out of bounds.
+    pub fn get(&self, row: usize, col: usize) -> Option<&T> {
+        if row < self.rows && col < self.cols {
+            self.data.get(row * self.cols + col)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the item at (`row`, `col`) mutably, or `None` if it is out of bounds.
+    pub fn get_mut(&mut self, row: usize, col: usize) -> Option<&mut T> {
+        if row < self.rows && col < self.cols {
+            self.data.get_mut(row * self.cols + col)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the items of a row.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `row` is out of bounds.
+    pub fn row(&self, row: usize) -> &[T] {
+        assert!(row < self.rows, "row out of bounds");
+        &self.data[row * self.cols..][..self.cols]
+    }
+
+    /// Returns the items of a row mutably.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `row` is out of bounds.
+    pub fn row_mut(&mut self, row: usize) -> &mut [T] {
+        assert!(row < self.rows, "row out of bounds");
+        &mut self.data[row * self.cols..][..self.cols]
+    }
+
+    /// Iterates over the rows.
+    pub fn iter_rows(&self) -> Rows<'_, T> {
+        Rows {
+            rest: &self.data,
+            cols: self.cols,
+            rows: self.rows,
+        }
+    }
+
+    /// Iterates over the rows mutably.
+    pub fn iter_rows_mut(&mut self) -> RowsMut<'_, T> {
+        RowsMut {
+            rest: &mut self.data,
+            cols: self.cols,
+            rows: self.rows,
+        }
+    }
+}
+
+impl<T: Clone> MBox2D<T> {
+    /// Creates a matrix filled with clones of `elem`.
+    pub fn from_elem(elem: T, rows: usize, cols: usize) -> Self {
+        Self {
+            data: MBox::from_elem(elem, area(rows, cols)),
+            rows,
+            cols,
+        }
+    }
+}
+
+impl<T> Index<(usize, usize)> for MBox2D<T> {
+    type Output = T;
+
+    fn index(&self, (row, col): (usize, usize)) -> &T {
+        self.get(row, col).expect("index out of bounds")
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for MBox2D<T> {
+    fn index_mut(&mut self, (row, col): (usize, usize)) -> &mut T {
+        self.get_mut(row, col).expect("index out of bounds")
+    }
+}
+
+impl<T: Debug> Debug for MBox2D<T> {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        formatter.debug_list().entries(self.iter_rows()).finish()
+    }
+}
+
+/// An iterator over the rows of an `MBox2D`.
+#[derive(Clone, Debug)]
+pub struct Rows<'a, T> {
+    rest: &'a [T],
+    cols: usize,
+    rows: usize,
+}
+
+impl<'a, T> Iterator for Rows<'a, T> {
+    type Item = &'a [T];
+
+    fn next(&mut self) -> Option<&'a [T]> {
+        if self.rows == 0 {
+            return None;
+        }
+        self.rows -= 1;
+        let (row, rest) = self.rest.split_at(self.cols);
+        self.rest = rest;
+        Some(row)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.rows, Some(self.rows))
+    }
+}
+
+impl<T> ExactSizeIterator for Rows<'_, T> {}
+
+impl<T> FusedIterator for Rows<'_, T> {}
+
+/// An iterator over the rows of an `MBox2D`, mutably.
+#[derive(Debug)]
+pub struct RowsMut<'a, T> {
+    rest: &'a mut [T],
+    cols: usize,
+    rows: usize,
+}
+
+impl<'a, T> Iterator for RowsMut<'a, T> {
+    type Item = &'a mut [T];
+
+    fn next(&mut self) -> Option<&'a mut [T]> {
+        if self.rows == 0 {
+            return None;
+        }
+        self.rows -= 1;
+        let (row, rest) = replace(&mut self.rest, &mut []).split_at_mut(self.cols);
+        self.rest = rest;
+        Some(row)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.rows, Some(self.rows))
+    }
+}
+
+impl<T> ExactSizeIterator for RowsMut<'_, T> {}
+
+impl<T> FusedIterator for RowsMut<'_, T> {}
+
+#[test]
+fn test_matrix() {
+    let mut matrix = MBox2D::from_fn(3, 2, |r, c| (r * 10 + c) as u32);
+    assert_eq!((matrix.rows(), matrix.cols()), (3, 2));
+    assert_eq!(matrix.as_slice(), &[0, 1, 10, 11, 20, 21]);
+    assert_eq!(matrix[(2, 1)], 21);
+    assert_eq!(matrix.get(3, 0), None);
+    assert_eq!(matrix.get(0, 2), None);
+    assert_eq!(matrix.row(1), &[10, 11]);
+    assert_eq!(matrix.byte_stride(), 8);
+
+    matrix[(0, 1)] = 5;
+    for row in matrix.iter_rows_mut() {
+        row[0] += 100;
+    }
+    let rows = matrix.iter_rows();
+    assert_eq!(rows.len(), 3);
+    assert_eq!(
+        rows.collect::<MBox<[_]>>(),
+        MBox::from_slice(&[&[100, 5][..], &[110, 11], &[120, 21]])
+    );
+
+    let (ptr, rows, cols) = matrix.into_raw_parts();
+    let matrix = unsafe { MBox2D::from_raw_parts(ptr, rows, cols) };
+    assert_eq!(&*matrix.into_mbox(), &[100, 5, 110, 11, 120, 21]);
+}
+
+#[test]
+fn test_matrix_empty() {
+    let mut matrix = MBox2D::<u8>::from_elem(7, 3, 0);
+    assert_eq!(matrix.iter_rows().count(), 3);
+    assert!(matrix.iter_rows_mut().all(|row| row.is_empty()));
+    assert_eq!(matrix.get(0, 0), None);
+
+    let matrix = MBox2D::from_mbox(MBox::<[u8]>::new_empty(), 0, 5);
+    assert_eq!(matrix.iter_rows().next(), None);
+}
+
+#[test]
+#[should_panic(expected = "dimensions do not match")]
+fn test_matrix_wrong_dimensions() {
+    MBox2D::from_mbox(MBox::from_slice(&[1u8, 2, 3]), 2, 2);
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_matrix_drop() {
+    use crate::test_util::DropCounter;
+
+    let counter = DropCounter::default();
+    let matrix = MBox2D::from_elem(counter.clone(), 2, 2);
+    counter.assert_eq(0);
+    drop(matrix);
+    counter.assert_eq(4);
+}
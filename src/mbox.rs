@@ -3,34 +3,47 @@
 #[cfg(feature = "stable_deref_trait")]
 use stable_deref_trait::StableDeref;
 
+use std::any::TypeId;
+#[cfg(feature = "std")]
+use std::borrow::Cow;
 use std::cmp::Ordering;
-use std::convert::{AsMut, AsRef};
-use std::fmt::{Debug, Display, Formatter, Pointer, Result as FormatResult};
+use std::convert::{AsMut, AsRef, TryFrom};
+use std::fmt::{Debug, Display, Formatter, Pointer, Result as FormatResult, Write};
+use std::future::Future;
 use std::hash::{Hash, Hasher};
-use std::iter::{DoubleEndedIterator, FromIterator, IntoIterator};
-use std::marker::Unpin;
-use std::mem::{forget, MaybeUninit};
+use std::iter::{DoubleEndedIterator, FromIterator, FusedIterator, IntoIterator};
+use std::marker::{PhantomData, Unpin};
+use std::mem::{align_of, forget, size_of, transmute, MaybeUninit};
 use std::ops::{Deref, DerefMut};
 use std::pin::Pin;
-use std::ptr::{copy_nonoverlapping, drop_in_place, read, write};
+use std::ptr::{copy_nonoverlapping, drop_in_place, read, write, write_bytes};
+#[cfg(feature = "std")]
+use std::rc::Rc;
 use std::slice::{Iter, IterMut};
-use std::str::{from_utf8, Utf8Error};
+use std::str::Utf8Error;
+#[cfg(feature = "std")]
+use std::sync::Arc;
+use std::task::{Context, Poll};
 use std::{
     borrow::{Borrow, BorrowMut},
     ptr::NonNull,
 };
 
-use crate::internal::{gen_free, gen_malloc, gen_realloc, Unique};
+use crate::error::{AllocError, FromUtf8Error};
+use crate::internal::{
+    copy_repeated, ct_eq_bytes, from_utf8, gen_calloc, gen_free, gen_malloc, gen_realloc,
+    joined_len, try_gen_calloc, try_gen_malloc, try_gen_realloc, EmptySlice, Unique,
+};
 
 #[cfg(all(test, not(windows)))]
-use crate::internal::DropCounter;
+use crate::test_util::DropCounter;
 #[cfg(test)]
-use crate::internal::PanicOnClone;
+use crate::test_util::PanicOnClone;
 #[cfg(test)]
 use std::iter::{once, repeat};
-#[cfg(all(test, not(windows)))]
-use std::mem::size_of;
 
+#[cfg(feature = "nightly")]
+use std::iter::TrustedLen;
 #[cfg(feature = "nightly")]
 use std::marker::Unsize;
 #[cfg(feature = "nightly")]
@@ -56,6 +69,11 @@ impl<T: ?Sized + Free> MBox<T> {
     /// Note that even when `T` is zero-sized, the input `ptr` is *still* expected to be released using
     /// `free()`. Therefore, you must not use a conceived dangling pointer such as `NonNull::dangling()`
     /// here. Consider using `malloc(1)` in case of ZSTs.
+    ///
+    /// With the `freertos` feature, or on targets other than Unix and Windows, if `T` is aligned to
+    /// more than `malloc()` guarantees (8 bytes with FreeRTOS, the pointer size elsewhere), the
+    /// block carries a hidden header. The `ptr` must then be obtained from this crate, e.g. by
+    /// `into_raw()`, rather than from C's `malloc()`, even if it is properly aligned.
     pub unsafe fn from_raw(ptr: *mut T) -> Self {
         Self::from_non_null_raw(NonNull::new_unchecked(ptr))
     }
@@ -72,7 +90,16 @@ impl<T: ?Sized + Free> MBox<T> {
     /// Note that even when `T` is zero-sized, the input `ptr` is *still* expected to be released using
     /// `free()`. Therefore, you must not use a conceived dangling pointer such as `NonNull::dangling()`
     /// here. Consider using `malloc(1)` in case of ZSTs.
+    ///
+    /// With the `freertos` feature, or on targets other than Unix and Windows, if `T` is aligned to
+    /// more than `malloc()` guarantees (8 bytes with FreeRTOS, the pointer size elsewhere), the
+    /// block carries a hidden header. The `ptr` must then be obtained from this crate, e.g. by
+    /// `into_raw()`, rather than from C's `malloc()`, even if it is properly aligned.
     pub unsafe fn from_non_null_raw(ptr: NonNull<T>) -> Self {
+        #[cfg(feature = "track_ownership")]
+        crate::registry::acquire(ptr.as_ptr() as *mut libc::c_void);
+        #[cfg(feature = "track_call_sites")]
+        crate::call_sites::returned(ptr.as_ptr() as *mut libc::c_void);
         Self(Unique::new(ptr))
     }
 
@@ -89,6 +116,7 @@ impl<T: ?Sized + Free> MBox<T> {
     /// Consumes the box and returns the original pointer.
     ///
     /// The caller is responsible for `free`ing the pointer after this.
+    #[cfg_attr(feature = "track_call_sites", track_caller)]
     pub fn into_raw(boxed: Self) -> *mut T {
         Self::into_non_null_raw(boxed).as_ptr()
     }
@@ -96,11 +124,24 @@ impl<T: ?Sized + Free> MBox<T> {
     /// Consumes the box and returns the original non-null pointer.
     ///
     /// The caller is responsible for `free`ing the pointer after this.
+    #[cfg_attr(feature = "track_call_sites", track_caller)]
     pub fn into_non_null_raw(boxed: Self) -> NonNull<T> {
         let ptr = boxed.0.as_non_null_ptr();
         forget(boxed);
+        #[cfg(feature = "track_ownership")]
+        crate::registry::release(ptr.as_ptr() as *mut libc::c_void);
+        #[cfg(feature = "track_call_sites")]
+        crate::call_sites::escaped(ptr.as_ptr() as *mut libc::c_void);
         ptr
     }
+
+    /// Converts an `MBox<T>` into a `Pin<MBox<T>>`.
+    ///
+    /// This conversion does not allocate on the heap and happens in place.
+    pub fn into_pin(boxed: Self) -> Pin<Self> {
+        // SAFETY: Same reason as why `Box::into_pin` is safe.
+        unsafe { Pin::new_unchecked(boxed) }
+    }
 }
 
 impl<T: ?Sized + Free> Drop for MBox<T> {
@@ -120,8 +161,24 @@ impl<T: ?Sized + Free> Deref for MBox<T> {
 #[cfg(feature = "stable_deref_trait")]
 unsafe impl<T: ?Sized + Free> StableDeref for MBox<T> {}
 
+// The box itself can be moved freely even if `T` is pinned, because moving the box never moves
+// the pointee. This is the same as `Box<T>`.
 impl<T: ?Sized + Free> Unpin for MBox<T> {}
 
+impl<T: ?Sized + Free> From<MBox<T>> for Pin<MBox<T>> {
+    fn from(boxed: MBox<T>) -> Self {
+        MBox::into_pin(boxed)
+    }
+}
+
+impl<F: ?Sized + Free + Future + Unpin> Future for MBox<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context) -> Poll<Self::Output> {
+        F::poll(Pin::new(&mut **self), context)
+    }
+}
+
 impl<T: ?Sized + Free> DerefMut for MBox<T> {
     fn deref_mut(&mut self) -> &mut T {
         unsafe { &mut *Self::as_mut_ptr(self) }
@@ -215,6 +272,17 @@ impl<T> MBox<T> {
         }
     }
 
+    /// Constructs a new malloc-backed box, and move an initialized value into it. Returns an
+    /// `AllocError` instead of aborting if the memory cannot be allocated.
+    pub fn try_new(value: T) -> Result<Self, AllocError> {
+        let storage = try_gen_malloc(1)?;
+        // SAFETY: same as `new()`.
+        unsafe {
+            write(storage.as_ptr(), value);
+            Ok(Self::from_non_null_raw(storage))
+        }
+    }
+
     /// Constructs a new malloc-backed box with uninitialized content.
     pub fn new_uninit() -> MBox<MaybeUninit<T>> {
         let storage = gen_malloc(1);
@@ -222,6 +290,29 @@ impl<T> MBox<T> {
         unsafe { MBox::from_non_null_raw(storage) }
     }
 
+    /// Constructs a new malloc-backed box with the content filled with zeros, using `calloc()`.
+    pub fn new_zeroed() -> MBox<MaybeUninit<T>> {
+        let storage = gen_calloc(1);
+        // SAFETY: The storage is allowed to be uninitialized.
+        unsafe { MBox::from_non_null_raw(storage) }
+    }
+
+    /// Constructs a new malloc-backed box with uninitialized content. Returns an `AllocError`
+    /// instead of aborting if the memory cannot be allocated.
+    pub fn try_new_uninit() -> Result<MBox<MaybeUninit<T>>, AllocError> {
+        let storage = try_gen_malloc(1)?;
+        // SAFETY: The storage is allowed to be uninitialized.
+        unsafe { Ok(MBox::from_non_null_raw(storage)) }
+    }
+
+    /// Constructs a new malloc-backed box with the content filled with zeros, using `calloc()`.
+    /// Returns an `AllocError` instead of aborting if the memory cannot be allocated.
+    pub fn try_new_zeroed() -> Result<MBox<MaybeUninit<T>>, AllocError> {
+        let storage = try_gen_calloc(1)?;
+        // SAFETY: The storage is allowed to be uninitialized.
+        unsafe { Ok(MBox::from_non_null_raw(storage)) }
+    }
+
     /// Constructs a new `Pin<MBox<T>>`. If `T` does not implement `Unpin`, then `value` will be
     /// pinned in memory and cannot be moved.
     pub fn pin(value: T) -> Pin<Self> {
@@ -252,15 +343,8 @@ impl<T> MBox<T> {
         }
     }
 
-    /// Converts an `MBox<T>` into a `Pin<MBox<T>>`.
-    ///
-    /// This conversion does not allocate on the heap and happens in place.
-    pub fn into_pin(boxed: Self) -> Pin<Self> {
-        // SAFETY: Same reason as why `Box::into_pin` is safe.
-        unsafe { Pin::new_unchecked(boxed) }
-    }
-
     /// Consumes and leaks the `MBox`, returning a mutable reference, `&'a mut T`.
+    #[cfg_attr(feature = "track_call_sites", track_caller)]
     pub fn leak<'a>(boxed: Self) -> &'a mut T
     where
         T: 'a,
@@ -423,6 +507,7 @@ fn test_zero_sized_type() {
 
 #[cfg(not(windows))]
 #[test]
+#[allow(clippy::nonminimal_bool)]
 fn test_non_zero() {
     let b = 0u64;
     assert!(!Some(MBox::new(0u64)).is_none());
@@ -458,14 +543,87 @@ fn test_aligned() {
     assert_eq!(MBox::as_ptr(&b) as usize % 4096, 0);
 }
 
+#[test]
+fn test_pin() {
+    use std::marker::PhantomPinned;
+
+    struct SelfReferential {
+        address: *const SelfReferential,
+        _pinned: PhantomPinned,
+    }
+
+    let mut pinned = MBox::pin(SelfReferential {
+        address: std::ptr::null(),
+        _pinned: PhantomPinned,
+    });
+    let address = &*pinned as *const SelfReferential;
+    // SAFETY: the pinned value is not moved.
+    unsafe { pinned.as_mut().get_unchecked_mut().address = address };
+
+    // moving the `Pin<MBox<T>>` itself does not move the pointee.
+    let moved = pinned;
+    assert_eq!(moved.address, &*moved as *const SelfReferential);
+
+    let mut pinned: Pin<MBox<u8>> = MBox::new(3u8).into();
+    assert_eq!(*pinned, 3);
+    *Pin::get_mut(pinned.as_mut()) = 4;
+    assert_eq!(*pinned, 4);
+}
+
+#[test]
+fn test_future() {
+    use std::task::{RawWaker, RawWakerVTable, Waker};
+
+    // `std::future::ready` needs Rust 1.48.
+    struct Ready(Option<u8>);
+    impl Future for Ready {
+        type Output = u8;
+        fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<u8> {
+            Poll::Ready(self.0.take().unwrap())
+        }
+    }
+
+    unsafe fn clone(_: *const ()) -> RawWaker {
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+    unsafe fn noop(_: *const ()) {}
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+
+    // SAFETY: the vtable does nothing and never touches the data pointer.
+    let waker = unsafe { Waker::from_raw(clone(std::ptr::null())) };
+    let mut context = Context::from_waker(&waker);
+    let mut future = MBox::new(Ready(Some(5)));
+    assert_eq!(Pin::new(&mut future).poll(&mut context), Poll::Ready(5));
+}
+
 //}}}
 
 //{{{ Slice helpers -------------------------------------------------------------------------------
 
 mod slice_helper {
     use super::*;
+    use crate::sanitizer;
+    use libc::c_void;
 
-    /// A `Vec`-like structure backed by `malloc()`.
+    /// A `Vec`-like structure backed by `malloc()`, for building an `MBox<[T]>` item by item.
+    ///
+    /// Only the first `len()` items are initialized. If the builder is dropped before it is
+    /// finished, e.g. when a panic unwinds through it, these items are dropped and the buffer is
+    /// freed. The uninitialized tail can be filled by C code through `spare_capacity()` and
+    /// `commit_spare()`.
+    ///
+    /// ```
+    /// use mbox::mbox::MSliceBuilder;
+    /// use mbox::MBox;
+    ///
+    /// let mut builder = MSliceBuilder::from_uninit(MBox::<[u8]>::new_uninit_slice(4));
+    /// builder.push(1);
+    /// builder.write_slice(&[2, 3]);
+    /// let spare = builder.spare_capacity();
+    /// spare[0] = std::mem::MaybeUninit::new(4);
+    /// unsafe { builder.commit_spare(1) };
+    /// assert_eq!(&*builder.into_mboxed_slice(), &[1, 2, 3, 4]);
+    /// ```
     pub struct MSliceBuilder<T> {
         ptr: NonNull<T>,
         cap: usize,
@@ -475,14 +633,65 @@ mod slice_helper {
     impl<T> MSliceBuilder<T> {
         /// Creates a new slice builder with an initial capacity.
         pub fn with_capacity(cap: usize) -> MSliceBuilder<T> {
-            MSliceBuilder {
+            let builder = MSliceBuilder {
                 ptr: gen_malloc(cap),
                 cap,
                 len: 0,
+            };
+            builder.poison_spare();
+            builder
+        }
+
+        /// Creates a new slice builder with an initial capacity, returning an `AllocError` if the
+        /// memory cannot be allocated.
+        pub fn try_with_capacity(cap: usize) -> Result<MSliceBuilder<T>, AllocError> {
+            let builder = MSliceBuilder {
+                ptr: try_gen_malloc(cap)?,
+                cap,
+                len: 0,
+            };
+            builder.poison_spare();
+            Ok(builder)
+        }
+
+        /// Creates a new slice builder reusing the buffer of an uninitialized boxed slice. The
+        /// length of the slice becomes the initial capacity.
+        pub fn from_uninit(buf: MBox<[MaybeUninit<T>]>) -> MSliceBuilder<T> {
+            let (ptr, cap) = buf.into_raw_parts();
+            let builder = MSliceBuilder {
+                // SAFETY: the pointer of a box is never null.
+                ptr: unsafe { NonNull::new_unchecked(ptr as *mut T) },
+                cap,
+                len: 0,
+            };
+            builder.poison_spare();
+            builder
+        }
+
+        /// Marks the uninitialized tail as inaccessible for the sanitizers.
+        fn poison_spare(&self) {
+            // SAFETY: the tail is allocated, and is only accessed after being unpoisoned.
+            unsafe {
+                sanitizer::poison(
+                    self.ptr.as_ptr().add(self.len) as *const c_void,
+                    (self.cap - self.len) * size_of::<T>(),
+                );
             }
         }
 
-        pub fn push(&mut self, obj: T) {
+        /// Marks the uninitialized tail as accessible again, before it is filled or released.
+        fn unpoison_spare(&self) {
+            // SAFETY: the tail is allocated.
+            unsafe {
+                sanitizer::unpoison(
+                    self.ptr.as_ptr().add(self.len) as *const c_void,
+                    (self.cap - self.len) * size_of::<T>(),
+                );
+            }
+        }
+
+        /// Doubles the capacity if there is no spare capacity left.
+        fn grow_if_full(&mut self) {
             if self.len >= self.cap {
                 let new_cap = (self.cap * 2).max(1);
                 // SAFETY:
@@ -491,7 +700,33 @@ mod slice_helper {
                     self.ptr = gen_realloc(self.ptr, self.cap, new_cap);
                 }
                 self.cap = new_cap;
+                self.poison_spare();
+            }
+        }
+
+        /// Ensures the capacity can hold at least `additional` more items.
+        ///
+        /// # Panics
+        ///
+        /// Panics if the new capacity overflows.
+        pub fn reserve(&mut self, additional: usize) {
+            let required = self.len.checked_add(additional).expect("memory overflow");
+            if required > self.cap {
+                let new_cap = required.max(self.cap.saturating_mul(2));
+                self.unpoison_spare();
+                // SAFETY: ptr is initialized from gen_malloc() so it can be placed into
+                // gen_realloc(), and the first `len` items are kept intact.
+                unsafe {
+                    self.ptr = gen_realloc(self.ptr, self.cap, new_cap);
+                }
+                self.cap = new_cap;
+                self.poison_spare();
             }
+        }
+
+        /// Appends an item, growing the buffer using `realloc()` if it is full.
+        pub fn push(&mut self, obj: T) {
+            self.grow_if_full();
 
             // SAFETY:
             //  - we guarantee that `ptr `points to an array of nonzero length `cap`, and
@@ -499,32 +734,142 @@ mod slice_helper {
             //    `ptr.add(self.len)` is always a valid (but uninitialized) object.
             //  - since `ptr[self.len]` is not yet initialized, we can `write()` into it safely.
             unsafe {
-                write(self.ptr.as_ptr().add(self.len), obj);
+                let slot = self.ptr.as_ptr().add(self.len);
+                sanitizer::unpoison(slot as *const c_void, size_of::<T>());
+                write(slot, obj);
             }
             self.len += 1;
         }
 
-        pub fn into_mboxed_slice(self) -> MBox<[T]> {
+        /// Pushes an object without checking the capacity.
+        ///
+        /// # Safety
+        ///
+        /// The length must be less than the capacity before calling this method.
+        pub unsafe fn push_unchecked(&mut self, obj: T) {
+            let slot = self.ptr.as_ptr().add(self.len);
+            sanitizer::unpoison(slot as *const c_void, size_of::<T>());
+            write(slot, obj);
+            self.len += 1;
+        }
+
+        /// Returns the number of pushed items.
+        pub fn len(&self) -> usize {
+            self.len
+        }
+
+        /// Returns whether no items have been pushed.
+        pub fn is_empty(&self) -> bool {
+            self.len == 0
+        }
+
+        /// Returns the number of items the buffer can hold without reallocating.
+        pub fn capacity(&self) -> usize {
+            self.cap
+        }
+
+        /// Returns the pushed items.
+        pub fn as_slice(&self) -> &[T] {
+            // SAFETY: the first `len` items are initialized.
+            unsafe { &*slice_from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+        }
+
+        /// Returns the pushed items mutably.
+        pub fn as_mut_slice(&mut self) -> &mut [T] {
+            // SAFETY: the first `len` items are initialized.
+            unsafe { &mut *slice_from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+        }
+
+        /// Returns the uninitialized spare capacity, which is grown first if it is empty. The
+        /// spare capacity is accessible until `commit_spare()` is called.
+        pub fn spare_capacity(&mut self) -> &mut [MaybeUninit<T>] {
+            self.grow_if_full();
+            self.unpoison_spare();
+            // SAFETY: the tail from `len` to `cap` is allocated, and may be uninitialized.
+            unsafe {
+                &mut *slice_from_raw_parts_mut(
+                    self.ptr.as_ptr().add(self.len) as *mut MaybeUninit<T>,
+                    self.cap - self.len,
+                )
+            }
+        }
+
+        /// Extends the length by `count` items of the spare capacity, which are now initialized.
+        ///
+        /// # Safety
+        ///
+        /// The first `count` items of the spare capacity must have been initialized, after the
+        /// last call of `spare_capacity()`.
+        pub unsafe fn commit_spare(&mut self, count: usize) {
+            assert!(
+                count <= self.cap - self.len,
+                "count exceeds the spare capacity"
+            );
+            self.len += count;
+            self.poison_spare();
+        }
+
+        /// Reallocates the buffer such that the capacity equals to the length.
+        pub fn shrink_to_fit(&mut self) {
+            self.shrink_to(0);
+        }
+
+        /// Reallocates the buffer such that the capacity is the larger of the length and
+        /// `min_capacity`. Does nothing if the capacity is already that small.
+        pub fn shrink_to(&mut self, min_capacity: usize) {
+            let new_cap = self.len.max(min_capacity);
+            if new_cap < self.cap {
+                self.unpoison_spare();
+                // SAFETY: ptr is initialized from gen_malloc() so it can be placed into
+                // gen_realloc(), and the first `len` items are kept intact.
+                unsafe {
+                    self.ptr = gen_realloc(self.ptr, self.cap, new_cap);
+                }
+                self.cap = new_cap;
+                self.poison_spare();
+            }
+        }
+
+        /// Finishes building, shrinking the buffer to the number of pushed items.
+        pub fn into_mboxed_slice(mut self) -> MBox<[T]> {
+            self.shrink_to_fit();
             // SAFETY: `self.ptr` has been allocated by malloc(), and its length is self.cap
-            // (>= self.len).
+            // (== self.len).
             let slice = unsafe { MBox::from_raw_parts(self.ptr.as_ptr(), self.len) };
             forget(self);
             slice
         }
     }
 
+    impl<T: Clone> MSliceBuilder<T> {
+        /// Appends the clones of all items of `items`. If a `clone()` panics, the items pushed so
+        /// far are still dropped with the builder.
+        pub fn write_slice(&mut self, items: &[T]) {
+            self.reserve(items.len());
+            for item in items {
+                // SAFETY: the capacity has been reserved for all items.
+                unsafe { self.push_unchecked(item.clone()) };
+            }
+        }
+    }
+
     impl<T> MSliceBuilder<MaybeUninit<T>> {
         /// Sets the length of the builder to the same as the capacity. The elements in the
         /// uninitialized tail remains uninitialized.
         pub fn set_len_to_cap(&mut self) {
+            self.unpoison_spare();
             self.len = self.cap;
         }
     }
 
     impl<T> Drop for MSliceBuilder<T> {
         fn drop(&mut self) {
-            // SAFETY: `ptr` has been allocated by `gen_malloc()`.
+            self.unpoison_spare();
+            // SAFETY: `ptr` has been allocated by `gen_malloc()`, and the first `len` items are
+            // initialized. This is reached when a panic unwinds through the builder, so the pushed
+            // items must be dropped here as nobody else owns them.
             unsafe {
+                drop_in_place(slice_from_raw_parts_mut(self.ptr.as_ptr(), self.len));
                 gen_free(self.ptr);
             }
         }
@@ -538,14 +883,31 @@ mod slice_helper {
 
     impl<T> Clone for SliceParts<T> {
         fn clone(&self) -> Self {
-            Self {
-                ptr: self.ptr,
-                len: self.len,
-            }
+            *self
         }
     }
     impl<T> Copy for SliceParts<T> {}
 
+    #[cfg(feature = "asan")]
+    #[test]
+    fn test_asan_poisoned_spare_capacity() {
+        use crate::sanitizer::__asan_address_is_poisoned;
+
+        let mut builder = MSliceBuilder::<u64>::with_capacity(4);
+        builder.push(1);
+        let ptr = builder.ptr.as_ptr();
+        // SAFETY: the spare capacity is only checked, not accessed.
+        unsafe {
+            assert_eq!(__asan_address_is_poisoned(ptr as *const c_void), 0);
+            assert_ne!(__asan_address_is_poisoned(ptr.add(1) as *const c_void), 0);
+            assert_ne!(__asan_address_is_poisoned(ptr.add(3) as *const c_void), 0);
+        }
+        builder.push(2);
+        // SAFETY: the spare capacity is only checked, not accessed.
+        unsafe { assert_eq!(__asan_address_is_poisoned(ptr.add(1) as *const c_void), 0) };
+        assert_eq!(&*builder.into_mboxed_slice(), &[1, 2]);
+    }
+
     #[repr(C)]
     union SliceTransformer<T> {
         fat_ptr: *mut [T],
@@ -569,7 +931,8 @@ mod slice_helper {
     }
 }
 
-use self::slice_helper::{slice_from_raw_parts_mut, slice_into_raw_parts_mut, MSliceBuilder};
+pub use self::slice_helper::MSliceBuilder;
+use self::slice_helper::{slice_from_raw_parts_mut, slice_into_raw_parts_mut};
 
 /// The iterator returned from `MBox<[T]>::into_iter()`.
 pub struct MSliceIntoIter<T> {
@@ -578,6 +941,24 @@ pub struct MSliceIntoIter<T> {
     end: usize,
 }
 
+impl<T> MSliceIntoIter<T> {
+    /// Returns the remaining items of this iterator as a slice.
+    pub fn as_slice(&self) -> &[T] {
+        // SAFETY: the items in `begin..end` are initialized and not yet moved out.
+        unsafe {
+            &*slice_from_raw_parts_mut(self.ptr.as_ptr().add(self.begin), self.end - self.begin)
+        }
+    }
+
+    /// Returns the remaining items of this iterator as a mutable slice.
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        // SAFETY: the items in `begin..end` are initialized and not yet moved out.
+        unsafe {
+            &mut *slice_from_raw_parts_mut(self.ptr.as_ptr().add(self.begin), self.end - self.begin)
+        }
+    }
+}
+
 impl<T> Iterator for MSliceIntoIter<T> {
     type Item = T;
 
@@ -618,6 +999,17 @@ unsafe impl<T: Sync> Sync for MSliceIntoIter<T> {}
 
 impl<T> ExactSizeIterator for MSliceIntoIter<T> {}
 
+impl<T> FusedIterator for MSliceIntoIter<T> {}
+
+impl<T: Debug> Debug for MSliceIntoIter<T> {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        formatter
+            .debug_tuple("MSliceIntoIter")
+            .field(&self.as_slice())
+            .finish()
+    }
+}
+
 impl<T> Drop for MSliceIntoIter<T> {
     fn drop(&mut self) {
         unsafe {
@@ -630,6 +1022,45 @@ impl<T> Drop for MSliceIntoIter<T> {
     }
 }
 
+/// The iterator returned from `MBox<[T]>::into_chunks()`.
+pub struct MSliceIntoChunks<T> {
+    iter: MSliceIntoIter<T>,
+    chunk_len: usize,
+}
+
+impl<T> Iterator for MSliceIntoChunks<T> {
+    type Item = MBox<[T]>;
+
+    fn next(&mut self) -> Option<MBox<[T]>> {
+        if self.iter.len() == 0 {
+            None
+        } else {
+            // `Take` reports an exact size hint, so each chunk is allocated exactly once.
+            Some(self.iter.by_ref().take(self.chunk_len).collect())
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.iter.len();
+        let chunks = len / self.chunk_len + (len % self.chunk_len != 0) as usize;
+        (chunks, Some(chunks))
+    }
+}
+
+impl<T> ExactSizeIterator for MSliceIntoChunks<T> {}
+
+impl<T> FusedIterator for MSliceIntoChunks<T> {}
+
+impl<T: Debug> Debug for MSliceIntoChunks<T> {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        formatter
+            .debug_struct("MSliceIntoChunks")
+            .field("remaining", &self.iter.as_slice())
+            .field("chunk_len", &self.chunk_len)
+            .finish()
+    }
+}
+
 //}}}
 
 //{{{ Slice ---------------------------------------------------------------------------------------
@@ -643,10 +1074,27 @@ impl<T> MBox<[T]> {
     ///
     /// The `malloc`ed size of the pointer must be at least `len * size_of::<T>()`. The content
     /// must already been initialized.
+    ///
+    /// With the `freertos` feature, or on targets other than Unix and Windows, if `T` is aligned to
+    /// more than `malloc()` guarantees (8 bytes with FreeRTOS, the pointer size elsewhere), the
+    /// block carries a hidden header. The `ptr` must then be obtained from this crate, e.g. by
+    /// `into_raw()`, rather than from C's `malloc()`, even if it is properly aligned.
     pub unsafe fn from_raw_parts(ptr: *mut T, len: usize) -> Self {
         Self::from_raw(slice_from_raw_parts_mut(ptr, len))
     }
 
+    /// Constructs an empty boxed slice without allocating. Being a `const fn`, this can be used to
+    /// initialize constants and statics.
+    ///
+    /// The box holds a dangling pointer, which is never passed to `free()`. A real block is only
+    /// allocated when the slice is grown. The pointer obtained from `into_raw()` therefore must not
+    /// be released by `free()` on the C side.
+    pub const fn new_empty() -> Self {
+        // SAFETY: the dangling pointer is not null, and is recognized by `gen_free()` and
+        // `gen_realloc()`.
+        unsafe { Self(Unique::new(NonNull::new_unchecked(EmptySlice::<T>::PTR))) }
+    }
+
     /// Constructs a new boxed slice with uninitialized contents.
     pub fn new_uninit_slice(len: usize) -> MBox<[MaybeUninit<T>]> {
         let mut builder = MSliceBuilder::with_capacity(len);
@@ -654,100 +1102,877 @@ impl<T> MBox<[T]> {
         builder.into_mboxed_slice()
     }
 
-    /// Decomposes the boxed slice into a pointer to the first element and the slice length.
-    pub fn into_raw_parts(mut self) -> (*mut T, usize) {
-        let (ptr, len) = slice_into_raw_parts_mut(Self::as_mut_ptr(&mut self));
-        forget(self);
-        (ptr, len)
+    /// Constructs a new boxed slice with the contents filled with zeros, using `calloc()`.
+    pub fn new_zeroed_slice(len: usize) -> MBox<[MaybeUninit<T>]> {
+        let ptr = gen_calloc(len);
+        // SAFETY: `ptr` is allocated to hold `len` items, which are allowed to be uninitialized.
+        unsafe { MBox::from_raw_parts(ptr.as_ptr(), len) }
     }
-}
 
-impl<T> MBox<[MaybeUninit<T>]> {
-    /// Converts into an initialized boxed slice.
+    /// Constructs a new boxed slice with uninitialized contents. Returns an `AllocError` instead
+    /// of panicking or aborting if the memory cannot be allocated.
+    pub fn try_new_uninit_slice(len: usize) -> Result<MBox<[MaybeUninit<T>]>, AllocError> {
+        let ptr = try_gen_malloc(len)?;
+        // SAFETY: `ptr` is allocated to hold `len` items, which are allowed to be uninitialized.
+        unsafe { Ok(MBox::from_raw_parts(ptr.as_ptr(), len)) }
+    }
+
+    /// Constructs a new boxed slice with the contents filled with zeros, using `calloc()`. Returns
+    /// an `AllocError` instead of panicking or aborting if the memory cannot be allocated.
+    pub fn try_new_zeroed_slice(len: usize) -> Result<MBox<[MaybeUninit<T>]>, AllocError> {
+        let ptr = try_gen_calloc(len)?;
+        // SAFETY: `ptr` is allocated to hold `len` items, which are allowed to be uninitialized.
+        unsafe { Ok(MBox::from_raw_parts(ptr.as_ptr(), len)) }
+    }
+
+    /// Changes the length without touching the allocation or the items.
     ///
     /// # Safety
     ///
-    /// The caller should guarantee `*self` is indeed initialized.
-    pub unsafe fn assume_init(self) -> MBox<[T]> {
-        MBox::from_raw(Self::into_raw(self) as *mut [T])
+    /// The allocation must be able to hold `len` items, and the first `len` items must be
+    /// initialized.
+    pub(crate) unsafe fn set_len(&mut self, len: usize) {
+        let ptr = Self::as_mut_ptr(self) as *mut T;
+        // the pointer is moved into the new box, so the old box must not be dropped. The new box
+        // is constructed directly since the ownership of the allocation does not change.
+        write(
+            self,
+            MBox(Unique::new(NonNull::new_unchecked(
+                slice_from_raw_parts_mut(ptr, len),
+            ))),
+        );
     }
-}
 
-impl<T> Default for MBox<[T]> {
-    fn default() -> Self {
-        unsafe { Self::from_raw_parts(gen_malloc(0).as_ptr(), 0) }
+    /// Reallocates the slice from holding `old_cap` items to `new_cap` items, keeping the length.
+    ///
+    /// # Safety
+    ///
+    /// The allocation must currently hold `old_cap` items, and `new_cap` must not be less than the
+    /// length.
+    unsafe fn realloc_items(&mut self, old_cap: usize, new_cap: usize) {
+        let len = self.len();
+        let ptr = NonNull::new_unchecked(Self::as_mut_ptr(self) as *mut T);
+        let new_ptr = gen_realloc(ptr, old_cap, new_cap);
+        // `ptr` has been consumed by `gen_realloc()`, so the old box must not be dropped.
+        write(self, Self::from_raw_parts(new_ptr.as_ptr(), len));
+    }
+
+    /// Same as `realloc_items()`, but returns an `AllocError` if the memory cannot be allocated,
+    /// in which case the slice is left untouched.
+    ///
+    /// # Safety
+    ///
+    /// Same as `realloc_items()`.
+    unsafe fn try_realloc_items(
+        &mut self,
+        old_cap: usize,
+        new_cap: usize,
+    ) -> Result<(), AllocError> {
+        let len = self.len();
+        let ptr = NonNull::new_unchecked(Self::as_mut_ptr(self) as *mut T);
+        let new_ptr = try_gen_realloc(ptr, old_cap, new_cap)?;
+        // `ptr` has been consumed by `try_gen_realloc()`, so the old box must not be dropped.
+        write(self, Self::from_raw_parts(new_ptr.as_ptr(), len));
+        Ok(())
+    }
+
+    /// Shortens the slice to `new_len` items, dropping the rest, and then shrinks the allocation
+    /// using `realloc()` to return the memory to the allocator.
+    ///
+    /// Does nothing if `new_len` is not less than the current length.
+    pub fn truncate(&mut self, new_len: usize) {
+        let len = self.len();
+        if new_len >= len {
+            return;
+        }
+        // SAFETY: the length is reduced before dropping the tail, so a panic while dropping leaks
+        // the rest of the tail instead of dropping it twice. The allocation is then shrunk,
+        // keeping the first `new_len` items intact.
+        unsafe {
+            self.set_len(new_len);
+            let ptr = Self::as_mut_ptr(self) as *mut T;
+            drop_in_place(slice_from_raw_parts_mut(ptr.add(new_len), len - new_len));
+            self.realloc_items(len, new_len);
+        }
     }
-}
 
-impl<T: Clone> Clone for MBox<[T]> {
-    fn clone(&self) -> Self {
-        Self::from_slice(self)
+    /// Grows the allocation to hold `new_len` items, and fills the tail with values returned from
+    /// `f`. Does nothing if `new_len` is not greater than the length.
+    fn grow_with<F: FnMut() -> T>(&mut self, new_len: usize, mut f: F) {
+        let len = self.len();
+        if new_len <= len {
+            return;
+        }
+        // SAFETY: the allocation now holds `new_len` items. The length is increased only after
+        // each new item is written, so a panic in `f` never exposes the uninitialized tail.
+        unsafe {
+            self.realloc_items(len, new_len);
+            for i in len..new_len {
+                let ptr = Self::as_mut_ptr(self) as *mut T;
+                write(ptr.add(i), f());
+                self.set_len(i + 1);
+            }
+        }
     }
-}
 
-impl<T: Clone> MBox<[T]> {
-    /// Creates a new `malloc`-boxed slice by cloning the content of an existing slice.
-    pub fn from_slice(slice: &[T]) -> MBox<[T]> {
-        let mut builder = MSliceBuilder::with_capacity(slice.len());
-        for item in slice {
-            builder.push(item.clone());
+    /// Retains only the items for which `f` returns `true`, preserving their order.
+    ///
+    /// The kept items are compacted to the front in place, the rest are dropped, and then the
+    /// allocation is shrunk using `realloc()`.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        let len = self.len();
+        let mut deleted = 0;
+        for i in 0..len {
+            if !f(&self[i]) {
+                deleted += 1;
+            } else if deleted > 0 {
+                self.swap(i - deleted, i);
+            }
         }
-        builder.into_mboxed_slice()
+        self.truncate(len - deleted);
     }
-}
 
-impl<T> FromIterator<T> for MBox<[T]> {
-    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
-        let iter = iter.into_iter();
-        let (lower_size, upper_size) = iter.size_hint();
-        let initial_capacity = upper_size.unwrap_or(lower_size).max(1);
-        let mut builder = MSliceBuilder::with_capacity(initial_capacity);
-        for item in iter {
-            builder.push(item);
+    /// Removes consecutive items for which `same_bucket(item, previous_kept_item)` returns `true`,
+    /// keeping only the first of each run.
+    ///
+    /// The kept items are compacted to the front in place, the rest are dropped, and then the
+    /// allocation is shrunk using `realloc()`.
+    pub fn dedup_by<F: FnMut(&mut T, &mut T) -> bool>(&mut self, mut same_bucket: F) {
+        let len = self.len();
+        if len <= 1 {
+            return;
         }
-        builder.into_mboxed_slice()
+        let mut kept = 1;
+        for i in 1..len {
+            let (front, back) = self.split_at_mut(i);
+            if !same_bucket(&mut back[0], &mut front[kept - 1]) {
+                self.swap(kept, i);
+                kept += 1;
+            }
+        }
+        self.truncate(kept);
     }
-}
 
-impl<T> IntoIterator for MBox<[T]> {
-    type Item = T;
-    type IntoIter = MSliceIntoIter<T>;
-    fn into_iter(self) -> MSliceIntoIter<T> {
-        let (ptr, len) = self.into_raw_parts();
-        MSliceIntoIter {
-            ptr: unsafe { NonNull::new_unchecked(ptr) },
-            begin: 0,
-            end: len,
+    /// Removes consecutive items which resolve to the same key, keeping only the first of each run.
+    pub fn dedup_by_key<K: PartialEq, F: FnMut(&mut T) -> K>(&mut self, mut key: F) {
+        self.dedup_by(|a, b| key(a) == key(b));
+    }
+
+    /// Resizes the slice in place using `realloc()`, so that the length becomes `new_len`.
+    ///
+    /// When shrinking, the tail items are dropped. When growing, the new tail is filled with values
+    /// returned from calling `f` repeatedly.
+    pub fn resize_with<F: FnMut() -> T>(&mut self, new_len: usize, f: F) {
+        if new_len < self.len() {
+            self.truncate(new_len);
+        } else {
+            self.grow_with(new_len, f);
         }
     }
-}
 
-impl<'a, T> IntoIterator for &'a MBox<[T]> {
-    type Item = &'a T;
-    type IntoIter = Iter<'a, T>;
-    fn into_iter(self) -> Iter<'a, T> {
-        self.iter()
+    /// Decomposes the boxed slice into a pointer to the first element and the slice length.
+    #[cfg_attr(feature = "track_call_sites", track_caller)]
+    pub fn into_raw_parts(self) -> (*mut T, usize) {
+        slice_into_raw_parts_mut(Self::into_raw(self))
     }
-}
 
-impl<'a, T> IntoIterator for &'a mut MBox<[T]> {
-    type Item = &'a mut T;
-    type IntoIter = IterMut<'a, T>;
-    fn into_iter(self) -> IterMut<'a, T> {
-        self.iter_mut()
+    /// Splits the boxed slice into owned chunks of `chunk_len` items, each moved into its own
+    /// allocation. The last chunk may be shorter.
+    ///
+    /// The original allocation is freed when the iterator is dropped, together with the items
+    /// which are not yet yielded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_len` is 0.
+    pub fn into_chunks(self, chunk_len: usize) -> MSliceIntoChunks<T> {
+        assert!(chunk_len != 0, "chunk_len must not be 0");
+        MSliceIntoChunks {
+            iter: self.into_iter(),
+            chunk_len,
+        }
     }
-}
 
-#[cfg(not(windows))]
-#[test]
-fn test_slice() {
-    unsafe {
-        let slice_content = gen_malloc::<u64>(5).as_ptr();
-        *slice_content.offset(0) = 16458340076686561191;
-        *slice_content.offset(1) = 15635007859502065083;
-        *slice_content.offset(2) = 4845947824042606450;
-        *slice_content.offset(3) = 8907026173756975745;
-        *slice_content.offset(4) = 7378932587879886134;
+    /// Splits the boxed slice into two at `mid`, each owning an independent allocation.
+    ///
+    /// The items `[mid, len)` are moved into a newly `malloc`-ed block, and the original block is
+    /// shrunk to the items `[0, mid)` using `realloc()`. No items are cloned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mid > len`.
+    pub fn split_at_owned(mut self, mid: usize) -> (MBox<[T]>, MBox<[T]>) {
+        let len = self.len();
+        assert!(mid <= len, "mid > len");
+        let back_len = len - mid;
+        let back = gen_malloc::<T>(back_len).as_ptr();
+        // SAFETY: the tail is moved into `back`, and then the front no longer claims ownership of
+        // the tail before shrinking the allocation.
+        unsafe {
+            let ptr = Self::as_mut_ptr(&mut self) as *mut T;
+            copy_nonoverlapping(ptr.add(mid), back, back_len);
+            self.set_len(mid);
+            self.realloc_items(len, mid);
+            (self, MBox::from_raw_parts(back, back_len))
+        }
+    }
+
+    /// Converts every item with `f` in place, reusing the allocation for the result. Stops at the
+    /// first error, which is returned with the index of the failing item. The items already
+    /// converted and those not yet visited are then dropped, and the allocation is freed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `U` does not have the same size and alignment as `T`. The block must be freed
+    /// with the alignment it was allocated for, which matters where over-aligned blocks carry a
+    /// hidden header.
+    pub fn try_map_in_place<U, E, F>(self, mut f: F) -> Result<MBox<[U]>, (E, usize)>
+    where
+        F: FnMut(T) -> Result<U, E>,
+    {
+        assert!(
+            size_of::<U>() == size_of::<T>() && align_of::<U>() == align_of::<T>(),
+            "U must have the same layout as T"
+        );
+        let (ptr, len) = self.into_raw_parts();
+        let mut guard = MapInPlaceGuard::<T, U> {
+            ptr,
+            len,
+            index: 0,
+            marker: PhantomData,
+        };
+        while guard.index < len {
+            // SAFETY: the item at `index` is still a `T`. It is consumed here, and replaced by a `U`
+            // of the same layout on success. On failure or panic, the guard skips this slot.
+            unsafe {
+                let slot = ptr.add(guard.index);
+                match f(read(slot)) {
+                    Ok(mapped) => write(slot as *mut U, mapped),
+                    Err(e) => {
+                        let index = guard.index;
+                        drop(guard);
+                        return Err((e, index));
+                    }
+                }
+            }
+            guard.index += 1;
+        }
+        forget(guard);
+        // SAFETY: all items have been converted into `U` within the same allocation.
+        unsafe { Ok(MBox::from_raw_parts(ptr as *mut U, len)) }
+    }
+}
+
+impl<A, B> MBox<[(A, B)]> {
+    /// Splits a slice of pairs into a slice of the first items and a slice of the second items,
+    /// moving every item. Each output is allocated once.
+    pub fn unzip(self) -> (MBox<[A]>, MBox<[B]>) {
+        let len = self.len();
+        let mut first = MSliceBuilder::with_capacity(len);
+        let mut second = MSliceBuilder::with_capacity(len);
+        for (a, b) in self {
+            // SAFETY: both builders have the capacity for all `len` items.
+            unsafe {
+                first.push_unchecked(a);
+                second.push_unchecked(b);
+            }
+        }
+        (first.into_mboxed_slice(), second.into_mboxed_slice())
+    }
+
+    /// Pairs up the items of two slices of the same length, moving every item into a single
+    /// allocation. This is the reverse of `unzip()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the slices have different lengths.
+    pub fn zip(first: MBox<[A]>, second: MBox<[B]>) -> Self {
+        let len = first.len();
+        assert_eq!(len, second.len(), "lengths do not match");
+        let mut builder = MSliceBuilder::with_capacity(len);
+        for pair in first.into_iter().zip(second) {
+            // SAFETY: the builder has the capacity for all `len` pairs.
+            unsafe { builder.push_unchecked(pair) };
+        }
+        builder.into_mboxed_slice()
+    }
+}
+
+/// Owns the buffer during `try_map_in_place()`. The items before `index` are already `U`, the item
+/// at `index` has been moved out, and the items after it are still `T`.
+struct MapInPlaceGuard<T, U> {
+    ptr: *mut T,
+    len: usize,
+    index: usize,
+    marker: PhantomData<(T, U)>,
+}
+
+impl<T, U> Drop for MapInPlaceGuard<T, U> {
+    fn drop(&mut self) {
+        // SAFETY: see the invariants of the guard. The buffer is allocated by `malloc()`.
+        unsafe {
+            drop_in_place(slice_from_raw_parts_mut(self.ptr as *mut U, self.index));
+            if self.index < self.len {
+                drop_in_place(slice_from_raw_parts_mut(
+                    self.ptr.add(self.index + 1),
+                    self.len - self.index - 1,
+                ));
+            }
+            gen_free(NonNull::new_unchecked(self.ptr));
+        }
+    }
+}
+
+impl<T> MBox<[MaybeUninit<T>]> {
+    /// Converts into an initialized boxed slice.
+    ///
+    /// # Safety
+    ///
+    /// The caller should guarantee `*self` is indeed initialized.
+    pub unsafe fn assume_init(self) -> MBox<[T]> {
+        MBox::from_raw(Self::into_raw(self) as *mut [T])
+    }
+}
+
+impl<T: PartialEq<U>, U> PartialEq<[U]> for MBox<[T]> {
+    fn eq(&self, other: &[U]) -> bool {
+        self[..] == other[..]
+    }
+}
+
+impl<'a, T: PartialEq<U>, U> PartialEq<&'a [U]> for MBox<[T]> {
+    fn eq(&self, other: &&'a [U]) -> bool {
+        self[..] == other[..]
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: PartialEq<U>, U> PartialEq<Vec<U>> for MBox<[T]> {
+    fn eq(&self, other: &Vec<U>) -> bool {
+        self[..] == other[..]
+    }
+}
+
+#[cfg(mbox_reverse_eq)]
+impl<T: PartialEq<U>, U> PartialEq<MBox<[U]>> for [T] {
+    fn eq(&self, other: &MBox<[U]>) -> bool {
+        self[..] == other[..]
+    }
+}
+
+#[cfg(mbox_reverse_eq)]
+impl<T: PartialEq<U>, U> PartialEq<MBox<[U]>> for &[T] {
+    fn eq(&self, other: &MBox<[U]>) -> bool {
+        self[..] == other[..]
+    }
+}
+
+#[cfg(all(feature = "std", mbox_reverse_eq))]
+impl<T: PartialEq<U>, U> PartialEq<MBox<[U]>> for Vec<T> {
+    fn eq(&self, other: &MBox<[U]>) -> bool {
+        self[..] == other[..]
+    }
+}
+
+impl<T> Default for MBox<[T]> {
+    /// Creates an empty boxed slice without allocating, same as `new_empty()`.
+    ///
+    /// The box holds a dangling pointer, so the pointer obtained from `into_raw()` must not be
+    /// released by `free()` on the C side. Use `MBox::from_slice(&[])` if C code expects a real
+    /// block.
+    fn default() -> Self {
+        Self::new_empty()
+    }
+}
+
+impl MBox<[u8]> {
+    /// Compares the bytes with `other` in constant time, i.e. the time taken depends only on the
+    /// lengths and not on the content. Use this to compare secrets like passwords or MACs.
+    pub fn ct_eq(&self, other: &[u8]) -> bool {
+        ct_eq_bytes(self, other)
+    }
+
+    /// Returns an adapter which formats the bytes as a `hexdump -C`-style table of offsets, hex
+    /// values and ASCII characters, through both `Debug` and `Display`.
+    ///
+    /// ```rust
+    /// use mbox::MBox;
+    ///
+    /// let bytes = MBox::from_slice(b"Hello, world!\n");
+    /// assert_eq!(
+    ///     bytes.hex_dump().to_string(),
+    ///     "00000000  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a        |Hello, world!.|",
+    /// );
+    /// ```
+    pub fn hex_dump(&self) -> HexDump<'_> {
+        HexDump(self)
+    }
+}
+
+/// The adapter returned from `MBox<[u8]>::hex_dump()`.
+#[derive(Clone, Copy)]
+pub struct HexDump<'a>(&'a [u8]);
+
+impl Display for HexDump<'_> {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        for (i, line) in self.0.chunks(16).enumerate() {
+            if i != 0 {
+                formatter.write_str("\n")?;
+            }
+            write!(formatter, "{:08x} ", i * 16)?;
+            for j in 0..16 {
+                if j == 8 {
+                    formatter.write_str(" ")?;
+                }
+                match line.get(j) {
+                    Some(byte) => write!(formatter, " {:02x}", byte)?,
+                    None => formatter.write_str("   ")?,
+                }
+            }
+            formatter.write_str("  |")?;
+            for &byte in line {
+                let c = if byte == b' ' || byte.is_ascii_graphic() {
+                    byte as char
+                } else {
+                    '.'
+                };
+                formatter.write_char(c)?;
+            }
+            formatter.write_str("|")?;
+        }
+        Ok(())
+    }
+}
+
+impl Debug for HexDump<'_> {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        Display::fmt(self, formatter)
+    }
+}
+
+impl<T: PartialEq> MBox<[T]> {
+    /// Removes consecutive repeated items, keeping only the first of each run.
+    pub fn dedup(&mut self) {
+        self.dedup_by(|a, b| a == b);
+    }
+}
+
+impl<T: Clone> Clone for MBox<[T]> {
+    fn clone(&self) -> Self {
+        Self::from_slice(self)
+    }
+
+    /// Clones the items into the existing allocation, resizing it with `realloc()` only when the
+    /// lengths differ.
+    fn clone_from(&mut self, source: &Self) {
+        let len = self.len();
+        let new_len = source.len();
+        if new_len < len {
+            self.truncate(new_len);
+            self.clone_from_slice(source);
+        } else {
+            self.clone_from_slice(&source[..len]);
+            let mut tail = source[len..].iter();
+            self.grow_with(new_len, || tail.next().unwrap().clone());
+        }
+    }
+}
+
+/// Clones a slice into a boxed slice. This trait exists to specialize on primitive `Copy` types.
+trait SpecCloneSlice<T> {
+    fn clone_into_mbox(&self) -> MBox<[T]>;
+}
+
+/// Clones the items one by one.
+fn clone_into_mbox_generic<T: Clone>(slice: &[T]) -> MBox<[T]> {
+    let mut builder = MSliceBuilder::with_capacity(slice.len());
+    for item in slice {
+        builder.push(item.clone());
+    }
+    builder.into_mboxed_slice()
+}
+
+/// Copies all items with a single `memcpy`.
+fn copy_into_mbox<T: Copy>(slice: &[T]) -> MBox<[T]> {
+    let len = slice.len();
+    let ptr = gen_malloc::<T>(len).as_ptr();
+    // SAFETY: `ptr` is allocated to hold `len` items, which are all initialized by the copy.
+    unsafe {
+        copy_nonoverlapping(slice.as_ptr(), ptr, len);
+        MBox::from_raw_parts(ptr, len)
+    }
+}
+
+impl<T: Clone> SpecCloneSlice<T> for [T] {
+    #[cfg(feature = "nightly")]
+    default fn clone_into_mbox(&self) -> MBox<[T]> {
+        clone_into_mbox_generic(self)
+    }
+
+    #[cfg(not(feature = "nightly"))]
+    fn clone_into_mbox(&self) -> MBox<[T]> {
+        clone_into_mbox_generic(self)
+    }
+}
+
+// `min_specialization` cannot specialize on `Copy` itself, so only the primitive types are
+// specialized. Use `clone_copy()` for other `Copy` types.
+#[cfg(feature = "nightly")]
+macro_rules! impl_copy_for_spec_clone_slice {
+    ($($ty:ty)*) => {
+        $(
+            impl SpecCloneSlice<$ty> for [$ty] {
+                fn clone_into_mbox(&self) -> MBox<[$ty]> {
+                    copy_into_mbox(self)
+                }
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "nightly")]
+impl_copy_for_spec_clone_slice!(
+    u8 i8 u16 i16 u32 i32 u64 i64 u128 i128 usize isize f32 f64 bool char
+);
+
+impl<T: Clone> MBox<[T]> {
+    /// Creates a new `malloc`-boxed slice by cloning the content of an existing slice.
+    ///
+    /// With the `nightly` feature, slices of primitive types are copied with a single `memcpy`.
+    pub fn from_slice(slice: &[T]) -> MBox<[T]> {
+        slice.clone_into_mbox()
+    }
+
+    /// Creates a new `malloc`-boxed slice by cloning the content of an existing slice. Returns an
+    /// `AllocError` instead of panicking or aborting if the memory cannot be allocated.
+    pub fn try_from_slice(slice: &[T]) -> Result<MBox<[T]>, AllocError> {
+        let mut builder = MSliceBuilder::try_with_capacity(slice.len())?;
+        for item in slice {
+            // SAFETY: the builder has the capacity for all items of the slice.
+            unsafe { builder.push_unchecked(item.clone()) };
+        }
+        Ok(builder.into_mboxed_slice())
+    }
+
+    /// Resizes the slice in place using `realloc()`, so that the length becomes `new_len`.
+    ///
+    /// When shrinking, the tail items are dropped. When growing, the new tail is filled with clones
+    /// of `value`. Filling with a zero byte (`u8` or `i8`) is done by `write_bytes()`, or by
+    /// `calloc()` if the slice is empty. With the `nightly` feature, this also applies to a zero of
+    /// any other primitive type.
+    pub fn resize(&mut self, new_len: usize, value: T) {
+        if new_len <= self.len() || !value.is_zero() {
+            self.resize_with(new_len, || value.clone());
+        } else if self.is_empty() {
+            // SAFETY: `is_zero()` only accepts types for which all-zero bytes are a valid value.
+            *self = unsafe { MBox::new_zeroed_slice(new_len).assume_init() };
+        } else {
+            let len = self.len();
+            // SAFETY: the allocation now holds `new_len` items, and the new tail is initialized by
+            // zeroing, which is a valid value as above.
+            unsafe {
+                self.realloc_items(len, new_len);
+                let ptr = Self::as_mut_ptr(self) as *mut T;
+                write_bytes(ptr.add(len), 0, new_len - len);
+                self.set_len(new_len);
+            }
+        }
+    }
+
+    /// Creates a new `malloc`-boxed slice of length `n` filled with clones of `elem`.
+    ///
+    /// Filling with a zero byte (`u8` or `i8`) is done by `calloc()`. Only with the `nightly`
+    /// feature, this also applies to a zero of any other primitive type.
+    pub fn from_elem(elem: T, n: usize) -> MBox<[T]> {
+        if elem.is_zero() {
+            // SAFETY: `is_zero()` only accepts types for which all-zero bytes are a valid value.
+            return unsafe { MBox::new_zeroed_slice(n).assume_init() };
+        }
+        let mut builder = MSliceBuilder::with_capacity(n);
+        if n > 0 {
+            for _ in 1..n {
+                builder.push(elem.clone());
+            }
+            builder.push(elem);
+        }
+        builder.into_mboxed_slice()
+    }
+}
+
+/// Detects items whose bytes are all zero, which can be allocated by `calloc()` instead of being
+/// cloned. This trait exists to specialize on zero primitives.
+///
+/// Implementations must only return `true` for types whose `Clone` is a bitwise copy, without
+/// `Drop`, and for which all-zero bytes are a valid value.
+trait IsZero {
+    fn is_zero(&self) -> bool;
+}
+
+impl<T> IsZero for T {
+    #[cfg(feature = "nightly")]
+    default fn is_zero(&self) -> bool {
+        is_zero_byte(self)
+    }
+
+    #[cfg(not(feature = "nightly"))]
+    fn is_zero(&self) -> bool {
+        is_zero_byte(self)
+    }
+}
+
+/// Returns whether `elem` is a zero `u8` or `i8`. This is detected without specialization, by
+/// comparing the `TypeId` of `T`, which is obtained even when `T` is not `'static`. Lifetimes are
+/// erased from such an ID, but `u8` and `i8` have none, so an equal ID means `T` is one of them.
+fn is_zero_byte<T>(elem: &T) -> bool {
+    trait NonStaticAny {
+        fn get_type_id(&self) -> TypeId
+        where
+            Self: 'static;
+    }
+
+    impl<T> NonStaticAny for PhantomData<T> {
+        fn get_type_id(&self) -> TypeId
+        where
+            Self: 'static,
+        {
+            TypeId::of::<T>()
+        }
+    }
+
+    let phantom = PhantomData::<T>;
+    // SAFETY: only the `TypeId` is read through the extended lifetime, not any value of `T`.
+    let phantom =
+        unsafe { transmute::<&dyn NonStaticAny, &(dyn NonStaticAny + 'static)>(&phantom) };
+    let id = phantom.get_type_id();
+    if id != TypeId::of::<u8>() && id != TypeId::of::<i8>() {
+        return false;
+    }
+    // SAFETY: `T` is `u8` or `i8`, which have the same layout.
+    unsafe { *(elem as *const T as *const u8) == 0 }
+}
+
+#[cfg(feature = "nightly")]
+macro_rules! impl_is_zero {
+    ($($ty:ty: |$elem:ident| $is_zero:expr;)*) => {
+        $(
+            impl IsZero for $ty {
+                fn is_zero(&self) -> bool {
+                    let $elem = *self;
+                    $is_zero
+                }
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "nightly")]
+impl_is_zero! {
+    u8: |elem| elem == 0;
+    i8: |elem| elem == 0;
+    u16: |elem| elem == 0;
+    i16: |elem| elem == 0;
+    u32: |elem| elem == 0;
+    i32: |elem| elem == 0;
+    u64: |elem| elem == 0;
+    i64: |elem| elem == 0;
+    u128: |elem| elem == 0;
+    i128: |elem| elem == 0;
+    usize: |elem| elem == 0;
+    isize: |elem| elem == 0;
+    f32: |elem| elem.to_bits() == 0;
+    f64: |elem| elem.to_bits() == 0;
+    bool: |elem| !elem;
+    char: |elem| elem == '\0';
+}
+
+/// Collects an iterator into a boxed slice. This trait exists to specialize on `TrustedLen`.
+trait SpecFromIter<T>: Iterator<Item = T> + Sized {
+    fn collect_into_mbox(self) -> MBox<[T]>;
+}
+
+/// Collects an iterator of any length.
+fn collect_into_mbox_generic<T, I: Iterator<Item = T>>(iter: I) -> MBox<[T]> {
+    let (lower_size, upper_size) = iter.size_hint();
+    // when the iterator reports an exact size, allocate exactly once. otherwise, start from
+    // the lower bound and grow geometrically by realloc(), then shrink to fit at the end.
+    let initial_capacity = if upper_size == Some(lower_size) {
+        lower_size
+    } else {
+        lower_size.max(1)
+    };
+    let mut builder = MSliceBuilder::with_capacity(initial_capacity);
+    for item in iter {
+        builder.push(item);
+    }
+    builder.into_mboxed_slice()
+}
+
+impl<T, I: Iterator<Item = T>> SpecFromIter<T> for I {
+    #[cfg(feature = "nightly")]
+    default fn collect_into_mbox(self) -> MBox<[T]> {
+        collect_into_mbox_generic(self)
+    }
+
+    #[cfg(not(feature = "nightly"))]
+    fn collect_into_mbox(self) -> MBox<[T]> {
+        collect_into_mbox_generic(self)
+    }
+}
+
+#[cfg(feature = "nightly")]
+impl<T, I: TrustedLen<Item = T>> SpecFromIter<T> for I {
+    fn collect_into_mbox(self) -> MBox<[T]> {
+        let len = match self.size_hint() {
+            (_, Some(upper_size)) => upper_size,
+            _ => panic!("memory overflow"),
+        };
+        let mut builder = MSliceBuilder::with_capacity(len);
+        // SAFETY: `TrustedLen` guarantees the iterator yields exactly `len` items, which is the
+        // capacity of the builder.
+        self.for_each(|item| unsafe { builder.push_unchecked(item) });
+        builder.into_mboxed_slice()
+    }
+}
+
+impl<T: Copy> MBox<[T]> {
+    /// Clones the slice with a single `memcpy`, instead of cloning the items one by one.
+    ///
+    /// With the `nightly` feature, `clone()` already does this for primitive types through
+    /// specialization.
+    pub fn clone_copy(&self) -> MBox<[T]> {
+        copy_into_mbox(self)
+    }
+
+    /// Appends the items of `other`, growing the allocation using `realloc()` and then copying the
+    /// new items with a single `memcpy`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resulting size overflows.
+    pub fn extend_from_slice(&mut self, other: &[T]) {
+        let len = self.len();
+        let new_len = len.checked_add(other.len()).expect("memory overflow");
+        // SAFETY: the allocation holds `new_len` items after reallocation, and the new tail is
+        // initialized by the copy before the length is updated.
+        unsafe {
+            self.realloc_items(len, new_len);
+            let ptr = Self::as_mut_ptr(self) as *mut T;
+            copy_nonoverlapping(other.as_ptr(), ptr.add(len), other.len());
+            self.set_len(new_len);
+        }
+    }
+
+    /// Appends the items of `other` like `extend_from_slice()`. Returns an `AllocError` instead of
+    /// panicking or aborting if the memory cannot be allocated, in which case the slice is left
+    /// unchanged.
+    pub fn try_extend_from_slice(&mut self, other: &[T]) -> Result<(), AllocError> {
+        let len = self.len();
+        let new_len = len
+            .checked_add(other.len())
+            .ok_or(AllocError::CapacityOverflow)?;
+        // SAFETY: same as `extend_from_slice()`. On error, the allocation is left untouched.
+        unsafe {
+            self.try_realloc_items(len, new_len)?;
+            let ptr = Self::as_mut_ptr(self) as *mut T;
+            copy_nonoverlapping(other.as_ptr(), ptr.add(len), other.len());
+            self.set_len(new_len);
+        }
+        Ok(())
+    }
+
+    /// Creates a `malloc`-boxed slice by copying this slice `n` times.
+    ///
+    /// The result is allocated in a single block, which is then filled by repeatedly doubling the
+    /// copied region.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resulting size overflows.
+    pub fn repeat(&self, n: usize) -> MBox<[T]> {
+        let len = self.len();
+        let total = len.checked_mul(n).expect("memory overflow");
+        let ptr = gen_malloc::<T>(total).as_ptr();
+        // SAFETY: `ptr` is allocated to hold `total` items, which are all initialized by
+        // `copy_repeated()`.
+        unsafe {
+            copy_repeated(self.as_ptr(), len, ptr, n);
+            MBox::from_raw_parts(ptr, total)
+        }
+    }
+}
+
+impl<T> FromIterator<T> for MBox<[T]> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        iter.into_iter().collect_into_mbox()
+    }
+}
+
+/// Concatenates the slices into a single `malloc`-boxed slice.
+///
+/// The total length is computed first, so the result is allocated exactly once.
+pub fn concat<T: Clone, S: Borrow<[T]>>(slices: &[S]) -> MBox<[T]> {
+    join(slices, &[])
+}
+
+/// Concatenates the slices into a single `malloc`-boxed slice, placing clones of `separator`
+/// between them.
+///
+/// The total length is computed first, so the result is allocated exactly once.
+pub fn join<T: Clone, S: Borrow<[T]>>(slices: &[S], separator: &[T]) -> MBox<[T]> {
+    let total = joined_len(slices.iter().map(|s| s.borrow().len()), separator.len());
+    let mut builder = MSliceBuilder::with_capacity(total);
+    for (i, slice) in slices.iter().enumerate() {
+        if i != 0 {
+            for item in separator {
+                builder.push(item.clone());
+            }
+        }
+        for item in slice.borrow() {
+            builder.push(item.clone());
+        }
+    }
+    builder.into_mboxed_slice()
+}
+
+impl<T> IntoIterator for MBox<[T]> {
+    type Item = T;
+    type IntoIter = MSliceIntoIter<T>;
+    fn into_iter(self) -> MSliceIntoIter<T> {
+        let (ptr, len) = self.into_raw_parts();
+        MSliceIntoIter {
+            ptr: unsafe { NonNull::new_unchecked(ptr) },
+            begin: 0,
+            end: len,
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a MBox<[T]> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut MBox<[T]> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T>;
+    fn into_iter(self) -> IterMut<'a, T> {
+        self.iter_mut()
+    }
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_slice() {
+    unsafe {
+        let slice_content = gen_malloc::<u64>(5).as_ptr();
+        *slice_content.offset(0) = 16458340076686561191;
+        *slice_content.offset(1) = 15635007859502065083;
+        *slice_content.offset(2) = 4845947824042606450;
+        *slice_content.offset(3) = 8907026173756975745;
+        *slice_content.offset(4) = 7378932587879886134;
         let mbox = MBox::from_raw_parts(slice_content, 5);
         assert_eq!(
             &mbox as &[u64],
@@ -780,83 +2005,524 @@ fn test_slice_with_drops() {
         mbox[2].assert_eq(0);
         assert_eq!(mbox.len(), 3);
     }
-    counter.assert_eq(3);
+    counter.assert_eq(3);
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_split_at_owned() {
+    let counter = DropCounter::default();
+    {
+        let slice = MBox::from_elem(counter.clone(), 5);
+        let (front, back) = slice.split_at_owned(2);
+        counter.assert_eq(0);
+        assert_eq!((front.len(), back.len()), (2, 3));
+        drop(front);
+        counter.assert_eq(2);
+    }
+    counter.assert_eq(5);
+
+    let (front, back) = MBox::from_slice(&[1u64, 2, 3]).split_at_owned(0);
+    assert_eq!((&*front, &*back), (&[][..], &[1, 2, 3][..]));
+    let (front, back) = MBox::from_slice(&[1u64, 2, 3]).split_at_owned(3);
+    assert_eq!((&*front, &*back), (&[1, 2, 3][..], &[][..]));
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_into_chunks() {
+    let mut chunks = MBox::from_slice(&[1u32, 2, 3, 4, 5]).into_chunks(2);
+    assert_eq!(chunks.len(), 3);
+    assert_eq!(chunks.next().unwrap(), &[1, 2][..]);
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks.next().unwrap(), &[3, 4][..]);
+    assert_eq!(chunks.next().unwrap(), &[5][..]);
+    assert!(chunks.next().is_none());
+    assert_eq!(MBox::<[u8]>::default().into_chunks(3).count(), 0);
+
+    let counter = DropCounter::default();
+    {
+        let mut chunks = MBox::from_elem(counter.clone(), 7).into_chunks(3);
+        let first = chunks.next().unwrap();
+        counter.assert_eq(0);
+        drop(chunks);
+        counter.assert_eq(4);
+        drop(first);
+        counter.assert_eq(7);
+    }
+}
+
+#[test]
+fn test_concat_and_join() {
+    let fragments = [
+        MBox::from_slice(b"ab"),
+        MBox::default(),
+        MBox::from_slice(b"c"),
+    ];
+    assert_eq!(concat(&fragments), &b"abc"[..]);
+    assert_eq!(join(&fragments, b", "), &b"ab, , c"[..]);
+    assert_eq!(join(&[&b"x"[..], b"y"], b"-"), &b"x-y"[..]);
+    assert_eq!(concat::<u8, MBox<[u8]>>(&[]).len(), 0);
+    assert_eq!(join::<u8, MBox<[u8]>>(&[], b"-").len(), 0);
+}
+
+#[test]
+#[should_panic(expected = "chunk_len must not be 0")]
+fn test_into_chunks_zero() {
+    let _ = MBox::from_slice(b"ab").into_chunks(0);
+}
+
+#[test]
+#[should_panic(expected = "mid > len")]
+fn test_split_at_owned_out_of_bounds() {
+    let _ = MBox::from_slice(b"ab").split_at_owned(3);
+}
+
+#[cfg(feature = "nightly")]
+#[test]
+fn test_coerce_unsized() {
+    let counter = DropCounter::default();
+    {
+        let pre_box = MBox::new([counter.clone(), counter.clone()]);
+        counter.assert_eq(0);
+        pre_box[0].assert_eq(0);
+        pre_box[1].assert_eq(0);
+        assert_eq!(pre_box.len(), 2);
+
+        let post_box: MBox<[DropCounter]> = pre_box;
+        counter.assert_eq(0);
+        post_box[0].assert_eq(0);
+        post_box[1].assert_eq(0);
+        assert_eq!(post_box.len(), 2);
+    }
+    counter.assert_eq(2);
+}
+
+#[cfg(not(windows))]
+#[test]
+#[allow(useless_ptr_null_checks)]
+fn test_empty_slice() {
+    let mbox = MBox::<[DropCounter]>::default();
+    let sl: &[DropCounter] = &mbox;
+    assert_eq!(sl.len(), 0);
+    assert!(!sl.as_ptr().is_null());
+}
+
+#[test]
+fn test_new_empty() {
+    static EMPTY: MBox<[u32]> = MBox::<[u32]>::new_empty();
+    assert!(EMPTY.is_empty());
+
+    let mut mbox = MBox::<[u32]>::new_empty();
+    mbox.truncate(0);
+    assert_eq!(mbox.clone(), MBox::<[u32]>::default());
+    assert_eq!(
+        MBox::into_raw(MBox::<[u32]>::default()) as *mut u32,
+        EmptySlice::<u32>::PTR as *mut u32
+    );
+    mbox.extend_from_slice(&[1, 2, 3]);
+    assert_eq!(&*mbox, &[1, 2, 3]);
+
+    let mut units = MBox::<[()]>::new_empty();
+    units.resize(2, ());
+    assert_eq!(units.len(), 2);
+
+    let mut string = MBox::<str>::new_empty();
+    assert_eq!(&*string, "");
+    string.clone_from(&MBox::from("abc"));
+    assert_eq!(&*string, "abc");
+}
+
+#[cfg(all(feature = "nightly", not(windows)))]
+#[test]
+#[allow(useless_ptr_null_checks)]
+fn test_coerce_from_empty_slice() {
+    let pre_box = MBox::<[DropCounter; 0]>::new([]);
+    assert_eq!(pre_box.len(), 0);
+    assert!(!pre_box.as_ptr().is_null());
+
+    let post_box: MBox<[DropCounter]> = pre_box;
+    let sl: &[DropCounter] = &post_box;
+    assert_eq!(sl.len(), 0);
+    assert!(!sl.as_ptr().is_null());
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_clone_slice() {
+    let counter = DropCounter::default();
+    unsafe {
+        let slice_content = gen_malloc::<DropCounter>(3).as_ptr();
+        {
+            write(slice_content.offset(0), counter.clone());
+            write(slice_content.offset(1), counter.clone());
+            write(slice_content.offset(2), counter.clone());
+        }
+        let mbox = MBox::from_raw_parts(slice_content, 3);
+        assert_eq!(mbox.len(), 3);
+
+        {
+            let cloned_mbox = mbox.clone();
+            counter.assert_eq(0);
+            assert_eq!(cloned_mbox.len(), 3);
+            cloned_mbox[0].assert_eq(0);
+            cloned_mbox[1].assert_eq(0);
+            cloned_mbox[2].assert_eq(0);
+        }
+
+        counter.assert_eq(3);
+        mbox[0].assert_eq(3);
+        mbox[1].assert_eq(3);
+        mbox[2].assert_eq(3);
+    }
+
+    counter.assert_eq(6);
+}
+
+#[test]
+fn test_slice_partial_eq() {
+    let mbox = MBox::from_slice(&[1u8, 2, 3]);
+    let slice: &[u8] = &[1, 2, 3];
+    assert!(mbox == *slice);
+    assert_eq!(mbox, slice);
+    assert_ne!(mbox, &[1u8, 2][..]);
+}
+
+#[cfg(mbox_reverse_eq)]
+#[test]
+fn test_slice_partial_eq_reverse() {
+    let mbox = MBox::from_slice(&[1u8, 2, 3]);
+    let slice: &[u8] = &[1, 2, 3];
+    assert!(*slice == mbox);
+    assert_eq!(slice, mbox);
+    assert_ne!(&[1u8, 2, 3, 4][..], mbox);
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_clone_from_slice() {
+    let counter = DropCounter::default();
+    {
+        let mut target = MBox::from_elem(counter.clone(), 3);
+        let short = MBox::from_elem(counter.clone(), 1);
+        let long = MBox::from_elem(counter.clone(), 5);
+        counter.assert_eq(0);
+
+        let ptr = target.as_ptr();
+        target.clone_from(&MBox::from_elem(counter.clone(), 3));
+        assert_eq!(target.as_ptr(), ptr);
+        counter.assert_eq(6);
+
+        target.clone_from(&short);
+        assert_eq!(target.len(), 1);
+        counter.assert_eq(9);
+
+        target.clone_from(&long);
+        assert_eq!(target.len(), 5);
+        counter.assert_eq(10);
+    }
+    counter.assert_eq(21);
+
+    let mut target = MBox::from_slice(b"abc");
+    target.clone_from(&MBox::default());
+    assert_eq!(&*target, b"");
+    target.clone_from(&MBox::from_slice(b"abcdef"));
+    assert_eq!(&*target, b"abcdef");
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_resize() {
+    let mut slice = MBox::from_slice(&[1u32, 2, 3]);
+    slice.resize(5, 9);
+    assert_eq!(slice, &[1, 2, 3, 9, 9][..]);
+    slice.resize(2, 0);
+    assert_eq!(slice, &[1, 2][..]);
+    let mut next = 10;
+    slice.resize_with(4, || {
+        next += 1;
+        next
+    });
+    assert_eq!(slice, &[1, 2, 11, 12][..]);
+    slice.resize_with(0, || unreachable!());
+    assert!(slice.is_empty());
+
+    let counter = DropCounter::default();
+    {
+        let mut slice = MBox::from_elem(counter.clone(), 2);
+        slice.resize(6, counter.clone());
+        counter.assert_eq(1);
+        slice.resize(1, counter.clone());
+        counter.assert_eq(7);
+    }
+    counter.assert_eq(8);
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_retain() {
+    let mut slice = MBox::from_slice(&[1u32, 2, 3, 4, 5, 6]);
+    slice.retain(|x| x % 2 == 0);
+    assert_eq!(slice, &[2, 4, 6][..]);
+    slice.retain(|_| true);
+    assert_eq!(slice, &[2, 4, 6][..]);
+    slice.retain(|_| false);
+    assert!(slice.is_empty());
+
+    let counter = DropCounter::default();
+    {
+        let mut slice = MBox::from_elem(counter.clone(), 4);
+        let mut keep = false;
+        slice.retain(|_| {
+            keep = !keep;
+            keep
+        });
+        assert_eq!(slice.len(), 2);
+        counter.assert_eq(2);
+    }
+    counter.assert_eq(4);
+}
+
+#[test]
+fn test_hex_dump() {
+    let bytes: MBox<[u8]> = (0..=0x20u8).collect();
+    assert_eq!(
+        mformat!("{:?}", bytes.hex_dump()),
+        "00000000  00 01 02 03 04 05 06 07  08 09 0a 0b 0c 0d 0e 0f  |................|\n\
+         00000010  10 11 12 13 14 15 16 17  18 19 1a 1b 1c 1d 1e 1f  |................|\n\
+         00000020  20                                                | |"
+    );
+    assert_eq!(mformat!("{}", MBox::<[u8]>::default().hex_dump()), "");
+}
+
+#[test]
+fn test_ct_eq() {
+    let mac = MBox::from_slice(b"\x01\x02\x03");
+    assert!(mac.ct_eq(b"\x01\x02\x03"));
+    assert!(!mac.ct_eq(b"\x01\x02\x04"));
+    assert!(!mac.ct_eq(b"\x01\x02"));
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_dedup() {
+    let mut slice = MBox::from_slice(&[1u32, 1, 2, 3, 3, 3, 1, 4, 4]);
+    slice.dedup();
+    assert_eq!(slice, &[1, 2, 3, 1, 4][..]);
+    slice.dedup_by_key(|x| *x / 2);
+    assert_eq!(slice, &[1, 2, 1, 4][..]);
+    slice.dedup_by(|_, _| true);
+    assert_eq!(slice, &[1][..]);
+
+    let mut empty = MBox::<[u32]>::default();
+    empty.dedup();
+    assert!(empty.is_empty());
+
+    let counter = DropCounter::default();
+    {
+        let mut slice = MBox::from_elem(counter.clone(), 5);
+        slice.dedup_by(|_, _| true);
+        assert_eq!(slice.len(), 1);
+        counter.assert_eq(4);
+    }
+    counter.assert_eq(5);
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_truncate() {
+    let counter = DropCounter::default();
+    {
+        let mut slice = MBox::from_elem(counter.clone(), 5);
+        slice.truncate(7);
+        assert_eq!(slice.len(), 5);
+        slice.truncate(2);
+        assert_eq!(slice.len(), 2);
+        counter.assert_eq(3);
+        slice.truncate(0);
+        assert!(slice.is_empty());
+        counter.assert_eq(5);
+    }
+    counter.assert_eq(5);
+}
+
+#[cfg(all(feature = "std", not(windows)))]
+#[test]
+fn test_resize_with_panic() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let counter = DropCounter::default();
+    let mut slice = MBox::from_elem(counter.clone(), 1);
+    let mut count = 0;
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        slice.resize_with(5, || {
+            count += 1;
+            if count == 3 {
+                panic!("panic during resize");
+            }
+            counter.clone()
+        });
+    }));
+    assert!(result.is_err());
+    assert_eq!(slice.len(), 3);
+    drop(slice);
+    counter.assert_eq(3);
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_extend_from_slice() {
+    let mut slice = MBox::from_slice(&[1u64, 2]);
+    slice.extend_from_slice(&[3, 4, 5]);
+    assert_eq!(slice, &[1, 2, 3, 4, 5][..]);
+    slice.extend_from_slice(&[]);
+    assert_eq!(slice.len(), 5);
+
+    let mut slice = MBox::<[u8]>::default();
+    slice.extend_from_slice(b"abc");
+    assert_eq!(&*slice, b"abc");
+}
+
+#[test]
+fn test_clone_copy() {
+    let slice = MBox::from_slice(b"abcdef");
+    assert_eq!(slice.clone_copy(), slice);
+    assert_eq!(MBox::<[u8]>::default().clone_copy().len(), 0);
+}
+
+#[test]
+fn test_clone_copy_only_copies() {
+    /// A `Copy` type whose `clone()` must never be called.
+    #[derive(Copy, Debug, PartialEq)]
+    struct CopyPanicOnClone(u8);
+
+    #[allow(clippy::non_canonical_clone_impl, clippy::expl_impl_clone_on_copy)]
+    impl Clone for CopyPanicOnClone {
+        fn clone(&self) -> Self {
+            panic!("panic on clone");
+        }
+    }
+
+    let slice: MBox<[_]> = [CopyPanicOnClone(1), CopyPanicOnClone(2)]
+        .iter()
+        .copied()
+        .collect();
+    assert_eq!(slice.clone_copy(), slice);
 }
 
-#[cfg(feature = "nightly")]
+#[cfg(feature = "std")]
 #[test]
-fn test_coerce_unsized() {
+fn test_slice_partial_eq_vec() {
+    let mbox = MBox::from_slice(&[1u8, 2, 3]);
+    assert_eq!(mbox, vec![1u8, 2, 3]);
+    assert_ne!(mbox, vec![1u8, 2, 3, 4]);
+}
+
+#[cfg(all(feature = "std", mbox_reverse_eq))]
+#[test]
+fn test_slice_partial_eq_vec_reverse() {
+    let mbox = MBox::from_slice(&[1u8, 2, 3]);
+    assert_eq!(vec![1u8, 2, 3], mbox);
+    assert_ne!(Vec::<u8>::new(), mbox);
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_from_elem() {
     let counter = DropCounter::default();
     {
-        let pre_box = MBox::new([counter.clone(), counter.clone()]);
-        counter.assert_eq(0);
-        pre_box[0].assert_eq(0);
-        pre_box[1].assert_eq(0);
-        assert_eq!(pre_box.len(), 2);
-
-        let post_box: MBox<[DropCounter]> = pre_box;
+        let slice = MBox::from_elem(counter.clone(), 4);
         counter.assert_eq(0);
-        post_box[0].assert_eq(0);
-        post_box[1].assert_eq(0);
-        assert_eq!(post_box.len(), 2);
+        assert_eq!(slice.len(), 4);
+        let empty = MBox::from_elem(counter.clone(), 0);
+        counter.assert_eq(1);
+        assert_eq!(empty.len(), 0);
     }
-    counter.assert_eq(2);
+    counter.assert_eq(5);
 }
 
 #[cfg(not(windows))]
 #[test]
-#[allow(useless_ptr_null_checks)]
-fn test_empty_slice() {
-    let mbox = MBox::<[DropCounter]>::default();
-    let sl: &[DropCounter] = &mbox;
-    assert_eq!(sl.len(), 0);
-    assert!(!sl.as_ptr().is_null());
+fn test_new_zeroed() {
+    let single = unsafe { MBox::<u64>::new_zeroed().assume_init() };
+    assert_eq!(*single, 0);
+    let slice = unsafe { MBox::<[u32]>::new_zeroed_slice(1000).assume_init() };
+    assert!(slice.iter().all(|x| *x == 0));
+    assert_eq!(MBox::from_elem(0u32, 1000), slice);
+    assert_eq!(MBox::from_elem(0.0f64, 3), &[0.0, 0.0, 0.0][..]);
+    assert_eq!(
+        MBox::from_elem(-0.0f64, 3)[2].to_bits(),
+        (-0.0f64).to_bits()
+    );
+    assert_eq!(MBox::from_elem(false, 0).len(), 0);
 }
 
-#[cfg(all(feature = "nightly", not(windows)))]
+// bytes take the `calloc()` path on every channel, other primitives only with `nightly`.
+#[cfg(not(windows))]
 #[test]
-#[allow(useless_ptr_null_checks)]
-fn test_coerce_from_empty_slice() {
-    let pre_box = MBox::<[DropCounter; 0]>::new([]);
-    assert_eq!(pre_box.len(), 0);
-    assert!(!pre_box.as_ptr().is_null());
+fn test_from_elem_zeroed() {
+    assert!(0u8.is_zero() && 0i8.is_zero() && !1u8.is_zero());
+    assert_eq!(0u32.is_zero(), cfg!(feature = "nightly"));
+    assert!(!MBox::from_slice(&[0u8]).is_zero());
 
-    let post_box: MBox<[DropCounter]> = pre_box;
-    let sl: &[DropCounter] = &post_box;
-    assert_eq!(sl.len(), 0);
-    assert!(!sl.as_ptr().is_null());
+    assert!(MBox::from_elem(0u8, 5000).iter().all(|&b| b == 0));
+    assert!(MBox::from_elem(0i8, 5000).iter().all(|&b| b == 0));
+    assert!(MBox::from_elem(0u32, 5000).iter().all(|&x| x == 0));
+    assert!(MBox::from_elem(0u8, 0).is_empty());
+    assert_eq!(&*MBox::from_elem(3u8, 2), &[3, 3]);
 }
 
 #[cfg(not(windows))]
 #[test]
-fn test_clone_slice() {
-    let counter = DropCounter::default();
-    unsafe {
-        let slice_content = gen_malloc::<DropCounter>(3).as_ptr();
-        {
-            write(slice_content.offset(0), counter.clone());
-            write(slice_content.offset(1), counter.clone());
-            write(slice_content.offset(2), counter.clone());
-        }
-        let mbox = MBox::from_raw_parts(slice_content, 3);
-        assert_eq!(mbox.len(), 3);
+fn test_resize_zeroed() {
+    let mut bytes = MBox::from_elem(0xABu8, 5000);
+    bytes.resize(10, 0);
+    bytes.resize(5000, 0);
+    assert!(bytes[..10].iter().all(|&b| b == 0xAB));
+    assert!(bytes[10..].iter().all(|&b| b == 0));
 
-        {
-            let cloned_mbox = mbox.clone();
-            counter.assert_eq(0);
-            assert_eq!(cloned_mbox.len(), 3);
-            cloned_mbox[0].assert_eq(0);
-            cloned_mbox[1].assert_eq(0);
-            cloned_mbox[2].assert_eq(0);
-        }
+    let mut words = MBox::from_elem(7u32, 100);
+    words.resize(200, 0);
+    assert!(words[..100].iter().all(|&x| x == 7));
+    assert!(words[100..].iter().all(|&x| x == 0));
 
-        counter.assert_eq(3);
-        mbox[0].assert_eq(3);
-        mbox[1].assert_eq(3);
-        mbox[2].assert_eq(3);
+    let mut empty = MBox::<[i8]>::new_empty();
+    empty.resize(100, 0);
+    assert!(empty.iter().all(|&b| b == 0));
+    empty.resize(0, 0);
+    assert!(empty.is_empty());
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_macros() {
+    let slice: MBox<[u32]> = mbox![7; 3];
+    assert_eq!(&*slice, &[7, 7, 7]);
+    let slice = mbox![1u32, 2, 3,];
+    assert_eq!(&*slice, &[1, 2, 3]);
+    let slice: MBox<[u32]> = mbox![];
+    assert_eq!(slice.len(), 0);
+
+    let counter = DropCounter::default();
+    {
+        let slice = mbox![counter.clone(), counter.clone()];
+        counter.assert_eq(0);
+        assert_eq!(slice.len(), 2);
     }
+    counter.assert_eq(2);
+}
 
-    counter.assert_eq(6);
+#[cfg(not(windows))]
+#[test]
+fn test_slice_repeat() {
+    let slice = MBox::from_slice(&[1u32, 2, 3]);
+    for n in 0..20 {
+        let repeated = slice.repeat(n);
+        assert_eq!(repeated.len(), 3 * n);
+        assert!(repeated.chunks(3).all(|c| c == [1, 2, 3]));
+    }
+    assert_eq!(MBox::<[u32]>::default().repeat(100).len(), 0);
+    assert_eq!(MBox::from_slice(&[(); 3]).repeat(5).len(), 15);
 }
 
 #[cfg(not(windows))]
@@ -892,6 +2558,296 @@ fn test_from_iterator_with_no_size_hint() {
     assert_eq!(&*slice, b"1234567890");
 }
 
+#[cfg(not(windows))]
+#[test]
+fn test_from_iterator_with_unknown_length() {
+    let counter = DropCounter::default();
+    {
+        let slice = repeat(counter.clone())
+            .take(100)
+            .enumerate()
+            .filter(|(i, _)| i % 3 == 0)
+            .map(|(_, c)| c)
+            .collect::<MBox<[_]>>();
+        counter.assert_eq(67);
+        assert_eq!(slice.len(), 34);
+    }
+    counter.assert_eq(101);
+
+    let slice = (0..1000u32).filter(|i| i % 7 == 0).collect::<MBox<[_]>>();
+    assert_eq!(slice.len(), 143);
+    assert!(slice.iter().zip((0..).step_by(7)).all(|(a, b)| *a == b));
+}
+
+#[cfg(all(feature = "nightly", not(windows)))]
+#[test]
+fn test_from_trusted_len_iterator() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let slice = (0..1000u32).map(|i| i * 2).collect::<MBox<[_]>>();
+    assert_eq!(slice.len(), 1000);
+    assert!(slice.iter().zip((0..).step_by(2)).all(|(a, b)| *a == b));
+
+    let counter = DropCounter::default();
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        (0..10)
+            .map(|i| {
+                if i == 7 {
+                    panic!("panic during iteration");
+                }
+                counter.clone()
+            })
+            .collect::<MBox<[_]>>()
+    }));
+    assert!(result.is_err());
+    counter.assert_eq(7);
+}
+
+#[cfg(all(feature = "std", not(windows)))]
+#[test]
+fn test_from_iterator_panic() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let counter = DropCounter::default();
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        (0..10)
+            .map(|i| {
+                if i == 5 {
+                    panic!("panic during iteration");
+                }
+                counter.clone()
+            })
+            .collect::<MBox<[_]>>()
+    }));
+    assert!(result.is_err());
+    counter.assert_eq(5);
+}
+
+#[test]
+fn test_try_map_in_place() {
+    #[derive(Debug, PartialEq)]
+    #[repr(u8)]
+    enum Color {
+        Red,
+        Green,
+    }
+    fn parse(b: u8) -> Result<Color, u8> {
+        match b {
+            0 => Ok(Color::Red),
+            1 => Ok(Color::Green),
+            _ => Err(b),
+        }
+    }
+
+    let bytes = MBox::from_slice(&[0u8, 1, 0]);
+    let ptr = bytes.as_ptr() as usize;
+    let colors = bytes.try_map_in_place(parse).unwrap();
+    assert_eq!(&*colors, &[Color::Red, Color::Green, Color::Red]);
+    assert_eq!(colors.as_ptr() as usize, ptr);
+
+    let result = MBox::from_slice(&[0u8, 7, 1]).try_map_in_place(parse);
+    assert_eq!(result.unwrap_err(), (7, 1));
+    assert!(MBox::<[u8]>::new_empty().try_map_in_place(parse).is_ok());
+}
+
+#[cfg(all(feature = "std", not(windows)))]
+#[test]
+fn test_try_map_in_place_drop() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    struct Wrapper(#[allow(dead_code)] DropCounter);
+
+    let counter = DropCounter::default();
+    let slice = MBox::from_elem(counter.clone(), 4);
+    let result = slice.try_map_in_place(|c| if c.get() < 1 { Ok(Wrapper(c)) } else { Err(()) });
+    assert!(result.is_ok());
+    drop(result);
+    counter.assert_eq(4);
+
+    let slice = MBox::from_elem(counter.clone(), 4);
+    let mut calls = 0;
+    let result = slice.try_map_in_place(|c| {
+        calls += 1;
+        if calls < 3 {
+            Ok(Wrapper(c))
+        } else {
+            Err(())
+        }
+    });
+    assert_eq!(result.err(), Some(((), 2)));
+    counter.assert_eq(8);
+
+    let slice = MBox::from_elem(counter.clone(), 4);
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        slice.try_map_in_place::<Wrapper, (), _>(|c| {
+            if counter.get() == 8 {
+                drop(c);
+                panic!("panic during map");
+            }
+            Ok(Wrapper(c))
+        })
+    }));
+    assert!(result.is_err());
+    counter.assert_eq(12);
+}
+
+#[test]
+#[should_panic(expected = "U must have the same layout as T")]
+fn test_try_map_in_place_wrong_layout() {
+    let _ = MBox::from_slice(&[1u8]).try_map_in_place(|b| Ok::<u16, ()>(u16::from(b)));
+}
+
+#[cfg(all(test, not(windows)))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(C, align(64))]
+struct OverAligned([u8; 32]);
+
+#[cfg(not(windows))]
+#[test]
+#[should_panic(expected = "U must have the same layout as T")]
+fn test_try_map_in_place_lower_alignment() {
+    let _ = MBox::from_slice(&[OverAligned([1; 32])]).try_map_in_place(|a| Ok::<_, ()>((a.0, a.0)));
+}
+
+// with the `freertos` feature, the result is freed through the hidden header of the block.
+#[cfg(not(windows))]
+#[test]
+fn test_try_map_in_place_over_aligned() {
+    #[derive(Debug, PartialEq)]
+    #[repr(C, align(64))]
+    struct Doubled([u8; 32]);
+
+    let slice = MBox::from_slice(&[OverAligned([1; 32]), OverAligned([2; 32])]);
+    let doubled = slice
+        .try_map_in_place(|mut a| {
+            a.0.iter_mut().for_each(|b| *b *= 2);
+            Ok::<_, ()>(Doubled(a.0))
+        })
+        .unwrap();
+    assert_eq!(&*doubled, &[Doubled([2; 32]), Doubled([4; 32])]);
+    assert_eq!(doubled.as_ptr() as usize % 64, 0);
+}
+
+#[test]
+fn test_zip_unzip() {
+    let pairs = MBox::from_slice(&[(1u8, 'a'), (2, 'b'), (3, 'c')]);
+    let (numbers, letters) = pairs.unzip();
+    assert_eq!(&*numbers, &[1, 2, 3]);
+    assert_eq!(&*letters, &['a', 'b', 'c']);
+    let pairs = MBox::zip(numbers, letters);
+    assert_eq!(&*pairs, &[(1, 'a'), (2, 'b'), (3, 'c')]);
+
+    let (a, b) = MBox::<[(u8, u16)]>::new_empty().unzip();
+    assert!(a.is_empty() && b.is_empty());
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_zip_unzip_drop() {
+    let counter = DropCounter::default();
+    let pairs = MBox::from_elem((counter.clone(), counter.clone()), 2);
+    counter.assert_eq(0);
+    let (first, second) = pairs.unzip();
+    counter.assert_eq(0);
+    drop(first);
+    counter.assert_eq(2);
+    let pairs = MBox::zip(MBox::from_slice(&[1u8, 2]), second);
+    counter.assert_eq(2);
+    drop(pairs);
+    counter.assert_eq(4);
+}
+
+#[test]
+#[should_panic(expected = "lengths do not match")]
+fn test_zip_wrong_lengths() {
+    MBox::zip(MBox::from_slice(&[1u8]), MBox::from_slice(&[1u8, 2]));
+}
+
+#[test]
+fn test_slice_builder() {
+    let mut builder = MSliceBuilder::from_uninit(MBox::<[u16]>::new_uninit_slice(2));
+    assert!(builder.is_empty());
+    assert_eq!(builder.capacity(), 2);
+    builder.push(1);
+    builder.write_slice(&[2, 3, 4]);
+    assert!(builder.capacity() >= 4);
+    builder.as_mut_slice()[0] = 5;
+    assert_eq!(builder.as_slice(), &[5, 2, 3, 4]);
+    assert_eq!(builder.len(), 4);
+    assert_eq!(&*builder.into_mboxed_slice(), &[5, 2, 3, 4]);
+
+    let mut builder = MSliceBuilder::from_uninit(MBox::<[u8]>::new_uninit_slice(0));
+    builder.reserve(3);
+    assert_eq!(builder.capacity(), 3);
+    builder.spare_capacity()[0] = MaybeUninit::new(9);
+    unsafe { builder.commit_spare(1) };
+    assert_eq!(&*builder.into_mboxed_slice(), &[9]);
+}
+
+#[test]
+fn test_slice_builder_shrink() {
+    let mut builder = MSliceBuilder::with_capacity(100);
+    builder.write_slice(b"abc");
+    builder.shrink_to(200);
+    assert_eq!(builder.capacity(), 100);
+    builder.shrink_to(10);
+    assert_eq!(builder.capacity(), 10);
+    builder.shrink_to(1);
+    assert_eq!(builder.capacity(), 3);
+    builder.push(b'd');
+    assert!(builder.capacity() >= 4);
+    builder.shrink_to_fit();
+    assert_eq!(builder.capacity(), 4);
+    assert_eq!(builder.as_slice(), b"abcd");
+}
+
+#[cfg(all(feature = "std", not(windows)))]
+#[test]
+fn test_slice_builder_panic() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let counter = DropCounter::default();
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let mut builder = MSliceBuilder::from_uninit(MBox::new_uninit_slice(4));
+        builder.write_slice(&[counter.clone(), counter.clone()]);
+        counter.assert_eq(2);
+        builder.push(counter.clone());
+        panic!("panic while building");
+    }));
+    assert!(result.is_err());
+    counter.assert_eq(5);
+
+    let result = catch_unwind(|| {
+        let mut builder = MSliceBuilder::with_capacity(1);
+        builder.write_slice(&[PanicOnClone::default()]);
+    });
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_from_iterator_with_wrong_size_hint() {
+    struct LyingSizeHint<I>(I, usize);
+
+    impl<I: Iterator> Iterator for LyingSizeHint<I> {
+        type Item = I::Item;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            self.0.next()
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            (self.1, Some(self.1))
+        }
+    }
+
+    let slice = LyingSizeHint(b"1234567890".iter().copied(), 3).collect::<MBox<[u8]>>();
+    assert_eq!(&*slice, b"1234567890");
+    let slice = LyingSizeHint(b"123".iter().copied(), 10).collect::<MBox<[u8]>>();
+    assert_eq!(&*slice, b"123");
+    let slice = LyingSizeHint(b"".iter().copied(), 0).collect::<MBox<[u8]>>();
+    assert_eq!(&*slice, b"");
+}
+
 #[cfg(not(windows))]
 #[test]
 fn test_into_iterator() {
@@ -921,8 +2877,34 @@ fn test_iter_properties() {
     assert_eq!(iter.collect::<Vec<_>>(), vec![4, 9, 16]);
 }
 
+#[test]
+fn test_iter_as_slice() {
+    let slice = MBox::from_slice(&[1u8, 2, 3, 4, 5]);
+    let mut iter = slice.into_iter();
+    assert_eq!(iter.as_slice(), &[1, 2, 3, 4, 5]);
+    assert_eq!(iter.next(), Some(1));
+    assert_eq!(iter.next_back(), Some(5));
+    assert_eq!(iter.as_slice(), &[2, 3, 4]);
+    iter.as_mut_slice()[1] = 9;
+    assert_eq!(iter.next(), Some(2));
+    assert_eq!(iter.next(), Some(9));
+    assert_eq!(iter.next(), Some(4));
+    assert!(iter.as_slice().is_empty());
+    assert_eq!(iter.next(), None);
+    assert_eq!(iter.next_back(), None);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_iter_debug() {
+    let mut iter = MBox::from_slice(&[1u8, 2, 3]).into_iter();
+    iter.next();
+    assert_eq!(format!("{:?}", iter), "MSliceIntoIter([2, 3])");
+}
+
 #[cfg(not(windows))]
 #[test]
+#[allow(clippy::unnecessary_operation)]
 fn test_iter_drop() {
     let counter = DropCounter::default();
     {
@@ -973,6 +2955,16 @@ fn test_panic_during_clone_from() {
 //{{{ UTF-8 String --------------------------------------------------------------------------------
 
 impl MBox<str> {
+    /// Constructs an empty string without allocating, like `MBox::<[u8]>::new_empty()`.
+    pub const fn new_empty() -> Self {
+        // SAFETY: same as `MBox::<[u8]>::new_empty()`, and an empty string is valid UTF-8.
+        unsafe {
+            Self(Unique::new(NonNull::new_unchecked(
+                EmptySlice::<u8>::PTR as *mut str,
+            )))
+        }
+    }
+
     /// Constructs a new malloc-backed string from the pointer and the length (number of UTF-8 code
     /// units).
     ///
@@ -1015,6 +3007,41 @@ impl MBox<str> {
         from_utf8(&bytes)?;
         unsafe { Ok(Self::from_utf8_unchecked(bytes)) }
     }
+
+    /// Reallocates the string to have `new_len` bytes.
+    ///
+    /// # Safety
+    ///
+    /// When growing, the new tail is uninitialized and must be filled with valid UTF-8 before the
+    /// string is used. When shrinking, `new_len` must lie on a char boundary.
+    pub(crate) unsafe fn realloc_bytes(&mut self, new_len: usize) {
+        let len = self.len();
+        let ptr = NonNull::new_unchecked(Self::as_mut_ptr(self) as *mut u8);
+        let new_ptr = gen_realloc(ptr, len, new_len);
+        // `ptr` has been consumed by `gen_realloc()`, so the old box must not be dropped.
+        write(
+            self,
+            Self::from_raw_utf8_parts_unchecked(new_ptr.as_ptr(), new_len),
+        );
+    }
+
+    /// Same as `realloc_bytes()`, but returns an `AllocError` if the memory cannot be allocated, in
+    /// which case the string is left untouched.
+    ///
+    /// # Safety
+    ///
+    /// Same as `realloc_bytes()`.
+    pub(crate) unsafe fn try_realloc_bytes(&mut self, new_len: usize) -> Result<(), AllocError> {
+        let len = self.len();
+        let ptr = NonNull::new_unchecked(Self::as_mut_ptr(self) as *mut u8);
+        let new_ptr = try_gen_realloc(ptr, len, new_len)?;
+        // `ptr` has been consumed by `try_gen_realloc()`, so the old box must not be dropped.
+        write(
+            self,
+            Self::from_raw_utf8_parts_unchecked(new_ptr.as_ptr(), new_len),
+        );
+        Ok(())
+    }
 }
 
 impl Default for MBox<str> {
@@ -1027,6 +3054,21 @@ impl Clone for MBox<str> {
     fn clone(&self) -> Self {
         Self::from(&**self)
     }
+
+    /// Copies the string into the existing allocation, resizing it with `realloc()` only when the
+    /// lengths differ.
+    fn clone_from(&mut self, source: &Self) {
+        let len = source.len();
+        // SAFETY: the whole buffer is overwritten by the valid UTF-8 `source` right after the
+        // reallocation, before anyone can observe the (possibly invalid) intermediate content.
+        unsafe {
+            if self.len() != len {
+                self.realloc_bytes(len);
+            }
+            let ptr = Self::as_mut_ptr(self) as *mut u8;
+            copy_nonoverlapping(source.as_ptr(), ptr, len);
+        }
+    }
 }
 
 impl From<&str> for MBox<str> {
@@ -1043,6 +3085,80 @@ impl From<&str> for MBox<str> {
     }
 }
 
+/// Implements conversions from the shared pointers `$ptr<[T]>` and `$ptr<str>`, by value and by
+/// reference. The content is always copied, since it may be shared with other owners.
+#[cfg(feature = "std")]
+macro_rules! impl_from_shared {
+    ($($ptr:ident)*) => {$(
+        impl<T: Clone> From<&$ptr<[T]>> for MBox<[T]> {
+            /// Creates a new `malloc`-boxed slice by cloning the items of a shared slice.
+            fn from(slice: &$ptr<[T]>) -> Self {
+                Self::from_slice(slice)
+            }
+        }
+
+        impl<T: Clone> From<$ptr<[T]>> for MBox<[T]> {
+            /// Creates a new `malloc`-boxed slice by cloning the items of a shared slice, and then
+            /// releases the shared reference.
+            fn from(slice: $ptr<[T]>) -> Self {
+                Self::from_slice(&slice)
+            }
+        }
+
+        impl From<&$ptr<str>> for MBox<str> {
+            /// Creates a new `malloc`-boxed string by copying the content of a shared string.
+            fn from(string: &$ptr<str>) -> Self {
+                Self::from(&**string)
+            }
+        }
+
+        impl From<$ptr<str>> for MBox<str> {
+            /// Creates a new `malloc`-boxed string by copying the content of a shared string, and
+            /// then releases the shared reference.
+            fn from(string: $ptr<str>) -> Self {
+                Self::from(&*string)
+            }
+        }
+    )*};
+}
+
+#[cfg(feature = "std")]
+impl_from_shared!(Arc Rc);
+
+#[cfg(feature = "std")]
+impl<T: Clone> From<Cow<'_, [T]>> for MBox<[T]> {
+    /// Creates a new `malloc`-boxed slice from a slice which is either borrowed or owned by a
+    /// `Vec`. Borrowed items are cloned, while owned items are moved.
+    fn from(slice: Cow<'_, [T]>) -> Self {
+        match slice {
+            Cow::Borrowed(slice) => Self::from_slice(slice),
+            Cow::Owned(vec) => vec.into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Cow<'_, str>> for MBox<str> {
+    /// Creates a new `malloc`-boxed string by copying the content of a borrowed or owned string.
+    fn from(string: Cow<'_, str>) -> Self {
+        Self::from(&*string)
+    }
+}
+
+impl TryFrom<MBox<[u8]>> for MBox<str> {
+    type Error = FromUtf8Error;
+
+    /// Converts raw bytes into a string. Unlike `MBox::from_utf8()`, the bytes are given back
+    /// inside the error if the content does not contain valid UTF-8.
+    fn try_from(bytes: MBox<[u8]>) -> Result<Self, FromUtf8Error> {
+        match from_utf8(&bytes) {
+            // SAFETY: the bytes are just checked to be valid UTF-8.
+            Ok(_) => unsafe { Ok(Self::from_utf8_unchecked(bytes)) },
+            Err(error) => Err(FromUtf8Error { bytes, error }),
+        }
+    }
+}
+
 #[test]
 fn test_string_from_bytes() {
     let bytes = MBox::from_slice(b"abcdef\xe4\xb8\x80\xe4\xba\x8c\xe4\xb8\x89");
@@ -1053,6 +3169,28 @@ fn test_string_from_bytes() {
     assert_eq!(&*bytes, b"abcdef\xe4\xb8\x80\xe4\xba\x8c\xe4\xb8\x89");
 }
 
+#[cfg(feature = "std")]
+#[test]
+fn test_from_shared() {
+    let slice: Arc<[u8]> = Arc::from(&b"abc"[..]);
+    assert_eq!(&*MBox::<[u8]>::from(&slice), b"abc");
+    assert_eq!(&*MBox::<[u8]>::from(slice), b"abc");
+    let slice: Rc<[u8]> = Rc::from(&b"de"[..]);
+    assert_eq!(&*MBox::<[u8]>::from(&slice), b"de");
+    assert_eq!(&*MBox::<[u8]>::from(slice), b"de");
+    assert_eq!(&*MBox::<[u8]>::from(Cow::Borrowed(&b"fg"[..])), b"fg");
+    assert_eq!(&*MBox::<[u8]>::from(Cow::Owned(b"hi".to_vec())), b"hi");
+
+    let string: Arc<str> = Arc::from("jk");
+    assert_eq!(&*MBox::<str>::from(&string), "jk");
+    assert_eq!(&*MBox::<str>::from(string), "jk");
+    let string: Rc<str> = Rc::from("lm");
+    assert_eq!(&*MBox::<str>::from(&string), "lm");
+    assert_eq!(&*MBox::<str>::from(string), "lm");
+    assert_eq!(&*MBox::<str>::from(Cow::Borrowed("no")), "no");
+    assert_eq!(&*MBox::<str>::from(Cow::Owned("pq".to_owned())), "pq");
+}
+
 #[test]
 fn test_string_with_internal_nul() {
     let string = MBox::<str>::from("ab\0c");
@@ -1066,11 +3204,77 @@ fn test_non_utf8() {
     assert!(string.is_err());
 }
 
+#[test]
+fn test_try_new() {
+    assert_eq!(*MBox::try_new(5u8).unwrap(), 5);
+    let slice = MBox::<[u8]>::try_new_uninit_slice(3).unwrap();
+    assert_eq!(slice.len(), 3);
+    assert_eq!(
+        MBox::<[u16]>::try_new_uninit_slice(std::usize::MAX).err(),
+        Some(AllocError::CapacityOverflow)
+    );
+
+    MBox::<u64>::try_new_uninit().unwrap();
+    let zeroed = MBox::<u64>::try_new_zeroed().unwrap();
+    assert_eq!(unsafe { zeroed.assume_init() }, MBox::new(0));
+    let zeroed = MBox::<[u32]>::try_new_zeroed_slice(4).unwrap();
+    assert_eq!(&*unsafe { zeroed.assume_init() }, &[0; 4]);
+    assert_eq!(
+        MBox::<[u32]>::try_new_zeroed_slice(std::usize::MAX).err(),
+        Some(AllocError::CapacityOverflow)
+    );
+
+    let mut slice = MBox::try_from_slice(&[1u8, 2, 3]).unwrap();
+    slice.try_extend_from_slice(&[4, 5]).unwrap();
+    assert_eq!(&*slice, &[1, 2, 3, 4, 5]);
+}
+
+#[test]
+fn test_clone_from_str() {
+    let mut string = MBox::<str>::from("abcdef");
+    string.clone_from(&MBox::<str>::from("一二"));
+    assert_eq!(&*string, "一二");
+    string.clone_from(&MBox::<str>::from("x"));
+    assert_eq!(&*string, "x");
+    string.clone_from(&MBox::<str>::default());
+    assert_eq!(&*string, "");
+}
+
 #[test]
 fn test_default_str() {
     assert_eq!(MBox::<str>::default(), MBox::<str>::from(""));
 }
 
+#[cfg(not(windows))]
+#[test]
+fn test_default_with_take() {
+    #[derive(Default)]
+    struct Composite {
+        single: MBox<u32>,
+        slice: MBox<[u16]>,
+        string: MBox<str>,
+    }
+
+    let mut composite = Composite::default();
+    assert_eq!(*composite.single, 0);
+    assert_eq!(composite.slice.len(), 0);
+    assert_eq!(&*composite.string, "");
+
+    *composite.single = 5;
+    composite.slice = MBox::from_slice(&[1, 2]);
+    composite.string = MBox::from("xyz");
+
+    let slice = std::mem::replace(&mut composite.slice, Default::default());
+    let string = std::mem::replace(&mut composite.string, Default::default());
+    let single = std::mem::replace(&mut composite.single, Default::default());
+    assert_eq!(&*slice, &[1, 2]);
+    assert_eq!(&*string, "xyz");
+    assert_eq!(*single, 5);
+    assert_eq!(composite.slice.len(), 0);
+    assert_eq!(&*composite.string, "");
+    assert_eq!(*composite.single, 0);
+}
+
 #[test]
 #[should_panic(expected = "panic on clone")]
 fn test_panic_on_clone_slice() {
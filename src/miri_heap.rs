@@ -0,0 +1,115 @@
+//! Allocation through the Rust global allocator, used when running under Miri.
+//!
+//! Miri tracks blocks from the global allocator together with their exact layout, and reports an
+//! access out of bounds, a mismatched deallocation or a leak with the allocation's backtrace. Since
+//! `free()` is not told the size of the block, the layouts are recorded in a global table. Blocks
+//! which are not in the table, e.g. allocated by C code and adopted through `MBox::from_raw()`, are
+//! forwarded to libc.
+
+use libc::c_void;
+
+use std::alloc::{self, Layout};
+use std::collections::HashMap;
+use std::ptr::null_mut;
+use std::sync::Mutex;
+
+/// The layouts of the live blocks, keyed by their address.
+// a const `Mutex::new()` needs Rust 1.63. Miri always runs on nightly, and the native test build of
+// this module is gated on `mbox_const_mutex`.
+#[allow(clippy::incompatible_msrv)]
+static LAYOUTS: Mutex<Option<HashMap<usize, Layout>>> = Mutex::new(None);
+
+fn with_layouts<R>(f: impl FnOnce(&mut HashMap<usize, Layout>) -> R) -> R {
+    // the table is still consistent even if another thread panicked while holding the lock.
+    let mut layouts = LAYOUTS.lock().unwrap_or_else(|e| e.into_inner());
+    f(layouts.get_or_insert_with(HashMap::new))
+}
+
+/// The alignment of blocks whose type is not known, matching what `malloc()` guarantees.
+pub const MALLOC_ALIGN: usize = 2 * std::mem::size_of::<usize>();
+
+unsafe fn allocate(size: usize, align: usize, zeroed: bool) -> *mut c_void {
+    let layout = match Layout::from_size_align(size.max(1), align) {
+        Ok(layout) => layout,
+        Err(_) => return null_mut(),
+    };
+    let ptr = if zeroed {
+        alloc::alloc_zeroed(layout)
+    } else {
+        alloc::alloc(layout)
+    };
+    if !ptr.is_null() {
+        with_layouts(|layouts| layouts.insert(ptr as usize, layout));
+    }
+    ptr as *mut c_void
+}
+
+/// Allocates `size` bytes aligned to `align`, which must be a power of two. Returns NULL on
+/// failure.
+pub unsafe fn malloc(size: usize, align: usize) -> *mut c_void {
+    allocate(size, align, false)
+}
+
+/// Allocates `size` bytes filled with zeros. Returns NULL on failure.
+pub unsafe fn calloc(size: usize) -> *mut c_void {
+    allocate(size, MALLOC_ALIGN, true)
+}
+
+/// Resizes a block to `size` bytes, keeping its alignment. Returns NULL on failure, in which case
+/// the block is left untouched.
+///
+/// # Safety
+///
+/// The `ptr` must be allocated by this module or by libc.
+pub unsafe fn realloc(ptr: *mut c_void, size: usize) -> *mut c_void {
+    let layout = match with_layouts(|layouts| layouts.get(&(ptr as usize)).copied()) {
+        Some(layout) => layout,
+        None => return libc::realloc(ptr, size),
+    };
+    let new_layout = match Layout::from_size_align(size.max(1), layout.align()) {
+        Ok(new_layout) => new_layout,
+        Err(_) => return null_mut(),
+    };
+    let res = alloc::realloc(ptr as *mut u8, layout, new_layout.size());
+    if !res.is_null() {
+        with_layouts(|layouts| {
+            layouts.remove(&(ptr as usize));
+            layouts.insert(res as usize, new_layout);
+        });
+    }
+    res as *mut c_void
+}
+
+/// Releases a block.
+///
+/// # Safety
+///
+/// The `ptr` must be allocated by this module or by libc, and not used afterwards.
+pub unsafe fn free(ptr: *mut c_void) {
+    match with_layouts(|layouts| layouts.remove(&(ptr as usize))) {
+        Some(layout) => alloc::dealloc(ptr as *mut u8, layout),
+        None => libc::free(ptr),
+    }
+}
+
+#[test]
+fn test_miri_heap() {
+    unsafe {
+        let ptr = malloc(3, 64);
+        assert_eq!(ptr as usize % 64, 0);
+        (ptr as *mut u8).copy_from_nonoverlapping(b"abc".as_ptr(), 3);
+        let ptr = realloc(ptr, 1000);
+        assert_eq!(ptr as usize % 64, 0);
+        assert_eq!(std::slice::from_raw_parts(ptr as *const u8, 3), b"abc");
+        free(ptr);
+
+        let ptr = calloc(16);
+        assert_eq!(std::slice::from_raw_parts(ptr as *const u8, 16), &[0; 16]);
+        free(ptr);
+
+        // blocks from libc are forwarded to libc.
+        let ptr = libc::malloc(4);
+        let ptr = realloc(ptr, 8);
+        free(ptr);
+    }
+}
@@ -0,0 +1,1192 @@
+//! Byte buffers backed by anonymous memory mappings.
+//!
+//! Unlike `MBox`, the memory of an `MMap` is obtained from `mmap()` and released with `munmap()`.
+//! It must never be passed to C's `free()`. In return, huge buffers do not fragment the `malloc()`
+//! heap, and are given back to the operating system as soon as they are dropped.
+//!
+//! Files can be mapped as well, either read-only with `ReadOnlyMMap::map_file()` or copy-on-write
+//! with `MMap::map_file_copy_on_write()`. `SharedMMap` creates a buffer in a shared memory object,
+//! which can be mapped by other processes. It can also map a file writable with
+//! `SharedMMap::map_file()`, and write the changes back to disk with `flush()`.
+
+use libc::{
+    c_int, c_void, MAP_ANONYMOUS, MAP_FAILED, MAP_PRIVATE, PROT_NONE, PROT_READ, PROT_WRITE,
+};
+
+use std::alloc::Layout;
+#[cfg(feature = "std")]
+use std::convert::TryFrom;
+use std::fmt::{Debug, Formatter, Result as FormatResult};
+#[cfg(feature = "std")]
+use std::fs::File;
+#[cfg(feature = "std")]
+use std::io;
+use std::ops::{Deref, DerefMut};
+#[cfg(feature = "std")]
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::ptr::{null_mut, write_bytes, NonNull};
+use std::slice::{from_raw_parts, from_raw_parts_mut};
+
+use crate::error::AllocError;
+use crate::internal::handle_alloc_error;
+
+/// Returns the size of a memory page.
+pub(crate) fn page_size() -> usize {
+    // SAFETY: `sysconf()` has no preconditions.
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+/// Rounds `len` up to a whole number of pages. Empty buffers still occupy one page, since
+/// `mmap()` cannot map 0 bytes.
+fn mapping_len(len: usize) -> Result<usize, AllocError> {
+    round_up_to_pages(len, page_size())
+}
+
+/// Rounds `len` up to a whole number of pages of the given size, which must be a power of two.
+fn round_up_to_pages(len: usize, page_size: usize) -> Result<usize, AllocError> {
+    let len = len.max(1);
+    len.checked_add(page_size - 1)
+        .map(|len| len & !(page_size - 1))
+        .ok_or(AllocError::CapacityOverflow)
+}
+
+/// An owned memory mapping, unmapped on drop.
+///
+/// The buffer starts at `ptr`. The mapping itself starts at `base`, and may be surrounded by
+/// `guard_len` bytes of inaccessible pages on each side.
+struct Mapping {
+    ptr: NonNull<u8>,
+    len: usize,
+    base: NonNull<u8>,
+    map_len: usize,
+    guard_len: usize,
+    #[cfg(feature = "std")]
+    shared: bool,
+    /// Whether the pages can be moved and grown with `mremap()`, i.e. the mapping is anonymous and
+    /// uses normal pages.
+    #[cfg(target_os = "linux")]
+    remappable: bool,
+}
+
+impl Mapping {
+    /// Maps `len` bytes with `mmap()`, returning `None` if it fails. The OS error is left in
+    /// `errno`.
+    ///
+    /// # Safety
+    ///
+    /// `fd`, `prot` and `flags` must describe a mapping which does not alias existing memory.
+    #[allow(clippy::incompatible_msrv)] // `c_int` from libc, see the MSRV note in lib.rs.
+    unsafe fn new(
+        len: usize,
+        map_len: usize,
+        prot: c_int,
+        flags: c_int,
+        fd: c_int,
+    ) -> Option<Self> {
+        let ptr = libc::mmap(null_mut(), map_len, prot, flags, fd, 0);
+        if ptr == MAP_FAILED {
+            return None;
+        }
+        // SAFETY: `mmap()` never returns NULL when we do not ask for a fixed address.
+        let ptr = NonNull::new_unchecked(ptr as *mut u8);
+        Some(Self {
+            ptr,
+            len,
+            base: ptr,
+            map_len,
+            guard_len: 0,
+            #[cfg(feature = "std")]
+            shared: flags & libc::MAP_SHARED != 0,
+            #[cfg(target_os = "linux")]
+            remappable: fd == -1 && flags & libc::MAP_HUGETLB == 0,
+        })
+    }
+
+    /// Returns the start and length of the accessible pages, excluding the guard pages.
+    fn accessible_pages(&self) -> (*mut c_void, usize) {
+        // SAFETY: the guard pages are within the mapping.
+        let start = unsafe { self.base.as_ptr().add(self.guard_len) };
+        (start as *mut c_void, self.map_len - 2 * self.guard_len)
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        // SAFETY: the first `len` bytes of the mapping are readable.
+        unsafe { from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+    #[cfg(feature = "std")]
+    fn advise(&self, advice: Advice) -> io::Result<()> {
+        let (start, len) = self.accessible_pages();
+        let advice = match advice {
+            Advice::Normal => libc::MADV_NORMAL,
+            Advice::Random => libc::MADV_RANDOM,
+            Advice::Sequential => libc::MADV_SEQUENTIAL,
+            Advice::WillNeed => libc::MADV_WILLNEED,
+            Advice::DontNeed if self.shared => libc::MADV_DONTNEED,
+            Advice::DontNeed => {
+                // `madvise(MADV_DONTNEED)` discards the content of private mappings on Linux,
+                // which cannot happen behind a shared reference. The POSIX variant is only a
+                // hint.
+                // SAFETY: the pages are owned by `self`.
+                let res = unsafe { libc::posix_madvise(start, len, libc::POSIX_MADV_DONTNEED) };
+                return if res == 0 {
+                    Ok(())
+                } else {
+                    Err(io::Error::from_raw_os_error(res))
+                };
+            }
+        };
+        // SAFETY: the pages are owned by `self`, and the advice does not change the content.
+        if unsafe { libc::madvise(start, len, advice) } == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+/// The expected access pattern of a mapping, given to `madvise()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Advice {
+    /// No special treatment. This is the default.
+    Normal,
+    /// Pages will be accessed in random order, so reading ahead is not useful.
+    Random,
+    /// Pages will be accessed in sequential order, so they can be read ahead aggressively and
+    /// released soon after being accessed.
+    Sequential,
+    /// Pages will be accessed soon, so they can be read ahead now.
+    WillNeed,
+    /// Pages will not be accessed soon, so the operating system can release them.
+    ///
+    /// For shared mappings, the pages are dropped and later accesses re-read them from the
+    /// underlying file. For private mappings, this is only a hint and the content is always
+    /// preserved.
+    DontNeed,
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: the mapping is owned by `self`.
+        unsafe {
+            libc::munmap(self.base.as_ptr() as *mut c_void, self.map_len);
+        }
+    }
+}
+
+impl Debug for Mapping {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        formatter
+            .debug_struct("MMap")
+            .field("ptr", &self.ptr)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+/// Returns the length of `file` as `usize`.
+#[cfg(feature = "std")]
+fn file_len(file: &File) -> io::Result<usize> {
+    usize::try_from(file.metadata()?.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "file too large to map"))
+}
+
+/// The number of NUMA nodes supported by `MMap::bind_to_numa_node()`.
+#[cfg(all(target_os = "linux", feature = "std"))]
+const MAX_NUMA_NODES: usize = 1024;
+
+/// Returns the NUMA node of the CPU the current thread is running on.
+///
+/// The thread may be migrated to another node at any time, unless its CPU affinity is restricted.
+#[cfg(all(target_os = "linux", feature = "std"))]
+#[allow(clippy::incompatible_msrv)] // `c_uint` for `getcpu()`.
+pub fn current_numa_node() -> io::Result<usize> {
+    let mut node: libc::c_uint = 0;
+    // SAFETY: `getcpu()` only writes to `node`, the CPU number and cache are not requested.
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_getcpu,
+            null_mut::<libc::c_uint>(),
+            &mut node as *mut libc::c_uint,
+            null_mut::<c_void>(),
+        )
+    };
+    if res != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(node as usize)
+}
+
+/// The kind of huge pages requested by `MMap::try_new_huge_pages()`.
+#[cfg(target_os = "linux")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HugePages {
+    /// Transparent huge pages, requested with `madvise(MADV_HUGEPAGE)`.
+    ///
+    /// The kernel backs aligned 2 MiB regions of the buffer with huge pages when it can, and falls
+    /// back to normal pages otherwise. This has no effect if transparent huge pages are disabled.
+    Transparent,
+    /// Explicit 2 MiB pages, mapped with `MAP_HUGETLB`. The pages must be reserved beforehand
+    /// through `/proc/sys/vm/nr_hugepages`.
+    Explicit2MiB,
+    /// Explicit 1 GiB pages, mapped with `MAP_HUGETLB`. The pages must be reserved beforehand,
+    /// usually on the kernel command line.
+    Explicit1GiB,
+}
+
+/// A zero-initialized byte buffer backed by a private memory mapping.
+///
+/// The mapping is page-aligned, and its length is rounded up to a whole number of pages. Only the
+/// first `len` bytes are accessible through `Deref`.
+#[derive(Debug)]
+pub struct MMap(Mapping);
+
+// SAFETY: `MMap` uniquely owns its mapping, like `MBox<[u8]>`.
+unsafe impl Send for MMap {}
+// SAFETY: `MMap` uniquely owns its mapping, like `MBox<[u8]>`.
+unsafe impl Sync for MMap {}
+
+impl MMap {
+    /// Maps `len` bytes of zero-initialized anonymous memory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` overflows when rounded up to a page, and aborts if `mmap()` fails.
+    pub fn new(len: usize) -> Self {
+        match Self::try_new(len) {
+            Ok(res) => res,
+            Err(AllocError::CapacityOverflow) => panic!("memory overflow"),
+            Err(AllocError::OutOfMemory(size)) => {
+                // SAFETY: the page size is a power of two.
+                handle_alloc_error(unsafe { Layout::from_size_align_unchecked(size, page_size()) })
+            }
+        }
+    }
+
+    /// Maps `len` bytes of zero-initialized anonymous memory, returning an error if `mmap()`
+    /// fails.
+    pub fn try_new(len: usize) -> Result<Self, AllocError> {
+        let map_len = mapping_len(len)?;
+        // SAFETY: an anonymous private mapping does not alias any existing memory.
+        unsafe {
+            Mapping::new(
+                len,
+                map_len,
+                PROT_READ | PROT_WRITE,
+                MAP_PRIVATE | MAP_ANONYMOUS,
+                -1,
+            )
+        }
+        .map(MMap)
+        .ok_or(AllocError::OutOfMemory(map_len))
+    }
+
+    /// Maps `len` bytes of zero-initialized anonymous memory surrounded by guard pages.
+    ///
+    /// The buffer is placed at the end of its pages, so that it is immediately followed by an
+    /// inaccessible page. Reads or writes past the end of the buffer, e.g. an off-by-one error in C
+    /// code, raise a segmentation fault instead of corrupting other memory. Another inaccessible
+    /// page precedes the buffer, which catches underflows once they cross the unused head of the
+    /// first page. This costs two extra pages of address space, and should be reserved for
+    /// debugging and hardening.
+    ///
+    /// Because of the placement, the buffer is generally not aligned.
+    pub fn try_new_guarded(len: usize) -> Result<Self, AllocError> {
+        let page_size = page_size();
+        let data_len = mapping_len(len)?;
+        let map_len = data_len
+            .checked_add(2 * page_size)
+            .ok_or(AllocError::CapacityOverflow)?;
+        // SAFETY: an anonymous private mapping does not alias any existing memory.
+        let mut map = unsafe {
+            Mapping::new(len, map_len, PROT_NONE, MAP_PRIVATE | MAP_ANONYMOUS, -1)
+                .ok_or(AllocError::OutOfMemory(map_len))?
+        };
+        map.guard_len = page_size;
+        let (start, accessible_len) = map.accessible_pages();
+        // SAFETY: the accessible pages are within the mapping owned by `map`.
+        unsafe {
+            if libc::mprotect(start, accessible_len, PROT_READ | PROT_WRITE) != 0 {
+                return Err(AllocError::OutOfMemory(map_len));
+            }
+            map.ptr = NonNull::new_unchecked((start as *mut u8).add(accessible_len - len));
+        }
+        Ok(MMap(map))
+    }
+
+    /// Maps `len` bytes of zero-initialized anonymous memory surrounded by guard pages.
+    ///
+    /// See `try_new_guarded()` for details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` overflows when rounded up to a page, and aborts if `mmap()` fails.
+    pub fn new_guarded(len: usize) -> Self {
+        match Self::try_new_guarded(len) {
+            Ok(res) => res,
+            Err(AllocError::CapacityOverflow) => panic!("memory overflow"),
+            Err(AllocError::OutOfMemory(size)) => {
+                // SAFETY: the page size is a power of two.
+                handle_alloc_error(unsafe { Layout::from_size_align_unchecked(size, page_size()) })
+            }
+        }
+    }
+
+    /// Maps `len` bytes of zero-initialized anonymous memory backed by huge pages, which reduces
+    /// TLB pressure when the buffer is very large.
+    ///
+    /// With explicit huge pages, the length is rounded up to a whole number of huge pages, and an
+    /// error is returned if not enough huge pages are reserved. Callers can fall back to `try_new()`
+    /// in that case.
+    #[cfg(target_os = "linux")]
+    pub fn try_new_huge_pages(len: usize, huge_pages: HugePages) -> Result<Self, AllocError> {
+        let (huge_page_size, flag) = match huge_pages {
+            HugePages::Transparent => {
+                let map = Self::try_new(len)?;
+                let (start, map_len) = map.0.accessible_pages();
+                // the advice is only a hint, so a failure (e.g. when transparent huge pages are
+                // not supported) is ignored.
+                // SAFETY: the pages are owned by `map`.
+                unsafe { libc::madvise(start, map_len, libc::MADV_HUGEPAGE) };
+                return Ok(map);
+            }
+            HugePages::Explicit2MiB => (2 << 20, libc::MAP_HUGE_2MB),
+            HugePages::Explicit1GiB => (1 << 30, libc::MAP_HUGE_1GB),
+        };
+        let map_len = round_up_to_pages(len, huge_page_size)?;
+        // SAFETY: an anonymous private mapping does not alias any existing memory.
+        unsafe {
+            Mapping::new(
+                len,
+                map_len,
+                PROT_READ | PROT_WRITE,
+                MAP_PRIVATE | MAP_ANONYMOUS | libc::MAP_HUGETLB | flag,
+                -1,
+            )
+        }
+        .map(MMap)
+        .ok_or(AllocError::OutOfMemory(map_len))
+    }
+
+    /// Binds the memory of the buffer to the NUMA node `node` with `mbind()`.
+    ///
+    /// Pages not yet touched will be allocated on that node, and pages already allocated are
+    /// moved there. Combine with `current_numa_node()` to keep the buffer close to the thread
+    /// processing it.
+    #[cfg(all(target_os = "linux", feature = "std"))]
+    #[allow(clippy::incompatible_msrv)] // `c_ulong` and `c_uint` for `mbind()`.
+    pub fn bind_to_numa_node(&self, node: usize) -> io::Result<()> {
+        // constants from `<linux/mempolicy.h>`.
+        const MPOL_BIND: libc::c_ulong = 2;
+        const MPOL_MF_MOVE: libc::c_uint = 1 << 1;
+        const BITS_PER_WORD: usize = 8 * std::mem::size_of::<libc::c_ulong>();
+
+        if node >= MAX_NUMA_NODES {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "NUMA node out of range",
+            ));
+        }
+        let mut mask = [0 as libc::c_ulong; MAX_NUMA_NODES / BITS_PER_WORD];
+        mask[node / BITS_PER_WORD] |= 1 << (node % BITS_PER_WORD);
+        let (start, len) = self.0.accessible_pages();
+        // SAFETY: the pages are owned by `self`, and binding does not change their content. The
+        // kernel reads one less bit than `maxnode`, hence the `+ 1`.
+        let res = unsafe {
+            libc::syscall(
+                libc::SYS_mbind,
+                start,
+                len,
+                MPOL_BIND,
+                mask.as_ptr(),
+                (MAX_NUMA_NODES + 1) as libc::c_ulong,
+                MPOL_MF_MOVE,
+            )
+        };
+        if res != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Maps the whole content of `file` copy-on-write.
+    ///
+    /// The buffer can be modified freely, but the changes are private to this mapping and never
+    /// written back to the file. The file must be opened for reading.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated, by this or any other process, while it is
+    /// mapped. Pages not yet written to through the buffer still show changes to the file, which
+    /// would modify the content behind a borrow. Truncation causes `SIGBUS` when the removed part
+    /// is accessed.
+    #[cfg(feature = "std")]
+    pub unsafe fn map_file_copy_on_write(file: &File) -> io::Result<Self> {
+        let len = file_len(file)?;
+        let map_len =
+            mapping_len(len).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        // SAFETY: a private file mapping does not alias any memory of this process, and the caller
+        // guarantees that the file is not changed underneath.
+        Mapping::new(
+            len,
+            map_len,
+            PROT_READ | PROT_WRITE,
+            MAP_PRIVATE,
+            file.as_raw_fd(),
+        )
+        .map(MMap)
+        .ok_or_else(io::Error::last_os_error)
+    }
+
+    /// Resizes the buffer to `new_len` bytes. Added bytes are zero-initialized.
+    ///
+    /// On Linux, the pages of an anonymous mapping are moved with `mremap()`, so growing even a
+    /// multi-gigabyte buffer does not copy its content. The buffer may move to a new address.
+    /// Elsewhere, and for guarded, copy-on-write or explicit huge-page buffers, a new mapping is
+    /// created and the content copied over. Guarded buffers stay guarded, while the others are
+    /// replaced by a plain anonymous mapping.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `new_len` overflows when rounded up to a page, and aborts if the mapping fails.
+    pub fn resize(&mut self, new_len: usize) {
+        match self.try_resize(new_len) {
+            Ok(()) => {}
+            Err(AllocError::CapacityOverflow) => panic!("memory overflow"),
+            Err(AllocError::OutOfMemory(size)) => {
+                // SAFETY: the page size is a power of two.
+                handle_alloc_error(unsafe { Layout::from_size_align_unchecked(size, page_size()) })
+            }
+        }
+    }
+
+    /// Resizes the buffer to `new_len` bytes, returning an error if the mapping fails. The buffer
+    /// is left untouched in that case.
+    ///
+    /// See `resize()` for details.
+    pub fn try_resize(&mut self, new_len: usize) -> Result<(), AllocError> {
+        let old_len = self.0.len;
+        let old_map_len = self.mapped_len();
+        let map_len = mapping_len(new_len)?;
+        if self.0.guard_len != 0 {
+            let mut map = Self::try_new_guarded(new_len)?;
+            let len = old_len.min(new_len);
+            map[..len].copy_from_slice(&self[..len]);
+            *self = map;
+            return Ok(());
+        }
+        if map_len != old_map_len {
+            #[cfg(target_os = "linux")]
+            {
+                if self.0.remappable {
+                    // SAFETY: the mapping is owned by `self`, and is updated right after moving.
+                    let ptr = unsafe {
+                        libc::mremap(
+                            self.0.base.as_ptr() as *mut c_void,
+                            old_map_len,
+                            map_len,
+                            libc::MREMAP_MAYMOVE,
+                        )
+                    };
+                    if ptr == MAP_FAILED {
+                        return Err(AllocError::OutOfMemory(map_len));
+                    }
+                    // SAFETY: `mremap()` never returns NULL when we do not ask for a fixed address.
+                    let ptr = unsafe { NonNull::new_unchecked(ptr as *mut u8) };
+                    self.0.ptr = ptr;
+                    self.0.base = ptr;
+                    self.0.map_len = map_len;
+                } else {
+                    self.resize_by_copy(new_len)?;
+                    return Ok(());
+                }
+            }
+            #[cfg(not(target_os = "linux"))]
+            {
+                self.resize_by_copy(new_len)?;
+                return Ok(());
+            }
+        }
+        // the tail of the old pages may still hold bytes from before an earlier shrink, while
+        // pages added by `mremap()` are already zeroed.
+        if new_len > old_len {
+            // SAFETY: the range is within the old pages, which are still mapped.
+            unsafe {
+                write_bytes(
+                    self.0.ptr.as_ptr().add(old_len),
+                    0,
+                    new_len.min(old_map_len) - old_len,
+                )
+            };
+        }
+        self.0.len = new_len;
+        Ok(())
+    }
+
+    /// Replaces the mapping by a new anonymous one of `new_len` bytes, copying the content over.
+    fn resize_by_copy(&mut self, new_len: usize) -> Result<(), AllocError> {
+        let mut map = Self::try_new(new_len)?;
+        let len = self.0.len.min(new_len);
+        map[..len].copy_from_slice(&self[..len]);
+        *self = map;
+        Ok(())
+    }
+
+    /// Returns the number of bytes actually mapped, which is `len()` rounded up to a whole
+    /// number of pages.
+    pub fn mapped_len(&self) -> usize {
+        self.0.accessible_pages().1
+    }
+
+    /// Tells the operating system how the buffer is going to be accessed.
+    #[cfg(feature = "std")]
+    pub fn advise(&self, advice: Advice) -> io::Result<()> {
+        self.0.advise(advice)
+    }
+
+    /// Makes the pages read-only with `mprotect()`, and returns an immutable handle.
+    ///
+    /// Any later write to the buffer, including through raw pointers kept by C code, raises a
+    /// segmentation fault instead of silently modifying the content.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mprotect()` fails.
+    pub fn freeze(self) -> ReadOnlyMMap {
+        let MMap(map) = self;
+        let (start, len) = map.accessible_pages();
+        // SAFETY: the mapping is owned by `map`, and no mutable borrows of it exist anymore.
+        let res = unsafe { libc::mprotect(start, len, PROT_READ) };
+        assert_eq!(res, 0, "mprotect() failed");
+        ReadOnlyMMap(map)
+    }
+}
+
+impl Deref for MMap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+}
+
+impl DerefMut for MMap {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: the first `len` bytes of the mapping are writable and owned by `self`.
+        unsafe { from_raw_parts_mut(self.0.ptr.as_ptr(), self.0.len) }
+    }
+}
+
+impl AsRef<[u8]> for MMap {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl AsMut<[u8]> for MMap {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+/// A read-only byte buffer backed by a memory mapping.
+///
+/// It is created by mapping a file with `map_file()`, or by freezing an `MMap`.
+///
+/// Writing to the pages of a `ReadOnlyMMap` by any means, including through raw pointers in C
+/// code, raises a segmentation fault.
+#[derive(Debug)]
+pub struct ReadOnlyMMap(Mapping);
+
+// SAFETY: `ReadOnlyMMap` uniquely owns its mapping, and only exposes it immutably.
+unsafe impl Send for ReadOnlyMMap {}
+// SAFETY: `ReadOnlyMMap` uniquely owns its mapping, and only exposes it immutably.
+unsafe impl Sync for ReadOnlyMMap {}
+
+impl ReadOnlyMMap {
+    /// Maps the whole content of `file` read-only. The file must be opened for reading.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated, by this or any other process, while it is
+    /// mapped. Any change to the file is immediately visible through the buffer, which would
+    /// modify the content behind a borrow. Truncation causes `SIGBUS` when the removed part is
+    /// accessed.
+    #[cfg(feature = "std")]
+    pub unsafe fn map_file(file: &File) -> io::Result<Self> {
+        let len = file_len(file)?;
+        let map_len =
+            mapping_len(len).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        // SAFETY: the mapping is never written to through `self`, and the caller guarantees that
+        // the file is not changed underneath.
+        Mapping::new(len, map_len, PROT_READ, libc::MAP_SHARED, file.as_raw_fd())
+            .map(ReadOnlyMMap)
+            .ok_or_else(io::Error::last_os_error)
+    }
+
+    /// Returns the number of bytes actually mapped, which is `len()` rounded up to a whole
+    /// number of pages.
+    pub fn mapped_len(&self) -> usize {
+        self.0.accessible_pages().1
+    }
+
+    /// Tells the operating system how the buffer is going to be accessed.
+    #[cfg(feature = "std")]
+    pub fn advise(&self, advice: Advice) -> io::Result<()> {
+        self.0.advise(advice)
+    }
+}
+
+impl Deref for ReadOnlyMMap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+}
+
+impl AsRef<[u8]> for ReadOnlyMMap {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+/// Creates an anonymous shared memory object of `len` bytes, which is closed on exec.
+#[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+#[allow(clippy::incompatible_msrv)] // `c_char` for the name.
+fn create_shared_memory(len: usize) -> io::Result<File> {
+    // SAFETY: the name is a valid C string.
+    let fd = unsafe {
+        libc::memfd_create(
+            b"mbox\0".as_ptr() as *const libc::c_char,
+            libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `fd` is a newly created file descriptor owned by nobody else.
+    let file = unsafe { File::from_raw_fd(fd) };
+    file.set_len(len as u64)?;
+    Ok(file)
+}
+
+/// Creates an anonymous shared memory object of `len` bytes, which is closed on exec.
+#[cfg(all(feature = "std", not(any(target_os = "linux", target_os = "android"))))]
+fn create_shared_memory(len: usize) -> io::Result<File> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let name = format!(
+        "/mbox-{}-{}\0",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    );
+    let name = name.as_ptr() as *const libc::c_char;
+    // SAFETY: `name` is a valid C string.
+    let fd = unsafe { libc::shm_open(name, libc::O_RDWR | libc::O_CREAT | libc::O_EXCL, 0o600) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `fd` is a newly created file descriptor owned by nobody else. The name is removed
+    // right away, so the object lives only as long as it is opened or mapped.
+    let file = unsafe {
+        libc::shm_unlink(name);
+        libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+        File::from_raw_fd(fd)
+    };
+    file.set_len(len as u64)?;
+    Ok(file)
+}
+
+/// A byte buffer backed by a shared memory object, which can be shared with other processes.
+///
+/// The memory object is a `memfd` on Linux and Android, and an unlinked POSIX shared memory object
+/// (`shm_open()`) elsewhere. Its file descriptor, obtained with `file()`, can be inherited by a
+/// child process or passed over a Unix socket, and mapped again there, e.g. with
+/// `ReadOnlyMMap::map_file()`. The object is destroyed once every descriptor is closed and every
+/// mapping is dropped.
+///
+/// The buffer is borrowed through `Deref` and `DerefMut` like a plain slice, so nothing else may
+/// access the memory object while it is borrowed. Getting the file descriptor is therefore
+/// `unsafe`, and the caller is responsible for synchronizing the other processes or mappings.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct SharedMMap {
+    map: Mapping,
+    file: File,
+    sync_on_drop: bool,
+}
+
+// SAFETY: `SharedMMap` uniquely owns its mapping within this process.
+#[cfg(feature = "std")]
+unsafe impl Send for SharedMMap {}
+// SAFETY: `SharedMMap` uniquely owns its mapping within this process.
+#[cfg(feature = "std")]
+unsafe impl Sync for SharedMMap {}
+
+#[cfg(feature = "std")]
+impl SharedMMap {
+    /// Creates a zero-initialized shared memory object of `len` bytes and maps it.
+    pub fn new(len: usize) -> io::Result<Self> {
+        let map_len =
+            mapping_len(len).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let file = create_shared_memory(len)?;
+        // SAFETY: the memory object is newly created, so the mapping does not alias any existing
+        // memory of this process.
+        let map = unsafe {
+            Mapping::new(
+                len,
+                map_len,
+                PROT_READ | PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+            )
+        }
+        .ok_or_else(io::Error::last_os_error)?;
+        Ok(Self {
+            map,
+            file,
+            sync_on_drop: false,
+        })
+    }
+
+    /// Maps the whole content of `file` shared and writable. The file must be opened for reading
+    /// and writing.
+    ///
+    /// Changes to the buffer are written back to the file by the operating system at some point,
+    /// or immediately with `flush()`.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified, truncated or mapped again, by this or any other process,
+    /// while the buffer is alive, except through the buffer itself. Any other change to the file
+    /// is immediately visible through the buffer, which would modify the content behind a borrow,
+    /// and a second writable mapping would alias it. Truncation causes `SIGBUS` when the removed
+    /// part is accessed.
+    pub unsafe fn map_file(file: File) -> io::Result<Self> {
+        let len = file_len(&file)?;
+        let map_len =
+            mapping_len(len).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        // SAFETY: the caller guarantees that nothing else modifies or maps the file, so the
+        // mapping does not alias any memory of this process.
+        let map = Mapping::new(
+            len,
+            map_len,
+            PROT_READ | PROT_WRITE,
+            libc::MAP_SHARED,
+            file.as_raw_fd(),
+        )
+        .ok_or_else(io::Error::last_os_error)?;
+        Ok(Self {
+            map,
+            file,
+            sync_on_drop: false,
+        })
+    }
+
+    /// Writes all modified pages back to the underlying file with `msync()`, and waits until it
+    /// is done.
+    pub fn flush(&self) -> io::Result<()> {
+        self.flush_range(0, self.map.len)
+    }
+
+    /// Writes the modified pages overlapping `offset..offset + len` back to the underlying file
+    /// with `msync()`, and waits until it is done.
+    pub fn flush_range(&self, offset: usize, len: usize) -> io::Result<()> {
+        if offset
+            .checked_add(len)
+            .map_or(true, |end| end > self.map.len)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "flush range out of bounds",
+            ));
+        }
+        // `msync()` requires a page-aligned address. The mapping itself is page-aligned.
+        let aligned_offset = offset & !(page_size() - 1);
+        // SAFETY: the pages are within the mapping owned by `self`, and syncing does not change
+        // their content.
+        let res = unsafe {
+            libc::msync(
+                self.map.ptr.as_ptr().add(aligned_offset) as *mut c_void,
+                len + (offset - aligned_offset),
+                libc::MS_SYNC,
+            )
+        };
+        if res != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Sets whether the buffer is flushed when dropped. This is disabled by default.
+    ///
+    /// Errors cannot be reported from `drop()`. Call `flush()` explicitly to handle them.
+    pub fn set_sync_on_drop(&mut self, sync_on_drop: bool) {
+        self.sync_on_drop = sync_on_drop;
+    }
+
+    /// Returns the shared memory object, or the file mapped by `map_file()`.
+    ///
+    /// # Safety
+    ///
+    /// While the buffer is borrowed, the file must not be modified or truncated, neither directly
+    /// nor through another mapping, by this or any other process. Otherwise the content would
+    /// change behind the borrow. Reading the file or its metadata is always fine.
+    pub unsafe fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// Returns the number of bytes actually mapped, which is `len()` rounded up to a whole
+    /// number of pages.
+    pub fn mapped_len(&self) -> usize {
+        self.map.accessible_pages().1
+    }
+
+    /// Tells the operating system how the buffer is going to be accessed.
+    #[cfg(feature = "std")]
+    pub fn advise(&self, advice: Advice) -> io::Result<()> {
+        self.map.advise(advice)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for SharedMMap {
+    fn drop(&mut self) {
+        if self.sync_on_drop {
+            let _ = self.flush();
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Deref for SharedMMap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.map.as_slice()
+    }
+}
+
+#[cfg(feature = "std")]
+impl DerefMut for SharedMMap {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: the first `len` bytes of the mapping are writable and owned by `self`.
+        unsafe { from_raw_parts_mut(self.map.ptr.as_ptr(), self.map.len) }
+    }
+}
+
+#[cfg(feature = "std")]
+impl AsRef<[u8]> for SharedMMap {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+#[cfg(feature = "std")]
+impl AsMut<[u8]> for SharedMMap {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+#[cfg(not(miri))]
+#[test]
+fn test_mmap() {
+    let mut map = MMap::new(10000);
+    assert_eq!(map.len(), 10000);
+    assert_eq!(map.mapped_len() % page_size(), 0);
+    assert!(map.mapped_len() >= 10000);
+    assert_eq!(map.as_ptr() as usize % page_size(), 0);
+    assert!(map.iter().all(|&b| b == 0));
+    map[9999] = 7;
+    map[..3].copy_from_slice(b"abc");
+    assert_eq!(&map[..3], b"abc");
+    assert_eq!(map[9999], 7);
+}
+
+#[cfg(not(miri))]
+#[test]
+fn test_mmap_empty() {
+    let map = MMap::new(0);
+    assert!(map.is_empty());
+    assert_eq!(map.mapped_len(), page_size());
+}
+
+#[cfg(not(miri))]
+#[test]
+fn test_mmap_overflow() {
+    assert_eq!(
+        MMap::try_new(usize::max_value()).unwrap_err(),
+        AllocError::CapacityOverflow
+    );
+}
+
+#[cfg(all(feature = "std", not(miri)))]
+#[cfg(test)]
+fn temp_file(content: &[u8]) -> File {
+    use std::io::{Seek, SeekFrom, Write};
+
+    let path = std::env::temp_dir().join(format!(
+        "mbox-mmap-test-{}-{:?}",
+        std::process::id(),
+        std::thread::current().id()
+    ));
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    file.write_all(content).unwrap();
+    file.seek(SeekFrom::Start(0)).unwrap();
+    file
+}
+
+#[cfg(all(feature = "std", not(miri)))]
+#[test]
+fn test_map_file() {
+    let file = temp_file(b"hello, world");
+    let map = unsafe { ReadOnlyMMap::map_file(&file) }.unwrap();
+    assert_eq!(&*map, b"hello, world");
+    assert_eq!(map.mapped_len(), page_size());
+
+    let map = unsafe { ReadOnlyMMap::map_file(&temp_file(b"")) }.unwrap();
+    assert!(map.is_empty());
+}
+
+#[cfg(all(feature = "std", not(miri)))]
+#[test]
+fn test_map_file_copy_on_write() {
+    use std::io::Read;
+
+    let mut file = temp_file(b"hello, world");
+    let mut map = unsafe { MMap::map_file_copy_on_write(&file) }.unwrap();
+    map[..5].copy_from_slice(b"HELLO");
+    assert_eq!(&*map, b"HELLO, world");
+    map.resize(2 * page_size());
+    assert_eq!(&map[..12], b"HELLO, world");
+    assert!(map[12..].iter().all(|&b| b == 0));
+
+    let mut content = Vec::new();
+    file.read_to_end(&mut content).unwrap();
+    assert_eq!(content, b"hello, world");
+}
+
+#[cfg(all(feature = "std", not(miri)))]
+#[test]
+fn test_shared_mmap() {
+    let mut shared = SharedMMap::new(10000).unwrap();
+    assert_eq!(shared.len(), 10000);
+    assert!(shared.iter().all(|&b| b == 0));
+    assert_eq!(unsafe { shared.file() }.metadata().unwrap().len(), 10000);
+
+    let other = unsafe { ReadOnlyMMap::map_file(shared.file()) }.unwrap();
+    shared[..5].copy_from_slice(b"hello");
+    shared[9999] = 1;
+    assert_eq!(&other[..5], b"hello");
+    assert_eq!(other[9999], 1);
+
+    drop(shared);
+    assert_eq!(&other[..5], b"hello");
+
+    assert!(SharedMMap::new(0).unwrap().is_empty());
+}
+
+#[cfg(all(feature = "std", not(miri)))]
+#[test]
+fn test_shared_mmap_map_file() {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let file = temp_file(b"hello, world");
+    let mut map = unsafe { SharedMMap::map_file(file) }.unwrap();
+    assert_eq!(&*map, b"hello, world");
+    map[..5].copy_from_slice(b"HELLO");
+    map.flush().unwrap();
+    map.flush_range(7, 5).unwrap();
+    map.flush_range(12, 0).unwrap();
+    assert_eq!(
+        map.flush_range(7, 6).unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+    assert_eq!(
+        map.flush_range(usize::max_value(), 2).unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+
+    let mut file = unsafe { map.file() }.try_clone().unwrap();
+    map[7] = b'W';
+    map.set_sync_on_drop(true);
+    drop(map);
+
+    let mut content = Vec::new();
+    file.seek(SeekFrom::Start(0)).unwrap();
+    file.read_to_end(&mut content).unwrap();
+    assert_eq!(content, b"HELLO, World");
+}
+
+#[cfg(not(miri))]
+#[test]
+fn test_freeze() {
+    let mut map = MMap::new(10000);
+    map[..5].copy_from_slice(b"hello");
+    let frozen = map.freeze();
+    assert_eq!(&frozen[..5], b"hello");
+    assert_eq!(frozen.len(), 10000);
+    assert!(frozen[5..].iter().all(|&b| b == 0));
+}
+
+#[cfg(not(miri))]
+#[test]
+fn test_mmap_resize() {
+    let mut map = MMap::new(100);
+    map[..5].copy_from_slice(b"hello");
+    map[99] = 1;
+    map.resize(10);
+    assert_eq!(&map[..], b"hello\0\0\0\0\0");
+    map.resize(100);
+    assert!(map[5..].iter().all(|&b| b == 0));
+
+    let len = 3 * page_size() + 1;
+    map.resize(len);
+    assert_eq!(map.len(), len);
+    assert_eq!(map.mapped_len(), 4 * page_size());
+    assert_eq!(map.as_ptr() as usize % page_size(), 0);
+    assert_eq!(&map[..5], b"hello");
+    assert!(map[5..].iter().all(|&b| b == 0));
+    map[len - 1] = 2;
+    map.resize(page_size());
+    assert_eq!(map.mapped_len(), page_size());
+    assert_eq!(&map[..5], b"hello");
+    map.resize(0);
+    assert!(map.is_empty());
+
+    assert_eq!(
+        map.try_resize(usize::max_value()).unwrap_err(),
+        AllocError::CapacityOverflow
+    );
+
+    let mut map = MMap::new_guarded(10);
+    map[9] = 3;
+    map.resize(page_size() + 10);
+    assert_eq!(map[9], 3);
+    assert_eq!(map.mapped_len(), 2 * page_size());
+    assert_eq!((map.as_ptr() as usize + map.len()) % page_size(), 0);
+}
+
+#[cfg(not(miri))]
+#[test]
+fn test_mmap_guarded() {
+    let mut map = MMap::new_guarded(100);
+    assert_eq!(map.len(), 100);
+    assert_eq!(map.mapped_len(), page_size());
+    assert_eq!((map.as_ptr() as usize + 100) % page_size(), 0);
+    assert!(map.iter().all(|&b| b == 0));
+    map[99] = 1;
+    let frozen = map.freeze();
+    assert_eq!(frozen[99], 1);
+
+    let map = MMap::new_guarded(0);
+    assert!(map.is_empty());
+    assert_eq!(map.as_ptr() as usize % page_size(), 0);
+}
+
+#[cfg(all(feature = "std", not(miri), not(target_os = "macos")))]
+#[test]
+fn test_mmap_guarded_overflow_faults() {
+    let map = MMap::new_guarded(100);
+    // SAFETY: the child process only reads past the end of the buffer, and exits.
+    unsafe {
+        let pid = libc::fork();
+        if pid == 0 {
+            std::ptr::read_volatile(map.as_ptr().add(100));
+            libc::_exit(0);
+        }
+        let mut status = 0;
+        assert_eq!(libc::waitpid(pid, &mut status, 0), pid);
+        // The child is normally killed by `SIGSEGV`, but sanitizers turn it into an exit code.
+        assert!(!libc::WIFEXITED(status) || libc::WEXITSTATUS(status) != 0);
+    }
+}
+
+#[cfg(all(feature = "std", not(miri)))]
+#[test]
+fn test_advise() {
+    let mut map = MMap::new(3 * page_size());
+    map[0] = 1;
+    for &advice in &[
+        Advice::Sequential,
+        Advice::Random,
+        Advice::WillNeed,
+        Advice::DontNeed,
+        Advice::Normal,
+    ] {
+        map.advise(advice).unwrap();
+    }
+    assert_eq!(map[0], 1);
+
+    let mut shared = SharedMMap::new(page_size()).unwrap();
+    shared[0] = 2;
+    shared.advise(Advice::DontNeed).unwrap();
+    assert_eq!(shared[0], 2);
+
+    let map = unsafe { ReadOnlyMMap::map_file(shared.file()) }.unwrap();
+    map.advise(Advice::Sequential).unwrap();
+    map.advise(Advice::DontNeed).unwrap();
+    assert_eq!(map[0], 2);
+
+    let mut map = MMap::new_guarded(10);
+    map[9] = 3;
+    map.advise(Advice::DontNeed).unwrap();
+    assert_eq!(map[9], 3);
+}
+
+#[cfg(all(target_os = "linux", not(miri)))]
+#[test]
+fn test_mmap_huge_pages() {
+    let mut map = MMap::try_new_huge_pages(5 << 20, HugePages::Transparent).unwrap();
+    assert_eq!(map.len(), 5 << 20);
+    map[(5 << 20) - 1] = 1;
+    assert!(map[..4096].iter().all(|&b| b == 0));
+
+    // explicit huge pages are usually not reserved on test machines.
+    match MMap::try_new_huge_pages(3 << 20, HugePages::Explicit2MiB) {
+        Ok(mut map) => {
+            assert_eq!(map.len(), 3 << 20);
+            assert_eq!(map.mapped_len(), 4 << 20);
+            assert_eq!(map.as_ptr() as usize % (2 << 20), 0);
+            map[0] = 1;
+        }
+        Err(e) => assert_eq!(e, AllocError::OutOfMemory(4 << 20)),
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "std", not(miri)))]
+#[test]
+fn test_bind_to_numa_node() {
+    let node = current_numa_node().unwrap();
+    assert!(node < MAX_NUMA_NODES);
+
+    let mut map = MMap::new(1 << 20);
+    map[0] = 1;
+    match map.bind_to_numa_node(node) {
+        Ok(()) => {}
+        // `mbind()` is unavailable in kernels without NUMA support, and in some sandboxes.
+        Err(e) => assert!(
+            [libc::ENOSYS, libc::EPERM].contains(&e.raw_os_error().unwrap()),
+            "{}",
+            e
+        ),
+    }
+    map[(1 << 20) - 1] = 2;
+    assert_eq!(map[0], 1);
+
+    assert_eq!(
+        map.bind_to_numa_node(MAX_NUMA_NODES).unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+}
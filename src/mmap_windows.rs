@@ -0,0 +1,365 @@
+//! Byte buffers backed by `VirtualAlloc()`.
+//!
+//! This is the Windows counterpart of the Unix memory mappings. The memory of an `MMap` is obtained
+//! from `VirtualAlloc()` and released with `VirtualFree()`. It must never be passed to C's
+//! `free()`. In return, huge buffers do not fragment the `malloc()` heap, and are given back to the
+//! operating system as soon as they are dropped.
+
+use libc::c_void;
+
+use std::alloc::Layout;
+use std::fmt::{Debug, Formatter, Result as FormatResult};
+use std::mem::zeroed;
+use std::ops::{Deref, DerefMut};
+use std::ptr::{null_mut, write_bytes, NonNull};
+use std::slice::{from_raw_parts, from_raw_parts_mut};
+
+use crate::error::AllocError;
+use crate::internal::handle_alloc_error;
+
+// constants from `<winnt.h>`.
+const MEM_COMMIT: u32 = 0x1000;
+const MEM_RESERVE: u32 = 0x2000;
+const MEM_RELEASE: u32 = 0x8000;
+const MEM_LARGE_PAGES: u32 = 0x2000_0000;
+const PAGE_READONLY: u32 = 0x02;
+const PAGE_READWRITE: u32 = 0x04;
+
+#[repr(C)]
+struct SystemInfo {
+    processor_architecture: u16,
+    reserved: u16,
+    page_size: u32,
+    minimum_application_address: *mut c_void,
+    maximum_application_address: *mut c_void,
+    active_processor_mask: usize,
+    number_of_processors: u32,
+    processor_type: u32,
+    allocation_granularity: u32,
+    processor_level: u16,
+    processor_revision: u16,
+}
+
+#[link(name = "kernel32")]
+extern "system" {
+    fn VirtualAlloc(
+        address: *mut c_void,
+        size: usize,
+        allocation_type: u32,
+        protect: u32,
+    ) -> *mut c_void;
+    fn VirtualFree(address: *mut c_void, size: usize, free_type: u32) -> i32;
+    fn VirtualProtect(
+        address: *mut c_void,
+        size: usize,
+        protect: u32,
+        old_protect: *mut u32,
+    ) -> i32;
+    fn GetSystemInfo(system_info: *mut SystemInfo);
+    fn GetLargePageMinimum() -> usize;
+}
+
+/// Returns the size of a memory page.
+fn page_size() -> usize {
+    // SAFETY: `GetSystemInfo()` fills in the whole structure.
+    unsafe {
+        let mut system_info = zeroed::<SystemInfo>();
+        GetSystemInfo(&mut system_info);
+        system_info.page_size as usize
+    }
+}
+
+/// Rounds `len` up to a whole number of pages of the given size, which must be a power of two.
+/// Empty buffers still occupy one page, since `VirtualAlloc()` cannot allocate 0 bytes.
+fn round_up_to_pages(len: usize, page_size: usize) -> Result<usize, AllocError> {
+    let len = len.max(1);
+    len.checked_add(page_size - 1)
+        .map(|len| len & !(page_size - 1))
+        .ok_or(AllocError::CapacityOverflow)
+}
+
+/// An owned `VirtualAlloc()` region, released on drop.
+struct Mapping {
+    ptr: NonNull<u8>,
+    len: usize,
+    map_len: usize,
+}
+
+impl Mapping {
+    /// Reserves and commits `map_len` bytes, returning `None` if it fails.
+    fn new(len: usize, map_len: usize, allocation_type: u32) -> Option<Self> {
+        // SAFETY: a new region does not alias any existing memory.
+        let ptr = unsafe {
+            VirtualAlloc(
+                null_mut(),
+                map_len,
+                MEM_RESERVE | MEM_COMMIT | allocation_type,
+                PAGE_READWRITE,
+            )
+        };
+        NonNull::new(ptr as *mut u8).map(|ptr| Self { ptr, len, map_len })
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        // SAFETY: the first `len` bytes of the region are readable.
+        unsafe { from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: the region is owned by `self`.
+        unsafe {
+            VirtualFree(self.ptr.as_ptr() as *mut c_void, 0, MEM_RELEASE);
+        }
+    }
+}
+
+impl Debug for Mapping {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        formatter
+            .debug_struct("MMap")
+            .field("ptr", &self.ptr)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+/// A zero-initialized byte buffer backed by `VirtualAlloc()`.
+///
+/// The region is page-aligned, and its length is rounded up to a whole number of pages. Only the
+/// first `len` bytes are accessible through `Deref`.
+#[derive(Debug)]
+pub struct MMap(Mapping);
+
+// SAFETY: `MMap` uniquely owns its region, like `MBox<[u8]>`.
+unsafe impl Send for MMap {}
+// SAFETY: `MMap` uniquely owns its region, like `MBox<[u8]>`.
+unsafe impl Sync for MMap {}
+
+impl MMap {
+    /// Allocates `len` bytes of zero-initialized memory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` overflows when rounded up to a page, and aborts if `VirtualAlloc()` fails.
+    pub fn new(len: usize) -> Self {
+        match Self::try_new(len) {
+            Ok(res) => res,
+            Err(AllocError::CapacityOverflow) => panic!("memory overflow"),
+            Err(AllocError::OutOfMemory(size)) => {
+                // SAFETY: the page size is a power of two.
+                handle_alloc_error(unsafe { Layout::from_size_align_unchecked(size, page_size()) })
+            }
+        }
+    }
+
+    /// Allocates `len` bytes of zero-initialized memory, returning an error if `VirtualAlloc()`
+    /// fails.
+    pub fn try_new(len: usize) -> Result<Self, AllocError> {
+        let map_len = round_up_to_pages(len, page_size())?;
+        Mapping::new(len, map_len, 0)
+            .map(MMap)
+            .ok_or(AllocError::OutOfMemory(map_len))
+    }
+
+    /// Allocates `len` bytes of zero-initialized memory backed by large pages, which reduces TLB
+    /// pressure when the buffer is very large.
+    ///
+    /// The length is rounded up to a whole number of large pages. Allocating large pages requires
+    /// the `SeLockMemoryPrivilege` privilege, and an error is returned without it. Callers can
+    /// fall back to `try_new()` in that case.
+    pub fn try_new_large_pages(len: usize) -> Result<Self, AllocError> {
+        // SAFETY: `GetLargePageMinimum()` has no preconditions.
+        let large_page_size = unsafe { GetLargePageMinimum() };
+        if large_page_size == 0 {
+            return Err(AllocError::OutOfMemory(len));
+        }
+        let map_len = round_up_to_pages(len, large_page_size)?;
+        Mapping::new(len, map_len, MEM_LARGE_PAGES)
+            .map(MMap)
+            .ok_or(AllocError::OutOfMemory(map_len))
+    }
+
+    /// Resizes the buffer to `new_len` bytes. Added bytes are zero-initialized.
+    ///
+    /// The content is copied into a new region unless it still fits in the current pages. The
+    /// buffer may move to a new address.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `new_len` overflows when rounded up to a page, and aborts if `VirtualAlloc()`
+    /// fails.
+    pub fn resize(&mut self, new_len: usize) {
+        match self.try_resize(new_len) {
+            Ok(()) => {}
+            Err(AllocError::CapacityOverflow) => panic!("memory overflow"),
+            Err(AllocError::OutOfMemory(size)) => {
+                // SAFETY: the page size is a power of two.
+                handle_alloc_error(unsafe { Layout::from_size_align_unchecked(size, page_size()) })
+            }
+        }
+    }
+
+    /// Resizes the buffer to `new_len` bytes, returning an error if `VirtualAlloc()` fails. The
+    /// buffer is left untouched in that case.
+    pub fn try_resize(&mut self, new_len: usize) -> Result<(), AllocError> {
+        let old_len = self.0.len;
+        if round_up_to_pages(new_len, page_size())? != self.0.map_len {
+            let mut map = Self::try_new(new_len)?;
+            let len = old_len.min(new_len);
+            map[..len].copy_from_slice(&self[..len]);
+            *self = map;
+            return Ok(());
+        }
+        if new_len > old_len {
+            // SAFETY: the range is within the committed pages, which may still hold bytes from
+            // before an earlier shrink.
+            unsafe { write_bytes(self.0.ptr.as_ptr().add(old_len), 0, new_len - old_len) };
+        }
+        self.0.len = new_len;
+        Ok(())
+    }
+
+    /// Returns the number of bytes actually allocated, which is `len()` rounded up to a whole
+    /// number of pages.
+    pub fn mapped_len(&self) -> usize {
+        self.0.map_len
+    }
+
+    /// Makes the pages read-only (`PAGE_READONLY`) with `VirtualProtect()`, and returns an
+    /// immutable handle.
+    ///
+    /// Any later write to the buffer, including through raw pointers kept by C code, raises an
+    /// access violation instead of silently modifying the content.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `VirtualProtect()` fails.
+    pub fn freeze(self) -> ReadOnlyMMap {
+        let MMap(map) = self;
+        let mut old_protect = 0;
+        // SAFETY: the region is owned by `map`, and no mutable borrows of it exist anymore.
+        let res = unsafe {
+            VirtualProtect(
+                map.ptr.as_ptr() as *mut c_void,
+                map.map_len,
+                PAGE_READONLY,
+                &mut old_protect,
+            )
+        };
+        assert_ne!(res, 0, "VirtualProtect() failed");
+        ReadOnlyMMap(map)
+    }
+}
+
+impl Deref for MMap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+}
+
+impl DerefMut for MMap {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: the first `len` bytes of the region are writable and owned by `self`.
+        unsafe { from_raw_parts_mut(self.0.ptr.as_ptr(), self.0.len) }
+    }
+}
+
+impl AsRef<[u8]> for MMap {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl AsMut<[u8]> for MMap {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+/// A read-only byte buffer backed by `VirtualAlloc()`, created by freezing an `MMap`.
+///
+/// Writing to the pages of a `ReadOnlyMMap` by any means, including through raw pointers in C
+/// code, raises an access violation.
+#[derive(Debug)]
+pub struct ReadOnlyMMap(Mapping);
+
+// SAFETY: `ReadOnlyMMap` uniquely owns its region, and only exposes it immutably.
+unsafe impl Send for ReadOnlyMMap {}
+// SAFETY: `ReadOnlyMMap` uniquely owns its region, and only exposes it immutably.
+unsafe impl Sync for ReadOnlyMMap {}
+
+impl ReadOnlyMMap {
+    /// Returns the number of bytes actually allocated, which is `len()` rounded up to a whole
+    /// number of pages.
+    pub fn mapped_len(&self) -> usize {
+        self.0.map_len
+    }
+}
+
+impl Deref for ReadOnlyMMap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+}
+
+impl AsRef<[u8]> for ReadOnlyMMap {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+#[test]
+fn test_mmap() {
+    let mut map = MMap::new(10000);
+    assert_eq!(map.len(), 10000);
+    assert_eq!(map.mapped_len() % page_size(), 0);
+    assert!(map.mapped_len() >= 10000);
+    assert_eq!(map.as_ptr() as usize % page_size(), 0);
+    assert!(map.iter().all(|&b| b == 0));
+    map[9999] = 7;
+    map[..3].copy_from_slice(b"abc");
+    assert_eq!(&map[..3], b"abc");
+
+    let frozen = map.freeze();
+    assert_eq!(&frozen[..3], b"abc");
+    assert_eq!(frozen[9999], 7);
+}
+
+#[test]
+fn test_mmap_resize() {
+    let mut map = MMap::new(100);
+    map[..5].copy_from_slice(b"hello");
+    map[99] = 1;
+    map.resize(10);
+    map.resize(100);
+    assert!(map[5..].iter().all(|&b| b == 0));
+    map.resize(3 * page_size() + 1);
+    assert_eq!(map.mapped_len(), 4 * page_size());
+    assert_eq!(&map[..5], b"hello");
+    assert!(map[5..].iter().all(|&b| b == 0));
+    map.resize(0);
+    assert!(map.is_empty());
+}
+
+#[test]
+fn test_mmap_empty() {
+    let map = MMap::new(0);
+    assert!(map.is_empty());
+    assert_eq!(map.mapped_len(), page_size());
+}
+
+#[test]
+fn test_mmap_large_pages() {
+    // large pages need a privilege which test accounts usually do not have.
+    if let Ok(mut map) = MMap::try_new_large_pages(100) {
+        assert_eq!(map.len(), 100);
+        map[99] = 1;
+    }
+    assert!(MMap::try_new_large_pages(usize::max_value()).is_err());
+}
@@ -0,0 +1,429 @@
+//! Cells which are initialized once, with their content allocated by `malloc()`.
+//!
+//! `MOnceCell<T>` and `MLazy<T>` are the single-threaded variants, like `std::cell::OnceCell` and
+//! `LazyCell`. `SyncMOnceCell<T>` and `SyncMLazy<T>` can be shared between threads, e.g. in a
+//! static. The content lives on the C heap, so it can be handed to C code once it is initialized.
+//!
+//! The thread-safe variants never block. When several threads initialize the cell at the same
+//! time, all of them run the initializer, and only the first result to be stored is kept. The
+//! others are dropped.
+//!
+//! ```
+//! use mbox::once::SyncMLazy;
+//!
+//! fn build_table() -> [u8; 16] {
+//!     let mut table = [0; 16];
+//!     for (i, entry) in table.iter_mut().enumerate() {
+//!         *entry = (i * i) as u8;
+//!     }
+//!     table
+//! }
+//!
+//! static SQUARES: SyncMLazy<[u8; 16]> = SyncMLazy::new(build_table);
+//!
+//! assert_eq!(SQUARES[3], 9);
+//! ```
+
+use std::cell::{Cell, UnsafeCell};
+use std::fmt::{Debug, Formatter, Result as FormatResult};
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+use crate::mbox::MBox;
+
+//{{{ Single-threaded -----------------------------------------------------------------------------
+
+/// A cell which can be written to only once. The value is allocated with `malloc()` when set.
+pub struct MOnceCell<T> {
+    inner: UnsafeCell<Option<MBox<T>>>,
+}
+
+impl<T> MOnceCell<T> {
+    /// Creates an empty cell.
+    pub const fn new() -> Self {
+        Self {
+            inner: UnsafeCell::new(None),
+        }
+    }
+
+    /// Returns the value, or `None` if the cell is empty.
+    pub fn get(&self) -> Option<&T> {
+        // SAFETY: the box is never modified through a shared reference once it is set.
+        unsafe { (*self.inner.get()).as_ref().map(|boxed| &**boxed) }
+    }
+
+    /// Returns the value mutably, or `None` if the cell is empty.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        // SAFETY: the cell is borrowed mutably.
+        unsafe { (*self.inner.get()).as_mut().map(|boxed| &mut **boxed) }
+    }
+
+    /// Sets the value of the cell. Returns the value back if the cell is already set.
+    pub fn set(&self, value: T) -> Result<(), T> {
+        if self.get().is_some() {
+            return Err(value);
+        }
+        // SAFETY: the cell is empty, so no references to its content exist.
+        unsafe { *self.inner.get() = Some(MBox::new(value)) };
+        Ok(())
+    }
+
+    /// Returns the value, initializing it with `f` if the cell is empty.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `f` initializes the cell itself.
+    pub fn get_or_init<F: FnOnce() -> T>(&self, f: F) -> &T {
+        if let Some(value) = self.get() {
+            return value;
+        }
+        let value = f();
+        assert!(self.set(value).is_ok(), "reentrant init");
+        self.get().unwrap()
+    }
+
+    /// Takes the box out of the cell, leaving it empty.
+    pub fn take(&mut self) -> Option<MBox<T>> {
+        // SAFETY: the cell is borrowed mutably.
+        unsafe { (*self.inner.get()).take() }
+    }
+
+    /// Consumes the cell and returns the box, if set.
+    pub fn into_inner(mut self) -> Option<MBox<T>> {
+        self.take()
+    }
+}
+
+impl<T> Default for MOnceCell<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Debug> Debug for MOnceCell<T> {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        formatter
+            .debug_tuple("MOnceCell")
+            .field(&self.get())
+            .finish()
+    }
+}
+
+/// A value which is initialized on first access, and allocated with `malloc()` at that time.
+pub struct MLazy<T, F = fn() -> T> {
+    cell: MOnceCell<T>,
+    init: Cell<Option<F>>,
+}
+
+impl<T, F> MLazy<T, F> {
+    /// Creates a lazy value which is initialized by `f`.
+    pub const fn new(f: F) -> Self {
+        Self {
+            cell: MOnceCell::new(),
+            init: Cell::new(Some(f)),
+        }
+    }
+
+    /// Consumes the lazy value and returns the box, if initialized.
+    pub fn into_inner(this: Self) -> Option<MBox<T>> {
+        this.cell.into_inner()
+    }
+}
+
+impl<T, F: FnOnce() -> T> MLazy<T, F> {
+    /// Initializes the value if needed, and returns it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the initializer panicked before.
+    pub fn force(this: &Self) -> &T {
+        this.cell.get_or_init(|| match this.init.take() {
+            Some(f) => f(),
+            None => panic!("MLazy instance has previously been poisoned"),
+        })
+    }
+}
+
+impl<T, F: FnOnce() -> T> Deref for MLazy<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        Self::force(self)
+    }
+}
+
+impl<T: Default> Default for MLazy<T> {
+    fn default() -> Self {
+        Self::new(T::default)
+    }
+}
+
+impl<T: Debug, F> Debug for MLazy<T, F> {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        formatter
+            .debug_tuple("MLazy")
+            .field(&self.cell.get())
+            .finish()
+    }
+}
+
+//}}}
+
+//{{{ Thread-safe ---------------------------------------------------------------------------------
+
+/// A thread-safe cell which can be written to only once. The value is allocated with `malloc()`
+/// when set.
+pub struct SyncMOnceCell<T> {
+    ptr: AtomicPtr<T>,
+    marker: PhantomData<MBox<T>>,
+}
+
+// SAFETY: the cell owns the box, like `MBox<T>`.
+unsafe impl<T: Send> Send for SyncMOnceCell<T> {}
+// SAFETY: the value may be set by one thread and read by others, like `std::sync::OnceLock`.
+unsafe impl<T: Send + Sync> Sync for SyncMOnceCell<T> {}
+
+impl<T> SyncMOnceCell<T> {
+    /// Creates an empty cell.
+    pub const fn new() -> Self {
+        Self {
+            ptr: AtomicPtr::new(null_mut()),
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the value, or `None` if the cell is empty.
+    pub fn get(&self) -> Option<&T> {
+        // SAFETY: a non-null pointer is owned by the cell and never changes until it is dropped.
+        // The `Acquire` load synchronizes with the store publishing it.
+        unsafe { self.ptr.load(Ordering::Acquire).as_ref() }
+    }
+
+    /// Returns the value mutably, or `None` if the cell is empty.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        // SAFETY: the cell is borrowed mutably.
+        unsafe { self.ptr.get_mut().as_mut() }
+    }
+
+    /// Stores `boxed` unless the cell is already set, and returns the value of the cell. On
+    /// failure, `boxed` is returned back as well.
+    fn try_insert(&self, boxed: MBox<T>) -> (&T, Option<MBox<T>>) {
+        let ptr = MBox::into_raw(boxed);
+        match self
+            .ptr
+            .compare_exchange(null_mut(), ptr, Ordering::AcqRel, Ordering::Acquire)
+        {
+            // SAFETY: the cell now owns `ptr`.
+            Ok(_) => (unsafe { &*ptr }, None),
+            // SAFETY: `ptr` was not stored, so it is still owned by us, while `current` is owned
+            // by the cell.
+            Err(current) => unsafe { (&*current, Some(MBox::from_raw(ptr))) },
+        }
+    }
+
+    /// Sets the value of the cell. Returns the value back if the cell is already set.
+    pub fn set(&self, value: T) -> Result<(), T> {
+        if self.get().is_some() {
+            return Err(value);
+        }
+        match self.try_insert(MBox::new(value)) {
+            (_, None) => Ok(()),
+            (_, Some(boxed)) => Err(MBox::into_inner(boxed)),
+        }
+    }
+
+    /// Returns the value, initializing it with `f` if the cell is empty.
+    ///
+    /// If several threads call this at the same time, `f` may run more than once. Only the first
+    /// value to be stored is kept.
+    pub fn get_or_init<F: FnOnce() -> T>(&self, f: F) -> &T {
+        if let Some(value) = self.get() {
+            return value;
+        }
+        self.try_insert(MBox::new(f())).0
+    }
+
+    /// Takes the box out of the cell, leaving it empty.
+    pub fn take(&mut self) -> Option<MBox<T>> {
+        let ptr = std::mem::replace(self.ptr.get_mut(), null_mut());
+        if ptr.is_null() {
+            None
+        } else {
+            // SAFETY: the pointer is removed from the cell, so its ownership is transferred to us.
+            Some(unsafe { MBox::from_raw(ptr) })
+        }
+    }
+
+    /// Consumes the cell and returns the box, if set.
+    pub fn into_inner(mut self) -> Option<MBox<T>> {
+        self.take()
+    }
+}
+
+impl<T> Default for SyncMOnceCell<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for SyncMOnceCell<T> {
+    fn drop(&mut self) {
+        self.take();
+    }
+}
+
+impl<T: Debug> Debug for SyncMOnceCell<T> {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        formatter
+            .debug_tuple("SyncMOnceCell")
+            .field(&self.get())
+            .finish()
+    }
+}
+
+/// A thread-safe value which is initialized on first access, and allocated with `malloc()` at that
+/// time.
+///
+/// Since several threads may initialize the value at the same time, the initializer is an `Fn`
+/// which can be called more than once.
+pub struct SyncMLazy<T, F = fn() -> T> {
+    cell: SyncMOnceCell<T>,
+    init: F,
+}
+
+impl<T, F> SyncMLazy<T, F> {
+    /// Creates a lazy value which is initialized by `f`.
+    pub const fn new(f: F) -> Self {
+        Self {
+            cell: SyncMOnceCell::new(),
+            init: f,
+        }
+    }
+
+    /// Consumes the lazy value and returns the box, if initialized.
+    pub fn into_inner(this: Self) -> Option<MBox<T>> {
+        this.cell.into_inner()
+    }
+}
+
+impl<T, F: Fn() -> T> SyncMLazy<T, F> {
+    /// Initializes the value if needed, and returns it.
+    pub fn force(this: &Self) -> &T {
+        this.cell.get_or_init(&this.init)
+    }
+}
+
+impl<T, F: Fn() -> T> Deref for SyncMLazy<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        Self::force(self)
+    }
+}
+
+impl<T: Default> Default for SyncMLazy<T> {
+    fn default() -> Self {
+        Self::new(T::default)
+    }
+}
+
+impl<T: Debug, F> Debug for SyncMLazy<T, F> {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        formatter
+            .debug_tuple("SyncMLazy")
+            .field(&self.cell.get())
+            .finish()
+    }
+}
+
+//}}}
+
+#[cfg(not(windows))]
+#[test]
+fn test_once_cell() {
+    use crate::test_util::DropCounter;
+
+    let counter = DropCounter::default();
+    let cell = MOnceCell::new();
+    assert!(cell.get().is_none());
+    assert!(cell.set(counter.clone()).is_ok());
+    assert!(cell.set(counter.clone()).is_err());
+    counter.assert_eq(1);
+    let value = cell.get_or_init(|| unreachable!());
+    assert_eq!(value, &counter);
+    drop(cell);
+    counter.assert_eq(2);
+
+    let mut cell = MOnceCell::default();
+    assert_eq!(*cell.get_or_init(|| 5u8), 5);
+    *cell.get_mut().unwrap() = 6;
+    assert_eq!(cell.take().map(|b| *b), Some(6));
+    assert!(cell.into_inner().is_none());
+
+    let cell = SyncMOnceCell::new();
+    assert!(cell.get().is_none());
+    assert!(cell.set(counter.clone()).is_ok());
+    assert!(cell.set(counter.clone()).is_err());
+    counter.assert_eq(3);
+    assert_eq!(cell.get_or_init(|| unreachable!()), &counter);
+    let boxed = cell.into_inner().unwrap();
+    counter.assert_eq(3);
+    drop(boxed);
+    counter.assert_eq(4);
+
+    let cell = SyncMOnceCell::<DropCounter>::default();
+    cell.get_or_init(|| counter.clone());
+    drop(cell);
+    counter.assert_eq(5);
+}
+
+#[test]
+#[should_panic(expected = "reentrant init")]
+fn test_once_cell_reentrant() {
+    let cell = MOnceCell::new();
+    cell.get_or_init(|| {
+        cell.set(1u8).unwrap();
+        2
+    });
+}
+
+#[test]
+fn test_lazy() {
+    let runs = Cell::new(0);
+    let lazy = MLazy::new(|| {
+        runs.set(runs.get() + 1);
+        7u8
+    });
+    assert_eq!(runs.get(), 0);
+    assert_eq!(*lazy, 7);
+    assert_eq!(*lazy, 7);
+    assert_eq!(runs.get(), 1);
+    assert_eq!(MLazy::into_inner(lazy).map(|b| *b), Some(7));
+
+    let lazy = SyncMLazy::new(|| 8u8);
+    assert_eq!(*SyncMLazy::force(&lazy), 8);
+    #[cfg(feature = "std")]
+    assert_eq!(format!("{:?}", lazy), "SyncMLazy(Some(8))");
+    assert_eq!(*MLazy::<u8>::default(), 0);
+}
+
+#[cfg(all(feature = "std", not(windows)))]
+#[test]
+fn test_sync_lazy_threads() {
+    use std::sync::atomic::AtomicUsize;
+    use std::thread;
+
+    static RUNS: AtomicUsize = AtomicUsize::new(0);
+    static LAZY: SyncMLazy<usize> = SyncMLazy::new(|| RUNS.fetch_add(1, Ordering::Relaxed) + 100);
+
+    let threads = (0..4).map(|_| thread::spawn(|| *LAZY)).collect::<Vec<_>>();
+    let values = threads
+        .into_iter()
+        .map(|t| t.join().unwrap())
+        .collect::<Vec<_>>();
+    assert!(values.iter().all(|&v| v == values[0]));
+    assert_eq!(*LAZY, values[0]);
+    assert!(RUNS.load(Ordering::Relaxed) >= 1);
+}
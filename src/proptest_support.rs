@@ -0,0 +1,79 @@
+//! `proptest` support.
+
+use proptest::arbitrary::{any_with, Arbitrary, Mapped};
+use proptest::strategy::Strategy;
+
+use std::fmt::Debug;
+
+use crate::mbox::MBox;
+use crate::sentinel::MString;
+
+impl<T: Arbitrary> Arbitrary for MBox<T> {
+    type Parameters = T::Parameters;
+    type Strategy = Mapped<T, Self>;
+
+    fn arbitrary_with(args: Self::Parameters) -> Self::Strategy {
+        any_with::<T>(args).prop_map(MBox::new)
+    }
+}
+
+impl<T: Arbitrary + Debug> Arbitrary for MBox<[T]> {
+    type Parameters = <Vec<T> as Arbitrary>::Parameters;
+    type Strategy = Mapped<Vec<T>, Self>;
+
+    fn arbitrary_with(args: Self::Parameters) -> Self::Strategy {
+        any_with::<Vec<T>>(args).prop_map(|v| v.into_iter().collect())
+    }
+}
+
+impl Arbitrary for MBox<str> {
+    type Parameters = <String as Arbitrary>::Parameters;
+    type Strategy = Mapped<String, Self>;
+
+    fn arbitrary_with(args: Self::Parameters) -> Self::Strategy {
+        any_with::<String>(args).prop_map(|s| MBox::from(&*s))
+    }
+}
+
+impl Arbitrary for MString {
+    type Parameters = <String as Arbitrary>::Parameters;
+    type Strategy = Mapped<String, Self>;
+
+    /// Generates strings without interior NULs, so the result is always a valid C string.
+    fn arbitrary_with(args: Self::Parameters) -> Self::Strategy {
+        any_with::<String>(args).prop_map(|s| s.chars().filter(|&c| c != '\0').collect())
+    }
+}
+
+#[cfg(test)]
+use proptest::{arbitrary::any, test_runner::TestRunner};
+
+#[test]
+fn test_proptest() {
+    let mut runner = TestRunner::deterministic();
+    runner
+        .run(&any::<MBox<u8>>(), |value| {
+            assert_eq!(MBox::into_inner(value.clone()), *value);
+            Ok(())
+        })
+        .unwrap();
+    runner
+        .run(&any::<MBox<[u8]>>(), |value| {
+            assert_eq!(value.iter().copied().collect::<MBox<[u8]>>(), value);
+            Ok(())
+        })
+        .unwrap();
+    runner
+        .run(&any::<MBox<str>>(), |value| {
+            assert_eq!(MBox::<str>::from(&*value), value);
+            Ok(())
+        })
+        .unwrap();
+    runner
+        .run(&any::<MString>(), |value| {
+            assert!(!value.contains('\0'));
+            assert_eq!(value.as_c_str().to_bytes(), value.as_bytes());
+            Ok(())
+        })
+        .unwrap();
+}
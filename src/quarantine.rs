@@ -0,0 +1,127 @@
+//! Quarantine delaying the release of freed blocks.
+//!
+//! When the `quarantine` feature is enabled, blocks released by `gen_free()` are filled with
+//! `POISON_BYTE` and kept in a global FIFO instead of being freed immediately. Only when more than
+//! `QUARANTINE_LEN` blocks are held, the oldest one is verified and actually freed.
+//!
+//! Since quarantined memory is not reused by the allocator, a dangling pointer kept by C code
+//! (perhaps on another thread) keeps reading poison rather than some unrelated live object, and a
+//! write through it is detected when the block leaves the quarantine.
+
+use libc::c_void;
+
+use std::collections::VecDeque;
+use std::slice::from_raw_parts;
+use std::sync::Mutex;
+
+use crate::internal::{usable_size, POISON_BYTE};
+
+/// The maximum number of blocks held in the quarantine.
+pub const QUARANTINE_LEN: usize = 1024;
+
+/// The quarantined blocks, oldest first.
+static BLOCKS: Mutex<Option<VecDeque<usize>>> = Mutex::new(None);
+
+/// Puts a poisoned block into the quarantine, returning the block evicted in exchange, if any.
+///
+/// # Safety
+///
+/// The block must be still allocated, filled with `POISON_BYTE`, and not used afterwards.
+///
+/// # Panics
+///
+/// Panics if the evicted block has been written after it was freed.
+pub unsafe fn push(ptr: *mut c_void) -> Option<*mut c_void> {
+    let evicted = {
+        // the queue is still consistent even if another thread panicked while holding the lock.
+        let mut blocks = BLOCKS.lock().unwrap_or_else(|e| e.into_inner());
+        let blocks = blocks.get_or_insert_with(VecDeque::new);
+        blocks.push_back(ptr as usize);
+        if blocks.len() > QUARANTINE_LEN {
+            blocks.pop_front()
+        } else {
+            None
+        }
+    };
+    evicted.map(|block| {
+        let block = block as *mut c_void;
+        check_poison(block);
+        block
+    })
+}
+
+/// Verifies that a quarantined block is still entirely filled with `POISON_BYTE`.
+///
+/// # Safety
+///
+/// The block must be still allocated.
+///
+/// # Panics
+///
+/// Panics if the block has been written after it was freed.
+unsafe fn check_poison(ptr: *mut c_void) {
+    let block = from_raw_parts(ptr as *const u8, usable_size(ptr));
+    if let Some(offset) = block.iter().position(|&b| b != POISON_BYTE) {
+        panic!(
+            "use after free detected: the block at {:p} was written at offset {} after it was freed",
+            ptr, offset
+        );
+    }
+}
+
+/// Returns whether the block is held in the quarantine, and still entirely filled with
+/// `POISON_BYTE`.
+///
+/// # Safety
+///
+/// The block must be obtained from the allocator.
+#[cfg(test)]
+pub unsafe fn holds_poisoned(ptr: *mut c_void) -> bool {
+    // the lock keeps the block from being evicted and freed while it is read.
+    let blocks = BLOCKS.lock().unwrap_or_else(|e| e.into_inner());
+    blocks
+        .as_ref()
+        .map_or(false, |blocks| blocks.contains(&(ptr as usize)))
+        && from_raw_parts(ptr as *const u8, usable_size(ptr))
+            .iter()
+            .all(|&b| b == POISON_BYTE)
+}
+
+#[test]
+fn test_quarantine() {
+    use crate::mbox::MBox;
+
+    // overflows the quarantine, so some blocks are evicted and freed.
+    for i in 0..QUARANTINE_LEN * 2 {
+        drop(MBox::from_slice(&[i; 4]));
+    }
+}
+
+#[cfg(all(
+    not(miri),
+    not(windows),
+    not(feature = "freertos"),
+    any(all(target_os = "linux", target_env = "gnu"), target_os = "macos")
+))]
+#[test]
+fn test_check_poison() {
+    use crate::internal::fill_volatile;
+    use std::panic::catch_unwind;
+
+    // SAFETY: the block is allocated by `malloc()`, and freed at the end.
+    unsafe {
+        let ptr = libc::malloc(100) as *mut u8;
+        fill_volatile(ptr, POISON_BYTE, usable_size(ptr as *mut c_void));
+        check_poison(ptr as *mut c_void);
+
+        // simulates a write through a dangling pointer.
+        *ptr.add(42) = 0;
+        let block = ptr as usize;
+        let error = catch_unwind(|| check_poison(block as *mut c_void)).unwrap_err();
+        let message = error.downcast_ref::<String>().unwrap();
+        assert!(message.starts_with("use after free detected: the block at "));
+        assert!(message.ends_with(" was written at offset 42 after it was freed"));
+
+        libc::free(ptr as *mut c_void);
+    }
+}
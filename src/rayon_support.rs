@@ -0,0 +1,207 @@
+//! rayon support.
+//!
+//! Slice methods such as `par_chunks()` and `par_sort()` are available through `Deref`. This module
+//! adds the by-value parallel iterator, which moves the items out of the `malloc`-ed memory and
+//! frees it afterwards.
+
+use rayon::iter::plumbing::{bridge, Consumer, Producer, ProducerCallback, UnindexedConsumer};
+use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+use rayon::slice::{Iter as ParIter, IterMut as ParIterMut};
+
+use std::mem::take;
+use std::ptr::{drop_in_place, read};
+use std::slice::{from_raw_parts_mut, IterMut};
+
+use crate::mbox::MBox;
+
+/// The parallel iterator returned from `MBox<[T]>::into_par_iter()`.
+#[derive(Debug)]
+pub struct MSliceIntoParIter<T: Send> {
+    boxed: MBox<[T]>,
+}
+
+impl<T: Send> IntoParallelIterator for MBox<[T]> {
+    type Item = T;
+    type Iter = MSliceIntoParIter<T>;
+
+    fn into_par_iter(self) -> Self::Iter {
+        MSliceIntoParIter { boxed: self }
+    }
+}
+
+impl<'a, T: Sync> IntoParallelIterator for &'a MBox<[T]> {
+    type Item = &'a T;
+    type Iter = ParIter<'a, T>;
+
+    fn into_par_iter(self) -> Self::Iter {
+        (**self).into_par_iter()
+    }
+}
+
+impl<'a, T: Send> IntoParallelIterator for &'a mut MBox<[T]> {
+    type Item = &'a mut T;
+    type Iter = ParIterMut<'a, T>;
+
+    fn into_par_iter(self) -> Self::Iter {
+        (**self).into_par_iter()
+    }
+}
+
+impl<T: Send> ParallelIterator for MSliceIntoParIter<T> {
+    type Item = T;
+
+    fn drive_unindexed<C: UnindexedConsumer<T>>(self, consumer: C) -> C::Result {
+        bridge(self, consumer)
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.boxed.len())
+    }
+}
+
+impl<T: Send> IndexedParallelIterator for MSliceIntoParIter<T> {
+    fn drive<C: Consumer<T>>(self, consumer: C) -> C::Result {
+        bridge(self, consumer)
+    }
+
+    fn len(&self) -> usize {
+        self.boxed.len()
+    }
+
+    fn with_producer<CB: ProducerCallback<T>>(mut self, callback: CB) -> CB::Output {
+        let len = self.boxed.len();
+        // SAFETY: the ownership of the items is transferred to the producer, so the box's length is
+        // set to 0 first. The box then only frees the memory when it is dropped at the end of this
+        // function, after all producers are done (or have dropped the remaining items when
+        // unwinding).
+        unsafe {
+            self.boxed.set_len(0);
+            let ptr = MBox::as_mut_ptr(&mut self.boxed) as *mut T;
+            callback.callback(DrainProducer {
+                slice: from_raw_parts_mut(ptr, len),
+            })
+        }
+    }
+}
+
+/// A producer owning the items in `slice`, but not the memory.
+struct DrainProducer<'a, T> {
+    slice: &'a mut [T],
+}
+
+impl<'a, T: Send> Producer for DrainProducer<'a, T> {
+    type Item = T;
+    type IntoIter = SliceDrain<'a, T>;
+
+    fn into_iter(mut self) -> SliceDrain<'a, T> {
+        SliceDrain {
+            iter: take(&mut self.slice).iter_mut(),
+        }
+    }
+
+    fn split_at(mut self, index: usize) -> (Self, Self) {
+        let (left, right) = take(&mut self.slice).split_at_mut(index);
+        (
+            DrainProducer { slice: left },
+            DrainProducer { slice: right },
+        )
+    }
+}
+
+impl<T> Drop for DrainProducer<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: the items in `slice` are owned by the producer and are not yet moved out.
+        unsafe { drop_in_place(take(&mut self.slice) as *mut [T]) }
+    }
+}
+
+/// An iterator moving the items out of the slice, dropping the remaining items on drop.
+struct SliceDrain<'a, T> {
+    iter: IterMut<'a, T>,
+}
+
+impl<T> Iterator for SliceDrain<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        // SAFETY: each item is visited once by the iterator, so it is moved out exactly once.
+        self.iter.next().map(|item| unsafe { read(item) })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<T> DoubleEndedIterator for SliceDrain<'_, T> {
+    fn next_back(&mut self) -> Option<T> {
+        // SAFETY: same as `next()`.
+        self.iter.next_back().map(|item| unsafe { read(item) })
+    }
+}
+
+impl<T> ExactSizeIterator for SliceDrain<'_, T> {}
+
+impl<T> Drop for SliceDrain<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: the items not yet visited are still owned by the iterator.
+        unsafe { drop_in_place(take(&mut self.iter).into_slice() as *mut [T]) }
+    }
+}
+
+#[cfg(test)]
+use rayon::{iter::IntoParallelRefIterator, slice::ParallelSlice};
+
+#[cfg(test)]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(test)]
+use std::sync::Arc;
+
+/// A thread-safe version of `DropCounter`.
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct SyncDropCounter(Arc<AtomicUsize>);
+
+#[cfg(test)]
+impl Drop for SyncDropCounter {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+impl SyncDropCounter {
+    fn assert_eq(&self, value: usize) {
+        assert_eq!(self.0.load(Ordering::Relaxed), value);
+    }
+}
+
+#[test]
+fn test_rayon_par_iter() {
+    let slice: MBox<[u32]> = (0..1000).collect();
+    assert_eq!(slice.par_iter().sum::<u32>(), 499500);
+    assert_eq!(slice.par_chunks(100).count(), 10);
+
+    let mut slice = slice;
+    (&mut slice).into_par_iter().for_each(|x| *x *= 2);
+    assert_eq!(slice[999], 1998);
+
+    let doubled: Vec<u32> = slice.into_par_iter().map(|x| x / 2).collect();
+    assert_eq!(doubled, (0..1000).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_rayon_into_par_iter_drops() {
+    let counter = SyncDropCounter::default();
+    let slice = MBox::from_elem(counter.clone(), 100);
+    slice.into_par_iter().for_each(drop);
+    counter.assert_eq(100);
+
+    let slice = MBox::from_elem(counter.clone(), 100);
+    assert_eq!(slice.into_par_iter().take(10).count(), 10);
+    counter.assert_eq(200);
+
+    let slice = MBox::from_elem(counter.clone(), 100);
+    assert!(slice.into_par_iter().find_any(|_| true).is_some());
+    counter.assert_eq(300);
+}
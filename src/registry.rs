@@ -0,0 +1,124 @@
+//! Registry of live allocations, detecting double ownership and invalid frees.
+//!
+//! When the `track_ownership` feature is enabled, every block allocated by this crate or adopted
+//! through `MBox::from_raw()` is recorded in a global table, together with whether it is currently
+//! owned by an `MBox`:
+//!
+//! * Allocating a block records it as released, since it is not owned by any `MBox` yet.
+//! * `from_raw()` marks the block as owned, and panics if it is already owned by another `MBox`.
+//!   Blocks allocated by C code are added to the table at this point.
+//! * `into_raw()` and `leak()` mark the block as released.
+//! * Freeing a block removes it from the table, and panics if it is not in the table, i.e. it has
+//!   already been freed, or it has never been allocated nor adopted by this crate.
+//!
+//! Blocks freed by C code after `into_raw()` stay in the table as released, which is harmless
+//! since `from_raw()` accepts released blocks.
+
+use libc::c_void;
+
+use std::collections::hash_map::{Entry, HashMap};
+use std::sync::Mutex;
+
+/// Whether a live block is owned by an `MBox`, keyed by its address.
+static BLOCKS: Mutex<Option<HashMap<usize, bool>>> = Mutex::new(None);
+
+fn with_blocks<R>(f: impl FnOnce(&mut HashMap<usize, bool>) -> R) -> R {
+    // the table is still consistent even if another thread panicked while holding the lock.
+    let mut blocks = BLOCKS.lock().unwrap_or_else(|e| e.into_inner());
+    f(blocks.get_or_insert_with(HashMap::new))
+}
+
+/// Records a block which has just been allocated.
+pub fn allocated(ptr: *mut c_void) {
+    with_blocks(|blocks| blocks.insert(ptr as usize, false));
+}
+
+/// Records that the block is now owned by an `MBox`.
+///
+/// # Panics
+///
+/// Panics if the block is already owned by another `MBox`.
+pub fn acquire(ptr: *mut c_void) {
+    let already_owned = with_blocks(|blocks| match blocks.entry(ptr as usize) {
+        Entry::Occupied(mut entry) => entry.insert(true),
+        Entry::Vacant(entry) => {
+            entry.insert(true);
+            false
+        }
+    });
+    if already_owned {
+        panic!(
+            "double ownership detected: {:p} is already owned by an MBox",
+            ptr
+        );
+    }
+}
+
+/// Records that the block is no longer owned by an `MBox`.
+pub fn release(ptr: *mut c_void) {
+    with_blocks(|blocks| {
+        if let Some(owned) = blocks.get_mut(&(ptr as usize)) {
+            *owned = false;
+        }
+    });
+}
+
+/// Removes a block about to be freed.
+///
+/// # Panics
+///
+/// Panics if the block is not live.
+pub fn freed(ptr: *mut c_void) {
+    if with_blocks(|blocks| blocks.remove(&(ptr as usize))).is_none() {
+        panic!(
+            "invalid free detected: {:p} is not allocated by mbox, or has already been freed",
+            ptr
+        );
+    }
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_double_ownership() {
+    use crate::mbox::MBox;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let raw = MBox::into_raw(MBox::new(1u32));
+    // SAFETY: `raw` is released by `into_raw()`.
+    let first = unsafe { MBox::from_raw(raw) };
+    // SAFETY: not really safe, but the second box is never constructed.
+    let error = catch_unwind(AssertUnwindSafe(|| unsafe { MBox::from_raw(raw) })).unwrap_err();
+    let message = error.downcast_ref::<String>().unwrap();
+    assert!(message.starts_with("double ownership detected: "));
+    drop(first);
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_invalid_free() {
+    use crate::internal::gen_free;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::ptr::NonNull;
+
+    let mut value = 0u32;
+    let ptr = NonNull::from(&mut value);
+    // SAFETY: not really safe, but the registry panics before `free()` is called.
+    let error = catch_unwind(AssertUnwindSafe(|| unsafe { gen_free(ptr) })).unwrap_err();
+    let message = error.downcast_ref::<String>().unwrap();
+    assert!(message.starts_with("invalid free detected: "));
+}
+
+#[test]
+fn test_foreign_pointer() {
+    use crate::mbox::MBox;
+
+    // SAFETY: the pointer is allocated by C's `malloc()`.
+    unsafe {
+        let raw = libc::malloc(4) as *mut u32;
+        raw.write(7);
+        let foreign = MBox::from_raw(raw);
+        assert_eq!(*foreign, 7);
+        let raw = MBox::into_raw(foreign);
+        drop(MBox::from_raw(raw));
+    }
+}
@@ -0,0 +1,149 @@
+//! rkyv support.
+//!
+//! `MBox<T>` is archived in the same format as `Box<T>`, and `MString` in the same format as
+//! `String`. Deserialization writes directly into `malloc`-ed memory.
+
+use rkyv::boxed::{ArchivedBox, BoxResolver};
+use rkyv::rancor::{Fallible, Source};
+use rkyv::string::{ArchivedString, StringResolver};
+use rkyv::{Archive, ArchiveUnsized, Deserialize, Place, Serialize, SerializeUnsized};
+
+use crate::free::Free;
+use crate::mbox::{MBox, MSliceBuilder};
+use crate::sentinel::MString;
+
+impl<T: ArchiveUnsized + Free + ?Sized> Archive for MBox<T> {
+    type Archived = ArchivedBox<T::Archived>;
+    type Resolver = BoxResolver;
+
+    fn resolve(&self, resolver: Self::Resolver, out: Place<Self::Archived>) {
+        ArchivedBox::resolve_from_ref(&**self, resolver, out);
+    }
+}
+
+impl<T, S> Serialize<S> for MBox<T>
+where
+    T: SerializeUnsized<S> + Free + ?Sized,
+    S: Fallible + ?Sized,
+{
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        ArchivedBox::serialize_from_ref(&**self, serializer)
+    }
+}
+
+impl<T, D> Deserialize<MBox<T>, D> for ArchivedBox<T::Archived>
+where
+    T: Archive,
+    T::Archived: Deserialize<T, D>,
+    D: Fallible + ?Sized,
+{
+    fn deserialize(&self, deserializer: &mut D) -> Result<MBox<T>, D::Error> {
+        self.get().deserialize(deserializer).map(MBox::new)
+    }
+}
+
+impl<T, D> Deserialize<MBox<[T]>, D> for ArchivedBox<[T::Archived]>
+where
+    T: Archive,
+    T::Archived: Deserialize<T, D>,
+    D: Fallible + ?Sized,
+{
+    fn deserialize(&self, deserializer: &mut D) -> Result<MBox<[T]>, D::Error> {
+        let archived = self.get();
+        let mut builder = MSliceBuilder::with_capacity(archived.len());
+        for item in archived {
+            builder.push(item.deserialize(deserializer)?);
+        }
+        Ok(builder.into_mboxed_slice())
+    }
+}
+
+impl<D: Fallible + ?Sized> Deserialize<MBox<str>, D> for ArchivedBox<str> {
+    fn deserialize(&self, _: &mut D) -> Result<MBox<str>, D::Error> {
+        Ok(MBox::from(self.get()))
+    }
+}
+
+impl Archive for MString {
+    type Archived = ArchivedString;
+    type Resolver = StringResolver;
+
+    fn resolve(&self, resolver: Self::Resolver, out: Place<Self::Archived>) {
+        ArchivedString::resolve_from_str(self, resolver, out);
+    }
+}
+
+impl<S> Serialize<S> for MString
+where
+    S: Fallible + ?Sized,
+    S::Error: Source,
+    str: SerializeUnsized<S>,
+{
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        ArchivedString::serialize_from_str(self, serializer)
+    }
+}
+
+impl<D: Fallible + ?Sized> Deserialize<MString, D> for ArchivedString {
+    fn deserialize(&self, _: &mut D) -> Result<MString, D::Error> {
+        Ok(MString::from(self.as_str()))
+    }
+}
+
+#[cfg(test)]
+use rkyv::{rancor::Error, Archived};
+
+#[cfg(test)]
+fn roundtrip<T>(value: &T) -> T
+where
+    T: for<'a> Serialize<
+        rkyv::api::high::HighSerializer<
+            rkyv::util::AlignedVec,
+            rkyv::ser::allocator::ArenaHandle<'a>,
+            Error,
+        >,
+    >,
+    Archived<T>: Deserialize<T, rkyv::api::high::HighDeserializer<Error>>,
+{
+    let bytes = rkyv::to_bytes::<Error>(value).unwrap();
+    // SAFETY: the bytes are produced by `to_bytes()` above.
+    let archived = unsafe { rkyv::access_unchecked::<Archived<T>>(&bytes) };
+    rkyv::deserialize::<T, Error>(archived).unwrap()
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_rkyv_single_object() {
+    let value = MBox::new(0x1234_5678u32);
+    assert_eq!(roundtrip(&value), value);
+    let value = MBox::new(MBox::new(7u8));
+    assert_eq!(roundtrip(&value), value);
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_rkyv_slice() {
+    let value = MBox::from_slice(&[1u64, 2, 3, 4, 5]);
+    assert_eq!(roundtrip(&value), value);
+    let value = MBox::<[u64]>::default();
+    assert_eq!(roundtrip(&value), value);
+}
+
+#[test]
+fn test_rkyv_str() {
+    let value = MBox::<str>::from("abcdef一二三");
+    assert_eq!(roundtrip(&value), value);
+
+    let value = MString::from("a somewhat long string which cannot be inlined");
+    let bytes = rkyv::to_bytes::<Error>(&value).unwrap();
+    // SAFETY: the bytes are produced by `to_bytes()` above.
+    let archived = unsafe { rkyv::access_unchecked::<ArchivedString>(&bytes) };
+    assert_eq!(archived.as_str(), &*value);
+    let deserialized = rkyv::deserialize::<MString, Error>(archived).unwrap();
+    assert_eq!(deserialized, value);
+    assert_eq!(
+        deserialized.as_bytes_with_sentinel(),
+        value.as_bytes_with_sentinel()
+    );
+    assert_eq!(roundtrip(&MString::from("short")), "short");
+}
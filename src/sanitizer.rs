@@ -0,0 +1,95 @@
+//! AddressSanitizer and Valgrind annotations.
+//!
+//! The spare capacity of an `MSliceBuilder` is allocated, but must not be accessed before an item
+//! is pushed into it. Neither tool can know this by itself, since the whole block comes from a
+//! single `malloc()`. With the `asan` or `valgrind` feature, the spare capacity is marked as
+//! inaccessible, so a stray access is reported precisely.
+//!
+//! The `asan` feature must only be enabled when building with `-Zsanitizer=address`, which provides
+//! the `__asan_*_memory_region` functions. The `valgrind` feature issues client requests, which are
+//! no-ops when the program is not running under Valgrind.
+
+use libc::c_void;
+
+#[cfg(feature = "asan")]
+extern "C" {
+    fn __asan_poison_memory_region(addr: *const c_void, size: usize);
+    fn __asan_unpoison_memory_region(addr: *const c_void, size: usize);
+}
+
+#[cfg(all(feature = "asan", test))]
+extern "C" {
+    pub fn __asan_address_is_poisoned(addr: *const c_void) -> i32;
+}
+
+/// The base of Memcheck's client requests, `VG_USERREQ_TOOL_BASE('M', 'C')`.
+#[cfg(feature = "valgrind")]
+const MEMCHECK_BASE: usize = (b'M' as usize) << 24 | (b'C' as usize) << 16;
+#[cfg(feature = "valgrind")]
+const VG_USERREQ_MAKE_MEM_NOACCESS: usize = MEMCHECK_BASE;
+#[cfg(feature = "valgrind")]
+const VG_USERREQ_MAKE_MEM_UNDEFINED: usize = MEMCHECK_BASE + 1;
+
+/// Issues a Valgrind client request, which is the magic instruction sequence from `<valgrind.h>`.
+/// Outside of Valgrind, the sequence does nothing.
+#[cfg(all(feature = "valgrind", target_arch = "x86_64"))]
+unsafe fn client_request(request: usize, arg1: usize, arg2: usize) {
+    let args = [request, arg1, arg2, 0, 0, 0];
+    core::arch::asm!(
+        "rol rdi, 3",
+        "rol rdi, 13",
+        "rol rdi, 61",
+        "rol rdi, 51",
+        "xchg rbx, rbx",
+        in("rax") args.as_ptr(),
+        inout("rdx") 0usize => _,
+        options(nostack),
+    );
+}
+
+#[cfg(all(feature = "valgrind", target_arch = "aarch64"))]
+unsafe fn client_request(request: usize, arg1: usize, arg2: usize) {
+    let args = [request, arg1, arg2, 0, 0, 0];
+    core::arch::asm!(
+        "ror x12, x12, #3",
+        "ror x12, x12, #13",
+        "ror x12, x12, #51",
+        "ror x12, x12, #61",
+        "orr x10, x10, x10",
+        in("x4") args.as_ptr(),
+        inout("x3") 0usize => _,
+        options(nostack),
+    );
+}
+
+#[cfg(all(
+    feature = "valgrind",
+    not(any(target_arch = "x86_64", target_arch = "aarch64"))
+))]
+unsafe fn client_request(_: usize, _: usize, _: usize) {}
+
+/// Marks `len` bytes as inaccessible.
+///
+/// # Safety
+///
+/// The bytes must be allocated, and must not be accessed until `unpoison()` is called on them.
+#[allow(unused_variables)]
+pub unsafe fn poison(ptr: *const c_void, len: usize) {
+    #[cfg(feature = "asan")]
+    __asan_poison_memory_region(ptr, len);
+    #[cfg(feature = "valgrind")]
+    client_request(VG_USERREQ_MAKE_MEM_NOACCESS, ptr as usize, len);
+}
+
+/// Marks `len` bytes as accessible again, with undefined content.
+///
+/// # Safety
+///
+/// The bytes must be allocated.
+#[allow(unused_variables)]
+pub unsafe fn unpoison(ptr: *const c_void, len: usize) {
+    #[cfg(feature = "asan")]
+    __asan_unpoison_memory_region(ptr, len);
+    #[cfg(feature = "valgrind")]
+    client_request(VG_USERREQ_MAKE_MEM_UNDEFINED, ptr as usize, len);
+}
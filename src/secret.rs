@@ -0,0 +1,211 @@
+//! Secrets locked in memory.
+//!
+//! `SecretMBox<T>` and `SecretMString` keep their content in dedicated pages which are locked with
+//! `mlock()`, so they are never written to swap. On drop, the content is zeroed before the pages
+//! are unlocked and unmapped.
+//!
+//! The pages are obtained from `mmap()` rather than `malloc()`, because locking is done per page,
+//! and unlocking a page shared with other allocations would unlock them as well. Consequently, the
+//! memory must not be passed to C's `free()`.
+//!
+//! Moving a value into a `SecretMBox` may leave copies of it on the stack. Prefer filling the
+//! secret in place through `DerefMut`.
+
+use libc::c_void;
+
+use std::ffi::CStr;
+use std::fmt::{Debug, Formatter, Result as FormatResult};
+use std::io;
+use std::marker::PhantomData;
+use std::mem::{align_of, size_of};
+use std::ops::{Deref, DerefMut};
+use std::ptr::{copy_nonoverlapping, drop_in_place, write};
+use std::str::from_utf8_unchecked;
+
+use crate::internal::fill_volatile;
+use crate::mmap::{page_size, MMap};
+
+/// Maps `len` bytes of anonymous memory, and locks them.
+fn locked_mmap(len: usize) -> io::Result<MMap> {
+    let map = MMap::try_new(len).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    // SAFETY: the pages are owned by `map`.
+    if unsafe { libc::mlock(map.as_ptr() as *const c_void, map.mapped_len()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(map)
+}
+
+/// Zeroes the content of a locked mapping, and unlocks it. The mapping itself is unmapped when it
+/// is dropped afterwards.
+fn wipe(map: &mut MMap) {
+    // SAFETY: the pages are owned by `map`.
+    unsafe {
+        fill_volatile(map.as_mut_ptr(), 0, map.len());
+        libc::munlock(map.as_ptr() as *const c_void, map.mapped_len());
+    }
+}
+
+/// A single object stored in locked memory, and zeroed on drop.
+pub struct SecretMBox<T> {
+    map: MMap,
+    marker: PhantomData<T>,
+}
+
+impl<T> SecretMBox<T> {
+    /// Moves `value` into newly locked memory.
+    ///
+    /// Returns an error if the memory cannot be mapped or locked, e.g. when exceeding
+    /// `RLIMIT_MEMLOCK`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the alignment of `T` exceeds the page size, since the mapping is only
+    /// page-aligned.
+    pub fn new(value: T) -> io::Result<Self> {
+        assert!(
+            align_of::<T>() <= page_size(),
+            "alignment exceeds the page size"
+        );
+        let mut map = locked_mmap(size_of::<T>())?;
+        // SAFETY: the mapping is page-aligned, and large enough to store a `T`.
+        unsafe { write(map.as_mut_ptr() as *mut T, value) };
+        Ok(Self {
+            map,
+            marker: PhantomData,
+        })
+    }
+}
+
+impl<T> Drop for SecretMBox<T> {
+    fn drop(&mut self) {
+        // SAFETY: the mapping stores an initialized `T`, which is never used again.
+        unsafe { drop_in_place(self.map.as_mut_ptr() as *mut T) };
+        wipe(&mut self.map);
+    }
+}
+
+impl<T> Deref for SecretMBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the mapping stores an initialized `T`.
+        unsafe { &*(self.map.as_ptr() as *const T) }
+    }
+}
+
+impl<T> DerefMut for SecretMBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the mapping stores an initialized `T`.
+        unsafe { &mut *(self.map.as_mut_ptr() as *mut T) }
+    }
+}
+
+/// The content is never printed.
+impl<T> Debug for SecretMBox<T> {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        formatter.write_str("SecretMBox(..)")
+    }
+}
+
+/// A NUL-terminated string stored in locked memory, and zeroed on drop.
+pub struct SecretMString {
+    map: MMap,
+}
+
+impl SecretMString {
+    /// Copies `value` into newly locked memory, followed by a NUL.
+    ///
+    /// Returns an error if the memory cannot be mapped or locked, e.g. when exceeding
+    /// `RLIMIT_MEMLOCK`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` contains an interior NUL, which would truncate the C string.
+    pub fn new(value: &str) -> io::Result<Self> {
+        assert!(!value.contains('\0'), "secret contains interior NUL");
+        let len = value.len();
+        let mut map = locked_mmap(len.checked_add(1).expect("memory overflow"))?;
+        // SAFETY: the mapping has `len + 1` zero-initialized bytes.
+        unsafe { copy_nonoverlapping(value.as_ptr(), map.as_mut_ptr(), len) };
+        Ok(Self { map })
+    }
+
+    /// Obtains the string as a C string.
+    pub fn as_c_str(&self) -> &CStr {
+        // SAFETY: the content is NUL-terminated without interior NULs.
+        unsafe { CStr::from_bytes_with_nul_unchecked(&self.map) }
+    }
+}
+
+impl Drop for SecretMString {
+    fn drop(&mut self) {
+        wipe(&mut self.map);
+    }
+}
+
+impl Deref for SecretMString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        // SAFETY: the content is copied from a `str`.
+        unsafe { from_utf8_unchecked(&self.map[..self.map.len() - 1]) }
+    }
+}
+
+/// The content is never printed.
+impl Debug for SecretMString {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        formatter.write_str("SecretMString(..)")
+    }
+}
+
+#[cfg(not(miri))]
+#[test]
+fn test_secret_mbox() {
+    use crate::test_util::DropCounter;
+
+    let mut secret = SecretMBox::new([0u8; 32]).unwrap();
+    secret[..6].copy_from_slice(b"secret");
+    assert_eq!(&secret[..6], b"secret");
+    assert_eq!(secret.map.as_ptr() as usize % page_size(), 0);
+    assert_eq!(format!("{:?}", secret), "SecretMBox(..)");
+
+    let counter = DropCounter::default();
+    let secret = SecretMBox::new(counter.clone()).unwrap();
+    counter.assert_eq(0);
+    drop(secret);
+    counter.assert_eq(1);
+
+    let secret = SecretMBox::new(()).unwrap();
+    assert_eq!(*secret, ());
+}
+
+#[cfg(not(miri))]
+#[test]
+#[should_panic(expected = "alignment exceeds the page size")]
+fn test_secret_mbox_over_aligned() {
+    #[repr(align(65536))]
+    struct OverAligned;
+
+    let _ = SecretMBox::new(OverAligned);
+}
+
+#[cfg(not(miri))]
+#[test]
+fn test_secret_mstring() {
+    let secret = SecretMString::new("hunter2").unwrap();
+    assert_eq!(&*secret, "hunter2");
+    assert_eq!(secret.as_c_str().to_bytes_with_nul(), b"hunter2\0");
+    assert_eq!(format!("{:?}", secret), "SecretMString(..)");
+
+    let secret = SecretMString::new("").unwrap();
+    assert_eq!(&*secret, "");
+    assert_eq!(secret.as_c_str().to_bytes(), b"");
+}
+
+#[cfg(not(miri))]
+#[test]
+#[should_panic(expected = "secret contains interior NUL")]
+fn test_secret_mstring_interior_nul() {
+    let _ = SecretMString::new("a\0b");
+}
@@ -4,22 +4,37 @@ use libc::{c_char, strlen};
 #[cfg(feature = "stable_deref_trait")]
 use stable_deref_trait::StableDeref;
 
+#[cfg(feature = "std")]
+use std::borrow::Cow;
 use std::borrow::{Borrow, BorrowMut};
+use std::cmp::Ordering;
 use std::convert::{AsMut, AsRef};
 use std::default::Default;
 #[cfg(feature = "std")]
-use std::ffi::CStr;
+use std::ffi::{CStr, OsStr};
+use std::fmt::{Result as FormatResult, Write};
 use std::hash::{Hash, Hasher};
-use std::iter::once;
-use std::ops::{Deref, DerefMut};
-use std::ptr::{copy_nonoverlapping, null, null_mut, write};
+use std::iter::{once, FromIterator};
+use std::ops::{Add, AddAssign, Deref, DerefMut};
+#[cfg(feature = "std")]
+use std::path::Path;
+use std::ptr::{copy, copy_nonoverlapping, null, null_mut, write, NonNull};
+#[cfg(feature = "std")]
+use std::rc::Rc;
+use std::slice::from_raw_parts;
 use std::str::Utf8Error;
+use std::str::{from_utf8, from_utf8_unchecked};
+#[cfg(feature = "std")]
+use std::sync::Arc;
 
-use crate::internal::gen_malloc;
+use crate::error::{AllocError, FromUtf8Error};
+use crate::internal::{
+    copy_repeated, ct_eq_bytes, gen_malloc, gen_realloc, joined_len, try_gen_malloc,
+};
 use crate::mbox::MBox;
 
 #[cfg(all(test, not(windows)))]
-use crate::internal::DropCounter;
+use crate::test_util::DropCounter;
 
 /// Implemented for types which has a sentinel value.
 pub trait Sentinel: Eq {
@@ -50,11 +65,11 @@ macro_rules! impl_zero_for_sentinel {
 impl_zero_for_sentinel!(u8 i8 u16 i16 u32 i32 u64 i64 u128 i128 usize isize);
 
 /// A `malloc`-backed array with an explicit sentinel at the end.
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[derive(Clone, Debug)]
 pub struct MArray<T: Sentinel>(MBox<[T]>);
 
 /// A `malloc`-backed null-terminated string (similar to `CString`).
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[derive(Debug)]
 pub struct MString(MBox<str>);
 
 impl<T: Sentinel> MArray<T> {
@@ -94,6 +109,16 @@ impl<T: Sentinel + Clone> MArray<T> {
 }
 
 impl MString {
+    /// Constructs an empty string without allocating. Being a `const fn`, this can be used to
+    /// initialize constants and statics.
+    ///
+    /// Like `MBox::<str>::new_empty()`, the string holds a dangling pointer, and not even the
+    /// sentinel is allocated until the string is grown. `as_c_str()` still returns an empty C
+    /// string.
+    pub const fn new_empty() -> MString {
+        MString(MBox::<str>::new_empty())
+    }
+
     /// Constructs a new malloc-backed string from a null-terminated C string.
     ///
     /// # Safety
@@ -104,6 +129,8 @@ impl MString {
     /// the result, and thus should not be used after this function returns.
     ///
     /// The string must be valid UTF-8.
+    // `c_char` from libc, see the MSRV note in lib.rs.
+    #[allow(clippy::incompatible_msrv)]
     pub unsafe fn from_raw_unchecked(base: *mut c_char) -> MString {
         let len = strlen(base);
         MString(MBox::from_raw_utf8_parts_unchecked(
@@ -115,24 +142,134 @@ impl MString {
     /// Constructs a new malloc-backed string from a null-terminated C string. Errors with
     /// `Utf8Error` if the string is not in valid UTF-8.
     ///
+    /// The string is freed if it is not valid UTF-8. Use `from_raw_cstr()` to get its bytes back
+    /// instead.
+    ///
     /// # Safety
     ///
     /// The `base` must be allocated via `malloc()`, `calloc()` or similar C functions that is
     /// expected to be deallocated using `free()`. It must not be null. The content of the string
     /// must be already initialized, and terminated by `'\0'`. The string's ownership is passed into
     /// the result, and thus should not be used after this function returns.
+    // `c_char` from libc, see the MSRV note in lib.rs.
+    #[allow(clippy::incompatible_msrv)]
     pub unsafe fn from_raw(base: *mut c_char) -> Result<MString, Utf8Error> {
         let len = strlen(base);
         let mbox = MBox::from_raw_utf8_parts(base as *mut u8, len + 1)?;
         Ok(MString(mbox))
     }
 
+    /// Takes ownership of a null-terminated C string, e.g. a `char*` returned by a C function. The
+    /// length is measured by `strlen()` and the content is validated, but never copied.
+    ///
+    /// If the content is not valid UTF-8, the error owns the bytes without the terminating `'\0'`,
+    /// so they are not lost.
+    ///
+    /// ```
+    /// use mbox::MString;
+    ///
+    /// let string = unsafe {
+    ///     let raw = libc::strdup(b"hello\0".as_ptr() as *const libc::c_char);
+    ///     assert!(!raw.is_null());
+    ///     MString::from_raw_cstr(raw).unwrap()
+    /// };
+    /// assert_eq!(&*string, "hello");
+    ///
+    /// let error = unsafe {
+    ///     let raw = libc::strdup(b"caf\xe9\0".as_ptr() as *const libc::c_char);
+    ///     assert!(!raw.is_null());
+    ///     MString::from_raw_cstr(raw).unwrap_err()
+    /// };
+    /// assert_eq!(error.as_bytes(), b"caf\xe9");
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// The `ptr` must be allocated via `malloc()`, `calloc()` or similar C functions that is
+    /// expected to be deallocated using `free()`. It must not be null. The content of the string
+    /// must be already initialized, and terminated by `'\0'`. The string's ownership is passed into
+    /// the result, even on error, and thus should not be used after this function returns.
+    // `c_char` from libc, see the MSRV note in lib.rs.
+    #[allow(clippy::incompatible_msrv)]
+    pub unsafe fn from_raw_cstr(ptr: *mut c_char) -> Result<MString, FromUtf8Error> {
+        let len = strlen(ptr);
+        match from_utf8(from_raw_parts(ptr as *const u8, len)) {
+            // SAFETY: the content is valid UTF-8, and followed by the sentinel.
+            Ok(_) => Ok(MString::from_raw_parts_utf8_unchecked(ptr as *mut u8, len)),
+            Err(error) => Err(FromUtf8Error {
+                bytes: MBox::from_raw_parts(ptr as *mut u8, len),
+                error,
+            }),
+        }
+    }
+
+    /// Constructs a new malloc-backed string from a pointer and the length of the string, without
+    /// scanning for the terminating `'\0'` nor validating the content.
+    ///
+    /// # Safety
+    ///
+    /// The `ptr` must be allocated via `malloc()`, `calloc()` or similar C functions that is
+    /// expected to be deallocated using `free()`. It must not be null. The first `len` bytes must
+    /// be initialized and valid UTF-8, and be followed by a `'\0'` at `ptr[len]`. The string's
+    /// ownership is passed into the result, and thus should not be used after this function
+    /// returns.
+    pub unsafe fn from_raw_parts_utf8_unchecked(ptr: *mut u8, len: usize) -> MString {
+        MString(MBox::from_raw_utf8_parts_unchecked(ptr, len + 1))
+    }
+
+    /// Constructs a new malloc-backed string from a buffer of `len` bytes which is not
+    /// null-terminated, as returned by C APIs producing a (pointer, length) pair. The sentinel is
+    /// appended by growing the buffer with `realloc()`. Errors with `Utf8Error` if the content is
+    /// not in valid UTF-8, in which case the buffer is freed.
+    ///
+    /// # Safety
+    ///
+    /// The `ptr` must be allocated via `malloc()`, `calloc()` or similar C functions that is
+    /// expected to be deallocated using `free()`. It must not be null. The first `len` bytes must
+    /// be initialized. The buffer's ownership is passed into the result, and thus should not be
+    /// used after this function returns.
+    pub unsafe fn from_raw_utf8(ptr: *mut u8, len: usize) -> Result<MString, Utf8Error> {
+        let string = MBox::from_raw_utf8_parts(ptr, len)?;
+        Ok(Self::from_utf8_unchecked(string.into_bytes()))
+    }
+
+    /// Converts a box of bytes into a string without validating the content. The sentinel is
+    /// appended by growing the buffer with `realloc()`, so the bytes are usually not copied.
+    ///
+    /// # Safety
+    ///
+    /// The bytes must be valid UTF-8.
+    pub unsafe fn from_utf8_unchecked(bytes: MBox<[u8]>) -> MString {
+        let mut string = MBox::from_utf8_unchecked(bytes);
+        let len = string.len();
+        let new_len = len.checked_add(1).expect("memory overflow");
+        string.realloc_bytes(new_len);
+        write((MBox::as_mut_ptr(&mut string) as *mut u8).add(len), 0);
+        MString(string)
+    }
+
+    /// Wraps a string whose last byte is the sentinel.
+    ///
+    /// # Safety
+    ///
+    /// The string must end with `'\0'`.
+    #[cfg(feature = "std")]
+    pub(crate) unsafe fn from_mbox_with_sentinel_unchecked(mbox: MBox<str>) -> MString {
+        MString(mbox)
+    }
+
     pub fn into_bytes(self) -> MArray<u8> {
+        if self.0.is_empty() {
+            return MArray::default();
+        }
         MArray(self.0.into_bytes())
     }
 
     /// Converts into an `MBox` including the sentinel.
     pub fn into_mbox_with_sentinel(self) -> MBox<str> {
+        if self.0.is_empty() {
+            return MString::default().0;
+        }
         self.0
     }
 
@@ -142,15 +279,384 @@ impl MString {
     }
 
     /// Converts to a C string. This allows users to borrow an MString in FFI code.
-    #[cfg(all(feature = "std"))]
+    #[cfg(feature = "std")]
     pub fn as_c_str(&self) -> &CStr {
-        unsafe { CStr::from_bytes_with_nul_unchecked(self.0.as_bytes()) }
+        unsafe { CStr::from_bytes_with_nul_unchecked(self.as_bytes_with_sentinel()) }
     }
 
     /// Obtains the raw bytes including the sentinel.
     pub fn as_bytes_with_sentinel(&self) -> &[u8] {
+        if self.0.is_empty() {
+            return b"\0";
+        }
         self.0.as_bytes()
     }
+
+    /// Appends a string slice onto the end of this string, growing the buffer using `realloc()`.
+    pub fn push_str(&mut self, string: &str) {
+        // an empty string from `new_empty()` is extended as if it had a sentinel.
+        let old_len = self.0.len().max(1);
+        let new_len = old_len.checked_add(string.len()).expect("memory overflow");
+        // SAFETY: the new tail (and the old sentinel) is overwritten by `string` and a new sentinel
+        // immediately after reallocation.
+        unsafe {
+            self.0.realloc_bytes(new_len);
+            let ptr = MBox::as_mut_ptr(&mut self.0) as *mut u8;
+            copy_nonoverlapping(string.as_ptr(), ptr.add(old_len - 1), string.len());
+            write(ptr.add(new_len - 1), 0);
+        }
+    }
+
+    /// Creates a null-terminated string from the clone of a string. Returns an `AllocError`
+    /// instead of panicking or aborting if the memory cannot be allocated.
+    pub fn try_from_str(string: &str) -> Result<MString, AllocError> {
+        let len = string.len();
+        let len_with_sentinel = len.checked_add(1).ok_or(AllocError::CapacityOverflow)?;
+        // SAFETY: same as `from()`.
+        unsafe {
+            let ptr = try_gen_malloc(len_with_sentinel)?.as_ptr();
+            copy_nonoverlapping(string.as_ptr(), ptr, len);
+            write(ptr.add(len), 0);
+            Ok(MString(MBox::from_raw_utf8_parts_unchecked(
+                ptr,
+                len_with_sentinel,
+            )))
+        }
+    }
+
+    /// Appends a string slice like `push_str()`. Returns an `AllocError` instead of panicking or
+    /// aborting if the memory cannot be allocated, in which case the string is left unchanged.
+    pub fn try_push_str(&mut self, string: &str) -> Result<(), AllocError> {
+        let old_len = self.0.len().max(1);
+        let new_len = old_len
+            .checked_add(string.len())
+            .ok_or(AllocError::CapacityOverflow)?;
+        // SAFETY: same as `push_str()`. On error, the buffer is left untouched.
+        unsafe {
+            self.0.try_realloc_bytes(new_len)?;
+            let ptr = MBox::as_mut_ptr(&mut self.0) as *mut u8;
+            copy_nonoverlapping(string.as_ptr(), ptr.add(old_len - 1), string.len());
+            write(ptr.add(new_len - 1), 0);
+        }
+        Ok(())
+    }
+
+    /// Creates a new string by copying this string `n` times.
+    ///
+    /// The result is allocated in a single block, which is then filled by repeatedly doubling the
+    /// copied region.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resulting size overflows.
+    pub fn repeat(&self, n: usize) -> MString {
+        let len = self.len();
+        let total = len.checked_mul(n).expect("memory overflow");
+        let total_with_sentinel = total.checked_add(1).expect("memory overflow");
+        unsafe {
+            let ptr = gen_malloc(total_with_sentinel).as_ptr();
+            copy_repeated(self.as_ptr(), len, ptr, n);
+            write(ptr.add(total), 0);
+            MString(MBox::from_raw_utf8_parts_unchecked(
+                ptr,
+                total_with_sentinel,
+            ))
+        }
+    }
+
+    /// Decodes Latin-1 (ISO 8859-1) text into a new string. Every byte is a valid Latin-1
+    /// character, so this never fails. Bytes from `0x80` onwards expand to two bytes in UTF-8.
+    pub fn from_latin1(bytes: &[u8]) -> MString {
+        decode_single_byte(bytes, char::from)
+    }
+
+    /// Decodes Latin-1 text like `from_latin1()`, reusing the buffer of `bytes`. The buffer is grown
+    /// using `realloc()` and the characters are expanded in place from the end, so no other
+    /// allocation is needed.
+    pub fn from_latin1_owned(bytes: MBox<[u8]>) -> MString {
+        let extra = bytes.iter().filter(|b| **b >= 0x80).count();
+        let (ptr, len) = bytes.into_raw_parts();
+        // `len + extra` is at most twice the size of an allocated block, and cannot overflow.
+        let new_len = len + extra;
+        // SAFETY: the block is grown to hold the expanded text and the sentinel. Every byte is
+        // written before the content is read as a `str`, and the bytes before `src == dst` are
+        // ASCII which are already in place.
+        unsafe {
+            let ptr = gen_realloc(NonNull::new_unchecked(ptr), len, new_len + 1).as_ptr();
+            let (mut src, mut dst) = (len, new_len);
+            while src != dst {
+                src -= 1;
+                let byte = *ptr.add(src);
+                if byte < 0x80 {
+                    dst -= 1;
+                    write(ptr.add(dst), byte);
+                } else {
+                    dst -= 2;
+                    write(ptr.add(dst), 0xc0 | byte >> 6);
+                    write(ptr.add(dst + 1), 0x80 | byte & 0x3f);
+                }
+            }
+            write(ptr.add(new_len), 0);
+            MString(MBox::from_raw_utf8_parts_unchecked(ptr, new_len + 1))
+        }
+    }
+
+    /// Decodes Windows-1252 text into a new string. This is Latin-1 with printable characters such
+    /// as `€` and curly quotes in place of the C1 control codes. The five bytes not assigned by
+    /// Windows-1252 are replaced by `U+FFFD REPLACEMENT CHARACTER`.
+    pub fn from_windows_1252_lossy(bytes: &[u8]) -> MString {
+        decode_single_byte(bytes, decode_windows_1252)
+    }
+
+    /// Shortens the string to `new_len` bytes, and then shrinks the allocation using `realloc()` to
+    /// return the memory to the allocator.
+    ///
+    /// Does nothing if `new_len` is not less than the current length.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `new_len` does not lie on a char boundary.
+    pub fn truncate(&mut self, new_len: usize) {
+        if new_len >= self.len() {
+            return;
+        }
+        assert!(
+            self.is_char_boundary(new_len),
+            "new_len is not a char boundary"
+        );
+        // SAFETY: `new_len` lies on a char boundary, and the byte at `new_len` is immediately
+        // replaced by the sentinel.
+        unsafe {
+            self.0.realloc_bytes(new_len + 1);
+            let ptr = MBox::as_mut_ptr(&mut self.0) as *mut u8;
+            write(ptr.add(new_len), 0);
+        }
+    }
+
+    /// Finds the largest char boundary not greater than `index`. Returns the length of the string if
+    /// `index` is beyond the end.
+    pub fn floor_char_boundary(&self, index: usize) -> usize {
+        if index >= self.len() {
+            return self.len();
+        }
+        // a char is at most 4 bytes, so a boundary is found within 3 steps back.
+        (index.saturating_sub(3)..=index)
+            .rev()
+            .find(|i| self.is_char_boundary(*i))
+            .unwrap_or(0)
+    }
+
+    /// Shortens the string to at most `max_len` bytes, dropping the char split at that point if
+    /// any. Unlike `truncate()`, this never panics. To fit a C field of `N` bytes, use `N - 1` as
+    /// `max_len` to leave room for the sentinel.
+    pub fn truncate_lossy(&mut self, max_len: usize) {
+        let new_len = self.floor_char_boundary(max_len);
+        self.truncate(new_len);
+    }
+
+    /// Replaces all matches of `from` with `to`.
+    ///
+    /// When `to` is not longer than `from`, the buffer is edited in place and then shrunk using
+    /// `realloc()`. Otherwise, a new block is allocated only if there is any match.
+    pub fn replace_in_place(&mut self, from: &str, to: &str) {
+        if to.len() > from.len() || from.is_empty() {
+            self.replace_growing(from, to);
+            return;
+        }
+
+        let len = self.len();
+        let ptr = MBox::as_mut_ptr(&mut self.0) as *mut u8;
+        let mut read_pos = 0;
+        let mut write_pos = 0;
+        // SAFETY: since `to` is not longer than `from`, the write cursor never overtakes the read
+        // cursor, so the unprocessed part `[read_pos, len)` is never touched while searching. The
+        // resulting content is valid UTF-8 as it is a concatenation of whole segments of the
+        // original string and `to`.
+        unsafe {
+            loop {
+                let rest = from_utf8_unchecked(from_raw_parts(ptr.add(read_pos), len - read_pos));
+                match rest.find(from) {
+                    Some(i) => {
+                        copy(ptr.add(read_pos), ptr.add(write_pos), i);
+                        write_pos += i;
+                        copy_nonoverlapping(to.as_ptr(), ptr.add(write_pos), to.len());
+                        write_pos += to.len();
+                        read_pos += i + from.len();
+                    }
+                    None => {
+                        // `rest` must not be used after the copy, which may overwrite it.
+                        let rest_len = rest.len();
+                        copy(ptr.add(read_pos), ptr.add(write_pos), rest_len);
+                        write_pos += rest_len;
+                        break;
+                    }
+                }
+            }
+            if write_pos < len {
+                write(ptr.add(write_pos), 0);
+                self.0.realloc_bytes(write_pos + 1);
+            }
+        }
+    }
+
+    /// Replaces all matches of `from` with `to` into a new allocation.
+    fn replace_growing(&mut self, from: &str, to: &str) {
+        let matches = self.matches(from).count();
+        if matches == 0 {
+            return;
+        }
+        let total = (to.len() - from.len())
+            .checked_mul(matches)
+            .and_then(|extra| extra.checked_add(self.len()))
+            .expect("memory overflow");
+        let total_with_sentinel = total.checked_add(1).expect("memory overflow");
+        // SAFETY: exactly `total` bytes of valid UTF-8 are copied into the buffer, followed by the
+        // sentinel.
+        unsafe {
+            let ptr = gen_malloc::<u8>(total_with_sentinel).as_ptr();
+            let mut offset = 0;
+            let mut last_end = 0;
+            for (start, part) in self.match_indices(from) {
+                let segment = &self[last_end..start];
+                copy_nonoverlapping(segment.as_ptr(), ptr.add(offset), segment.len());
+                offset += segment.len();
+                copy_nonoverlapping(to.as_ptr(), ptr.add(offset), to.len());
+                offset += to.len();
+                last_end = start + part.len();
+            }
+            let segment = &self[last_end..];
+            copy_nonoverlapping(segment.as_ptr(), ptr.add(offset), segment.len());
+            write(ptr.add(total), 0);
+            *self = MString(MBox::from_raw_utf8_parts_unchecked(
+                ptr,
+                total_with_sentinel,
+            ));
+        }
+    }
+
+    /// Converts the string to ASCII upper case, reusing the allocation.
+    ///
+    /// Non-ASCII characters are unchanged. To convert in place without consuming the string, use
+    /// `str::make_ascii_uppercase()` through `DerefMut`.
+    pub fn to_ascii_uppercase_owned(mut self) -> MString {
+        self.make_ascii_uppercase();
+        self
+    }
+
+    /// Converts the string to ASCII lower case, reusing the allocation.
+    ///
+    /// Non-ASCII characters are unchanged. To convert in place without consuming the string, use
+    /// `str::make_ascii_lowercase()` through `DerefMut`.
+    pub fn to_ascii_lowercase_owned(mut self) -> MString {
+        self.make_ascii_lowercase();
+        self
+    }
+
+    /// Compares the string with `other` in constant time, i.e. the time taken depends only on the
+    /// lengths and not on the content. Use this to compare secrets like passwords or tokens.
+    pub fn ct_eq(&self, other: &str) -> bool {
+        ct_eq_bytes(self.as_bytes(), other.as_bytes())
+    }
+
+    /// Appends a character onto the end of this string.
+    pub fn push(&mut self, ch: char) {
+        self.push_str(ch.encode_utf8(&mut [0; 4]));
+    }
+}
+
+/// Concatenates the strings into a single `MString`.
+///
+/// The total length is computed first, so the result is allocated exactly once.
+pub fn concat_str<S: Borrow<str>>(strings: &[S]) -> MString {
+    join_str(strings, "")
+}
+
+/// Concatenates the strings into a single `MString`, placing `separator` between them.
+///
+/// The total length is computed first, so the result is allocated exactly once.
+pub fn join_str<S: Borrow<str>>(strings: &[S], separator: &str) -> MString {
+    let total = joined_len(strings.iter().map(|s| s.borrow().len()), separator.len());
+    let total_with_sentinel = total.checked_add(1).expect("memory overflow");
+    // SAFETY: exactly `total` bytes of valid UTF-8 are copied into the buffer, followed by the
+    // sentinel.
+    unsafe {
+        let ptr = gen_malloc::<u8>(total_with_sentinel).as_ptr();
+        let mut offset = 0;
+        for (i, string) in strings.iter().enumerate() {
+            if i != 0 {
+                copy_nonoverlapping(separator.as_ptr(), ptr.add(offset), separator.len());
+                offset += separator.len();
+            }
+            let string = string.borrow();
+            copy_nonoverlapping(string.as_ptr(), ptr.add(offset), string.len());
+            offset += string.len();
+        }
+        write(ptr.add(total), 0);
+        MString(MBox::from_raw_utf8_parts_unchecked(
+            ptr,
+            total_with_sentinel,
+        ))
+    }
+}
+
+impl Add<&str> for MString {
+    type Output = MString;
+
+    /// Concatenates a string slice onto the end of this string, reusing its allocation.
+    fn add(mut self, string: &str) -> MString {
+        self.push_str(string);
+        self
+    }
+}
+
+impl AddAssign<&str> for MString {
+    fn add_assign(&mut self, string: &str) {
+        self.push_str(string);
+    }
+}
+
+impl Extend<char> for MString {
+    fn extend<I: IntoIterator<Item = char>>(&mut self, iter: I) {
+        for ch in iter {
+            self.push(ch);
+        }
+    }
+}
+
+impl<'a> Extend<&'a str> for MString {
+    fn extend<I: IntoIterator<Item = &'a str>>(&mut self, iter: I) {
+        for string in iter {
+            self.push_str(string);
+        }
+    }
+}
+
+impl FromIterator<char> for MString {
+    fn from_iter<I: IntoIterator<Item = char>>(iter: I) -> Self {
+        let mut string = MString::default();
+        string.extend(iter);
+        string
+    }
+}
+
+impl<'a> FromIterator<&'a str> for MString {
+    fn from_iter<I: IntoIterator<Item = &'a str>>(iter: I) -> Self {
+        let mut string = MString::default();
+        string.extend(iter);
+        string
+    }
+}
+
+impl Write for MString {
+    fn write_str(&mut self, string: &str) -> FormatResult {
+        self.push_str(string);
+        Ok(())
+    }
+
+    fn write_char(&mut self, ch: char) -> FormatResult {
+        self.push(ch);
+        Ok(())
+    }
 }
 
 impl From<&str> for MString {
@@ -166,6 +672,90 @@ impl From<&str> for MString {
     }
 }
 
+/// Decodes text in a single-byte encoding into a new string, using `decode` to map every byte.
+fn decode_single_byte(bytes: &[u8], decode: fn(u8) -> char) -> MString {
+    let len = bytes
+        .iter()
+        .try_fold(0usize, |len, b| len.checked_add(decode(*b).len_utf8()))
+        .and_then(|len| len.checked_add(1))
+        .expect("memory overflow");
+    // SAFETY: every char is encoded into the buffer, followed by the sentinel.
+    unsafe {
+        let ptr = gen_malloc::<u8>(len).as_ptr();
+        let mut pos = 0;
+        for b in bytes {
+            let mut buf = [0; 4];
+            let encoded = decode(*b).encode_utf8(&mut buf);
+            copy_nonoverlapping(encoded.as_ptr(), ptr.add(pos), encoded.len());
+            pos += encoded.len();
+        }
+        write(ptr.add(pos), 0);
+        MString(MBox::from_raw_utf8_parts_unchecked(ptr, len))
+    }
+}
+
+/// Maps a Windows-1252 byte to its character.
+fn decode_windows_1252(byte: u8) -> char {
+    // the characters of the bytes 0x80 to 0x9f, which are C1 control codes in Latin-1.
+    const C1: [char; 32] = [
+        '\u{20ac}', '\u{fffd}', '\u{201a}', '\u{0192}', '\u{201e}', '\u{2026}', '\u{2020}',
+        '\u{2021}', '\u{02c6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{fffd}',
+        '\u{017d}', '\u{fffd}', '\u{fffd}', '\u{2018}', '\u{2019}', '\u{201c}', '\u{201d}',
+        '\u{2022}', '\u{2013}', '\u{2014}', '\u{02dc}', '\u{2122}', '\u{0161}', '\u{203a}',
+        '\u{0153}', '\u{fffd}', '\u{017e}', '\u{0178}',
+    ];
+    match byte {
+        0x80..=0x9f => C1[usize::from(byte - 0x80)],
+        _ => char::from(byte),
+    }
+}
+
+/// Implements conversions from the shared string `$ptr<str>`, by value and by reference. The
+/// content is always copied, since it may be shared with other owners.
+#[cfg(feature = "std")]
+macro_rules! impl_from_shared_str {
+    ($($ptr:ident)*) => {$(
+        impl From<&$ptr<str>> for MString {
+            /// Creates a null-terminated string from the copy of a shared string.
+            fn from(string: &$ptr<str>) -> MString {
+                MString::from(&**string)
+            }
+        }
+
+        impl From<$ptr<str>> for MString {
+            /// Creates a null-terminated string from the copy of a shared string, and then
+            /// releases the shared reference.
+            fn from(string: $ptr<str>) -> MString {
+                MString::from(&*string)
+            }
+        }
+    )*};
+}
+
+#[cfg(feature = "std")]
+impl_from_shared_str!(Arc Rc);
+
+#[cfg(feature = "std")]
+impl From<Cow<'_, str>> for MString {
+    /// Creates a null-terminated string from the copy of a borrowed or owned string.
+    fn from(string: Cow<'_, str>) -> MString {
+        MString::from(&*string)
+    }
+}
+
+impl Clone for MString {
+    fn clone(&self) -> Self {
+        MString(self.0.clone())
+    }
+
+    /// Copies the string into the existing allocation, resizing it with `realloc()` only when the
+    /// lengths differ.
+    fn clone_from(&mut self, source: &Self) {
+        // the sentinel is copied together with the content.
+        self.0.clone_from(&source.0);
+    }
+}
+
 impl<T: Sentinel> Deref for MArray<T> {
     type Target = [T];
     fn deref(&self) -> &[T] {
@@ -177,7 +767,7 @@ impl<T: Sentinel> Deref for MArray<T> {
 impl Deref for MString {
     type Target = str;
     fn deref(&self) -> &str {
-        let actual_len = self.0.len() - 1;
+        let actual_len = self.0.len().saturating_sub(1);
         &self.0[..actual_len]
     }
 }
@@ -194,23 +784,99 @@ impl<T: Sentinel> DerefMut for MArray<T> {
     }
 }
 
-#[allow(clippy::derive_hash_xor_eq)]
 impl Hash for MString {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.deref().hash(state);
     }
 }
 
-#[allow(clippy::derive_hash_xor_eq)]
 impl<T: Sentinel + Hash> Hash for MArray<T> {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.deref().hash(state);
     }
 }
 
+impl PartialEq for MString {
+    fn eq(&self, other: &Self) -> bool {
+        self.deref().eq(other.deref())
+    }
+}
+
+impl Eq for MString {}
+
+impl PartialOrd for MString {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for MString {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.deref().cmp(other.deref())
+    }
+}
+
+impl PartialEq<str> for MString {
+    fn eq(&self, other: &str) -> bool {
+        self.deref() == other
+    }
+}
+
+impl<'a> PartialEq<&'a str> for MString {
+    fn eq(&self, other: &&'a str) -> bool {
+        self.deref() == *other
+    }
+}
+
+#[cfg(feature = "std")]
+impl PartialEq<String> for MString {
+    fn eq(&self, other: &String) -> bool {
+        self.deref() == other
+    }
+}
+
+impl PartialEq<MString> for str {
+    fn eq(&self, other: &MString) -> bool {
+        self == other.deref()
+    }
+}
+
+impl PartialEq<MString> for &str {
+    fn eq(&self, other: &MString) -> bool {
+        *self == other.deref()
+    }
+}
+
+#[cfg(feature = "std")]
+impl PartialEq<MString> for String {
+    fn eq(&self, other: &MString) -> bool {
+        self == other.deref()
+    }
+}
+
+impl<T: Sentinel> PartialEq for MArray<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.deref().eq(other.deref())
+    }
+}
+
+impl<T: Sentinel> Eq for MArray<T> {}
+
+impl<T: Sentinel + PartialOrd> PartialOrd for MArray<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.deref().partial_cmp(other.deref())
+    }
+}
+
+impl<T: Sentinel + Ord> Ord for MArray<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.deref().cmp(other.deref())
+    }
+}
+
 impl DerefMut for MString {
     fn deref_mut(&mut self) -> &mut str {
-        let actual_len = self.0.len() - 1;
+        let actual_len = self.0.len().saturating_sub(1);
         &mut self.0[..actual_len]
     }
 }
@@ -283,6 +949,12 @@ impl BorrowMut<str> for MString {
     }
 }
 
+impl AsRef<[u8]> for MString {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
 #[cfg(feature = "std")]
 impl AsRef<CStr> for MString {
     fn as_ref(&self) -> &CStr {
@@ -290,6 +962,20 @@ impl AsRef<CStr> for MString {
     }
 }
 
+#[cfg(feature = "std")]
+impl AsRef<OsStr> for MString {
+    fn as_ref(&self) -> &OsStr {
+        OsStr::new(self.deref())
+    }
+}
+
+#[cfg(feature = "std")]
+impl AsRef<Path> for MString {
+    fn as_ref(&self) -> &Path {
+        Path::new(self.deref())
+    }
+}
+
 #[test]
 fn test_array() {
     unsafe {
@@ -361,6 +1047,80 @@ fn test_non_utf8_string() {
     }
 }
 
+#[test]
+fn test_string_unchecked_constructors() {
+    unsafe {
+        let src = gen_malloc::<u8>(4).as_ptr();
+        copy_nonoverlapping(b"abc\0".as_ptr(), src, 4);
+        let string = MString::from_raw_parts_utf8_unchecked(src, 3);
+        assert_eq!(string.as_bytes_with_sentinel(), b"abc\0");
+
+        let string = MString::from_utf8_unchecked(MBox::from_slice(b"xyz"));
+        assert_eq!(&*string, "xyz");
+        assert_eq!(string.as_bytes_with_sentinel(), b"xyz\0");
+
+        let string = MString::from_utf8_unchecked(MBox::<[u8]>::new_empty());
+        assert_eq!(string.as_bytes_with_sentinel(), b"\0");
+    }
+}
+
+#[test]
+fn test_string_from_latin1() {
+    let string = MString::from_latin1(b"caf\xe9 \xa3\xff");
+    assert_eq!(&*string, "café £ÿ");
+    assert_eq!(string.as_bytes_with_sentinel().last(), Some(&0));
+    assert_eq!(MString::from_latin1(b"").as_bytes_with_sentinel(), b"\0");
+
+    let string = MString::from_latin1_owned(MBox::from_slice(b"na\xefve \x80"));
+    assert_eq!(&*string, "naïve \u{80}");
+    assert_eq!(string.as_bytes_with_sentinel().last(), Some(&0));
+    let string = MString::from_latin1_owned(MBox::from_slice(b"ascii"));
+    assert_eq!(string.as_bytes_with_sentinel(), b"ascii\0");
+    let string = MString::from_latin1_owned(MBox::<[u8]>::new_empty());
+    assert_eq!(string.as_bytes_with_sentinel(), b"\0");
+
+    let string = MString::from_windows_1252_lossy(b"\x93\x80 5\x94 \x81\xe9");
+    assert_eq!(&*string, "\u{201c}\u{20ac} 5\u{201d} \u{fffd}é");
+}
+
+#[test]
+fn test_string_from_raw_utf8() {
+    unsafe {
+        let src = gen_malloc::<u8>(4).as_ptr();
+        copy_nonoverlapping("a甲".as_ptr(), src, 4);
+        let string = MString::from_raw_utf8(src, 4).unwrap();
+        assert_eq!(&*string, "a甲");
+        assert_eq!(string.as_bytes_with_sentinel(), b"a\xe7\x94\xb2\0");
+
+        let src = gen_malloc::<u8>(4).as_ptr();
+        copy_nonoverlapping("a甲".as_ptr(), src, 4);
+        assert!(MString::from_raw_utf8(src, 3).is_err());
+    }
+}
+
+#[test]
+fn test_string_from_raw_cstr() {
+    unsafe {
+        let src = gen_malloc::<u8>(5).as_ptr();
+        copy_nonoverlapping(b"a\xe7\x94\xb2\0".as_ptr(), src, 5);
+        let string = MString::from_raw_cstr(src as *mut c_char).unwrap();
+        assert_eq!(&*string, "a甲");
+        assert_eq!(string.as_bytes_with_sentinel(), b"a\xe7\x94\xb2\0");
+
+        let src = gen_malloc::<u8>(4).as_ptr();
+        copy_nonoverlapping(b"a\xe7\x94\0".as_ptr(), src, 4);
+        let error = MString::from_raw_cstr(src as *mut c_char).unwrap_err();
+        assert_eq!(error.utf8_error().valid_up_to(), 1);
+        let bytes = error.into_bytes();
+        assert_eq!(&*bytes, b"a\xe7\x94");
+        assert_eq!(bytes.as_ptr(), src as *const u8);
+
+        let src = gen_malloc::<u8>(1).as_ptr();
+        *src = 0;
+        assert_eq!(&*MString::from_raw_cstr(src as *mut c_char).unwrap(), "");
+    }
+}
+
 #[cfg(feature = "std")]
 #[test]
 fn test_c_str() {
@@ -374,6 +1134,29 @@ fn test_c_str() {
     }
 }
 
+#[test]
+fn test_string_as_ref() {
+    fn bytes_of<B: AsRef<[u8]> + ?Sized>(b: &B) -> &[u8] {
+        b.as_ref()
+    }
+    fn str_of<S: AsRef<str> + ?Sized>(s: &S) -> &str {
+        s.as_ref()
+    }
+
+    let string = MString::from("abc");
+    assert_eq!(bytes_of(&string), b"abc");
+    assert_eq!(str_of(&string), "abc");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_string_as_path() {
+    let string = MString::from("/usr/lib");
+    let path: &Path = string.as_ref();
+    assert_eq!(path.file_name(), Some(OsStr::new("lib")));
+    assert_eq!(Path::new("/usr").join(&string), Path::new("/usr/lib"));
+}
+
 #[cfg(not(windows))]
 #[test]
 fn test_array_into_mbox() {
@@ -408,6 +1191,254 @@ fn test_default_string() {
     assert_eq!(string.into_mbox_with_sentinel(), MBox::<str>::from("\0"));
 }
 
+#[test]
+fn test_new_empty_string() {
+    static EMPTY: MString = MString::new_empty();
+    assert_eq!(&*EMPTY, "");
+    assert_eq!(EMPTY.as_bytes_with_sentinel(), b"\0");
+    assert_eq!(EMPTY.clone(), MString::default());
+
+    let string = MString::new_empty();
+    assert_eq!(string.into_mbox_with_sentinel(), MBox::<str>::from("\0"));
+    assert_eq!(MString::new_empty().into_mbox(), MBox::<str>::from(""));
+
+    let mut string = MString::new_empty();
+    string.make_ascii_uppercase();
+    string.push_str("abc");
+    assert_eq!(string.as_bytes_with_sentinel(), b"abc\0");
+}
+
+#[test]
+fn test_take_string() {
+    let mut string = MString::from("abc");
+    let taken = std::mem::replace(&mut string, MString::default());
+    assert_eq!(taken, "abc");
+    assert_eq!(string, "");
+    assert_eq!(string.as_bytes_with_sentinel(), b"\0");
+}
+
+#[test]
+fn test_string_partial_eq() {
+    let string = MString::from("abc");
+    assert!(string == *"abc");
+    assert!(*"abc" == string);
+    assert_eq!(string, "abc");
+    assert_eq!("abc", string);
+    assert_ne!(string, "abc\0");
+    assert_ne!("ab", string);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_string_partial_eq_string() {
+    let string = MString::from("abc");
+    assert_eq!(string, String::from("abc"));
+    assert_eq!(String::from("abc"), string);
+    assert_ne!(string, String::new());
+}
+
+#[test]
+fn test_push_str() {
+    let mut string = MString::default();
+    string.push_str("abc");
+    string.push('d');
+    string.push('甲');
+    string.push_str("");
+    string.push_str("ef");
+    assert_eq!(string, "abcd甲ef");
+    assert_eq!(string.as_bytes_with_sentinel(), "abcd甲ef\0".as_bytes());
+}
+
+#[test]
+fn test_try_push_str() {
+    let mut string = MString::try_from_str("abc").unwrap();
+    string.try_push_str("").unwrap();
+    string.try_push_str("甲乙").unwrap();
+    assert_eq!(string, "abc甲乙");
+    assert_eq!(string.as_bytes_with_sentinel(), "abc甲乙\0".as_bytes());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_string_from_shared() {
+    let string: Arc<str> = Arc::from("ab");
+    assert_eq!(MString::from(&string).as_bytes_with_sentinel(), b"ab\0");
+    assert_eq!(MString::from(string), "ab");
+    let string: Rc<str> = Rc::from("cd");
+    assert_eq!(MString::from(&string), "cd");
+    assert_eq!(MString::from(string).as_bytes_with_sentinel(), b"cd\0");
+    assert_eq!(MString::from(Cow::Borrowed("ef")), "ef");
+    assert_eq!(MString::from(Cow::Owned("gh".to_owned())), "gh");
+}
+
+#[test]
+fn test_string_from_iter() {
+    let string = "a甲b".chars().rev().collect::<MString>();
+    assert_eq!(string, "b甲a");
+    let string = ["ab", "", "cde"].iter().copied().collect::<MString>();
+    assert_eq!(string, "abcde");
+    assert_eq!(string.as_bytes_with_sentinel(), b"abcde\0");
+    assert_eq!(None::<char>.into_iter().collect::<MString>(), "");
+
+    let mut string = MString::from("x");
+    string.extend("yz".chars());
+    string.extend(["1", "23"].iter().copied());
+    assert_eq!(string, "xyz123");
+}
+
+#[test]
+fn test_mstr() {
+    let string = mstr!("abc");
+    assert_eq!(string, "abc");
+    assert_eq!(string.as_bytes_with_sentinel(), b"abc\0");
+    assert_eq!(mstr!(""), "");
+}
+
+#[test]
+fn test_string_repeat() {
+    let string = MString::from("a甲");
+    assert_eq!(string.repeat(0), "");
+    assert_eq!(string.repeat(1), "a甲");
+    assert_eq!(string.repeat(5), "a甲a甲a甲a甲a甲");
+    assert_eq!(
+        string.repeat(3).as_bytes_with_sentinel(),
+        "a甲a甲a甲\0".as_bytes()
+    );
+    assert_eq!(MString::default().repeat(10), "");
+}
+
+#[test]
+fn test_concat_and_join_str() {
+    let fragments = [MString::from("ab"), MString::default(), MString::from("甲")];
+    assert_eq!(concat_str(&fragments), "ab甲");
+    assert_eq!(join_str(&fragments, ", "), "ab, , 甲");
+    assert_eq!(
+        join_str(&["x", "y"], "-").as_bytes_with_sentinel(),
+        b"x-y\0"
+    );
+    assert_eq!(concat_str::<&str>(&[]).as_bytes_with_sentinel(), b"\0");
+}
+
+#[test]
+fn test_string_truncate() {
+    let mut string = MString::from("ab甲c");
+    string.truncate(10);
+    assert_eq!(string, "ab甲c");
+    string.truncate(5);
+    assert_eq!(string.as_bytes_with_sentinel(), "ab甲\0".as_bytes());
+    string.truncate(0);
+    assert_eq!(string.as_bytes_with_sentinel(), b"\0");
+}
+
+#[test]
+fn test_string_truncate_lossy() {
+    let mut string = MString::from("ab甲c");
+    assert_eq!(string.floor_char_boundary(0), 0);
+    assert_eq!(string.floor_char_boundary(3), 2);
+    assert_eq!(string.floor_char_boundary(5), 5);
+    assert_eq!(string.floor_char_boundary(100), 6);
+    string.truncate_lossy(10);
+    assert_eq!(string, "ab甲c");
+    string.truncate_lossy(4);
+    assert_eq!(string.as_bytes_with_sentinel(), b"ab\0");
+    string.truncate_lossy(0);
+    assert_eq!(string.as_bytes_with_sentinel(), b"\0");
+
+    let mut string = MString::from("🦀");
+    string.truncate_lossy(3);
+    assert_eq!(string.as_bytes_with_sentinel(), b"\0");
+}
+
+#[test]
+#[should_panic(expected = "new_len is not a char boundary")]
+fn test_string_truncate_inside_char() {
+    MString::from("甲").truncate(1);
+}
+
+#[test]
+fn test_string_ascii_case() {
+    let mut string = MString::from("Hello, Wörld");
+    let ptr = string.as_ptr();
+    string.make_ascii_uppercase();
+    assert_eq!(string, "HELLO, WöRLD");
+    let string = string.to_ascii_lowercase_owned();
+    assert_eq!(string, "hello, wörld");
+    assert_eq!(string.as_ptr(), ptr);
+    let string = string.to_ascii_uppercase_owned();
+    assert_eq!(string.as_bytes_with_sentinel(), "HELLO, WöRLD\0".as_bytes());
+    assert_eq!(string.as_ptr(), ptr);
+}
+
+#[test]
+fn test_string_replace_in_place() {
+    let mut string = MString::from("one, two, three");
+    string.replace_in_place(", ", ",");
+    assert_eq!(string.as_bytes_with_sentinel(), b"one,two,three\0");
+    string.replace_in_place("two", "2");
+    assert_eq!(string.as_bytes_with_sentinel(), b"one,2,three\0");
+    string.replace_in_place("e", "E");
+    assert_eq!(string.as_bytes_with_sentinel(), b"onE,2,thrEE\0");
+    // without any match, the buffer is untouched.
+    let ptr = string.as_ptr();
+    string.replace_in_place("missing", "");
+    assert_eq!(string, "onE,2,thrEE");
+    assert_eq!(string.as_ptr(), ptr);
+
+    string.replace_in_place("E", "甲乙");
+    assert_eq!(
+        string.as_bytes_with_sentinel(),
+        "on甲乙,2,thr甲乙甲乙\0".as_bytes()
+    );
+    string.replace_in_place("甲乙", "");
+    assert_eq!(string.as_bytes_with_sentinel(), b"on,2,thr\0");
+    string.replace_in_place("", "-");
+    assert_eq!(string, "-o-n-,-2-,-t-h-r-");
+}
+
+#[test]
+fn test_string_ct_eq() {
+    let password = MString::from("hunter2");
+    assert!(password.ct_eq("hunter2"));
+    assert!(!password.ct_eq("hunter3"));
+    assert!(!password.ct_eq(""));
+}
+
+#[test]
+fn test_string_clone_from() {
+    let mut string = MString::from("abcdef");
+    string.clone_from(&MString::from("甲"));
+    assert_eq!(string, "甲");
+    assert_eq!(string.as_bytes_with_sentinel(), "甲\0".as_bytes());
+    string.clone_from(&MString::from("a longer string"));
+    assert_eq!(string.as_bytes_with_sentinel(), b"a longer string\0");
+    string.clone_from(&MString::default());
+    assert_eq!(string.as_bytes_with_sentinel(), b"\0");
+}
+
+#[test]
+fn test_string_add() {
+    let string = MString::from("ab") + "cd" + "" + "甲";
+    assert_eq!(string, "abcd甲");
+    let mut string = string;
+    string += "e";
+    string += &MString::from("fg");
+    assert_eq!(string, "abcd甲efg");
+    assert_eq!(string.as_bytes_with_sentinel(), "abcd甲efg\0".as_bytes());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_fmt_write() {
+    let mut string = MString::from("x = ");
+    write!(string, "{}, y = {:?}", 1, "2").unwrap();
+    assert_eq!(string, "x = 1, y = \"2\"");
+    assert_eq!(string.as_c_str().to_bytes(), b"x = 1, y = \"2\"");
+
+    let string = mformat!("{:>5}|{:<3}|", "ab", 7);
+    assert_eq!(string, "   ab|7  |");
+    assert_eq!(mformat!(""), "");
+}
+
 #[cfg(feature = "std")]
 #[test]
 fn test_hash_string() {
@@ -445,3 +1476,43 @@ fn test_hash_array() {
     assert!(!hs.contains(&MArray::from_slice(b"ef")));
     assert!(hs.contains(&MArray::from_slice(b"a")));
 }
+
+#[cfg(feature = "std")]
+#[test]
+fn test_ord_string() {
+    use std::collections::BTreeMap;
+
+    let mut map = BTreeMap::new();
+    map.insert(MString::from("bcd"), 1);
+    map.insert(MString::from("a"), 2);
+    map.insert(MString::from("ab"), 3);
+
+    assert_eq!(map.get("bcd"), Some(&1));
+    assert_eq!(map.get("ab"), Some(&3));
+    assert_eq!(map.get("bcd\0"), None);
+    let keys = map.keys().map(|k| &**k).collect::<Vec<_>>();
+    assert_eq!(keys, ["a", "ab", "bcd"]);
+
+    let ab = MString::from("ab");
+    assert_eq!(ab.cmp(&MString::from("abc")), Ordering::Less);
+    assert_eq!(ab.cmp(&MString::from("ab\0")), Ordering::Less);
+    assert_eq!(ab.cmp(&MString::from("a")), Ordering::Greater);
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_ord_array() {
+    let mut arrays = [
+        MArray::from_slice(&[3u32, 1]),
+        MArray::from_slice(&[3]),
+        MArray::from_slice(&[]),
+        MArray::from_slice(&[1, 2, 3]),
+    ];
+    arrays.sort();
+    assert!(arrays[0].is_empty());
+    assert_eq!(&*arrays[1], &[1, 2, 3]);
+    assert_eq!(&*arrays[2], &[3]);
+    assert_eq!(&*arrays[3], &[3, 1]);
+    assert_eq!(arrays[2], MArray::from_slice(&[3]));
+    assert_ne!(arrays[2], arrays[3]);
+}
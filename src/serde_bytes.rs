@@ -0,0 +1,115 @@
+//! Serializes `MBox<[u8]>` as a byte string instead of a sequence of integers.
+//!
+//! This module is compatible with the [`serde_bytes`](https://crates.io/crates/serde_bytes)
+//! crate, and is supposed to be used through the `#[serde(with)]` attribute:
+//!
+//! ```rust,ignore
+//! use mbox::MBox;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Packet {
+//!     #[serde(with = "mbox::serde_bytes")]
+//!     payload: MBox<[u8]>,
+//! }
+//! ```
+
+use serde::de::{Deserializer, Error, SeqAccess, Visitor};
+use serde::ser::Serializer;
+
+use std::fmt::{Formatter, Result as FormatResult};
+
+use crate::mbox::{MBox, MSliceBuilder};
+
+/// Upper bound of the initial capacity taken from an untrusted `size_hint`.
+const MAX_PREALLOCATED_BYTES: usize = 65536;
+
+/// Serializes the bytes as a byte string.
+pub fn serialize<T, S>(bytes: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: ?Sized + AsRef<[u8]>,
+    S: Serializer,
+{
+    serializer.serialize_bytes(bytes.as_ref())
+}
+
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = MBox<[u8]>;
+
+    fn expecting(&self, formatter: &mut Formatter) -> FormatResult {
+        formatter.write_str("a byte string")
+    }
+
+    fn visit_bytes<E: Error>(self, value: &[u8]) -> Result<Self::Value, E> {
+        Ok(MBox::from_slice(value))
+    }
+
+    fn visit_str<E: Error>(self, value: &str) -> Result<Self::Value, E> {
+        self.visit_bytes(value.as_bytes())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let capacity = seq.size_hint().unwrap_or(0).min(MAX_PREALLOCATED_BYTES);
+        let mut builder = MSliceBuilder::with_capacity(capacity);
+        while let Some(byte) = seq.next_element()? {
+            builder.push(byte);
+        }
+        Ok(builder.into_mboxed_slice())
+    }
+}
+
+/// Deserializes a byte string into `malloc`-ed memory.
+///
+/// Strings and sequences of integers are accepted as well, for formats without native support of
+/// byte strings.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<MBox<[u8]>, D::Error> {
+    deserializer.deserialize_bytes(BytesVisitor)
+}
+
+#[cfg(test)]
+use serde::{de::Deserialize, ser::Serialize};
+#[cfg(test)]
+use serde_test::{assert_de_tokens, assert_tokens, Token};
+
+#[cfg(test)]
+#[derive(Debug, PartialEq)]
+struct Payload(MBox<[u8]>);
+
+#[cfg(test)]
+impl Serialize for Payload {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize(&self.0, serializer)
+    }
+}
+
+#[cfg(test)]
+impl<'de> Deserialize<'de> for Payload {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize(deserializer).map(Payload)
+    }
+}
+
+#[test]
+fn test_serde_bytes() {
+    let payload = Payload(MBox::from_slice(b"\x00\x01\xff"));
+    assert_tokens(&payload, &[Token::Bytes(b"\x00\x01\xff")]);
+    assert_de_tokens(&payload, &[Token::BorrowedBytes(b"\x00\x01\xff")]);
+    assert_de_tokens(&payload, &[Token::ByteBuf(b"\x00\x01\xff")]);
+    assert_tokens(&Payload(MBox::default()), &[Token::Bytes(b"")]);
+}
+
+#[test]
+fn test_serde_bytes_fallbacks() {
+    assert_de_tokens(
+        &Payload(MBox::from_slice(b"ab")),
+        &[
+            Token::Seq { len: Some(2) },
+            Token::U8(b'a'),
+            Token::U8(b'b'),
+            Token::SeqEnd,
+        ],
+    );
+    assert_de_tokens(&Payload(MBox::from_slice(b"ab")), &[Token::Str("ab")]);
+}
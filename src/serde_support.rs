@@ -0,0 +1,175 @@
+//! Serde support.
+
+use serde::de::{Deserialize, Deserializer, Error, SeqAccess, Unexpected, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use std::fmt::{Formatter, Result as FormatResult};
+use std::marker::PhantomData;
+
+use crate::free::Free;
+use crate::internal::from_utf8;
+use crate::mbox::{MBox, MSliceBuilder};
+use crate::sentinel::MString;
+
+/// Upper bound of the initial capacity taken from an untrusted `size_hint`.
+const MAX_PREALLOCATED_ITEMS: usize = 4096;
+
+impl<T: ?Sized + Free + Serialize> Serialize for MBox<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+impl Serialize for MString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for MBox<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(MBox::new)
+    }
+}
+
+struct SliceVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for SliceVisitor<T> {
+    type Value = MBox<[T]>;
+
+    fn expecting(&self, formatter: &mut Formatter) -> FormatResult {
+        formatter.write_str("a sequence")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let capacity = seq.size_hint().unwrap_or(0).min(MAX_PREALLOCATED_ITEMS);
+        let mut builder = MSliceBuilder::with_capacity(capacity);
+        while let Some(item) = seq.next_element()? {
+            builder.push(item);
+        }
+        Ok(builder.into_mboxed_slice())
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for MBox<[T]> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(SliceVisitor(PhantomData))
+    }
+}
+
+struct StrVisitor;
+
+impl<'de> Visitor<'de> for StrVisitor {
+    type Value = MBox<str>;
+
+    fn expecting(&self, formatter: &mut Formatter) -> FormatResult {
+        formatter.write_str("a string")
+    }
+
+    fn visit_str<E: Error>(self, value: &str) -> Result<Self::Value, E> {
+        Ok(MBox::from(value))
+    }
+
+    fn visit_bytes<E: Error>(self, value: &[u8]) -> Result<Self::Value, E> {
+        match from_utf8(value) {
+            Ok(value) => self.visit_str(value),
+            Err(_) => Err(E::invalid_value(Unexpected::Bytes(value), &self)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for MBox<str> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(StrVisitor)
+    }
+}
+
+struct MStringVisitor;
+
+impl<'de> Visitor<'de> for MStringVisitor {
+    type Value = MString;
+
+    fn expecting(&self, formatter: &mut Formatter) -> FormatResult {
+        formatter.write_str("a string")
+    }
+
+    fn visit_str<E: Error>(self, value: &str) -> Result<Self::Value, E> {
+        Ok(MString::from(value))
+    }
+
+    fn visit_bytes<E: Error>(self, value: &[u8]) -> Result<Self::Value, E> {
+        match from_utf8(value) {
+            Ok(value) => self.visit_str(value),
+            Err(_) => Err(E::invalid_value(Unexpected::Bytes(value), &self)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for MString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(MStringVisitor)
+    }
+}
+
+#[cfg(test)]
+use serde_test::{assert_de_tokens, assert_de_tokens_error, assert_tokens, Token};
+
+#[test]
+fn test_serde_single_object() {
+    assert_tokens(&MBox::new(7u8), &[Token::U8(7)]);
+    assert_tokens(
+        &MBox::new((1u8, true)),
+        &[
+            Token::Tuple { len: 2 },
+            Token::U8(1),
+            Token::Bool(true),
+            Token::TupleEnd,
+        ],
+    );
+}
+
+#[test]
+fn test_serde_slice() {
+    assert_tokens(
+        &MBox::from_slice(b"ab"),
+        &[
+            Token::Seq { len: Some(2) },
+            Token::U8(b'a'),
+            Token::U8(b'b'),
+            Token::SeqEnd,
+        ],
+    );
+    assert_tokens(
+        &MBox::<[u8]>::default(),
+        &[Token::Seq { len: Some(0) }, Token::SeqEnd],
+    );
+    assert_de_tokens(
+        &MBox::from_slice(b"xyz"),
+        &[
+            Token::Seq { len: None },
+            Token::U8(b'x'),
+            Token::U8(b'y'),
+            Token::U8(b'z'),
+            Token::SeqEnd,
+        ],
+    );
+}
+
+#[test]
+fn test_serde_str() {
+    assert_tokens(&MBox::<str>::from("abc"), &[Token::Str("abc")]);
+    assert_de_tokens(&MBox::<str>::from("abc"), &[Token::BorrowedStr("abc")]);
+    assert_de_tokens(&MBox::<str>::from("abc"), &[Token::Bytes(b"abc")]);
+}
+
+#[test]
+fn test_serde_string() {
+    assert_tokens(&MString::from("abc"), &[Token::Str("abc")]);
+    assert_tokens(&MString::default(), &[Token::Str("")]);
+    assert_de_tokens(&MString::from("abc"), &[Token::String("abc")]);
+    assert_de_tokens(&MString::from("甲"), &[Token::Bytes("甲".as_bytes())]);
+    assert_de_tokens_error::<MString>(
+        &[Token::Bytes(b"\xff")],
+        "invalid value: byte array, expected a string",
+    );
+}
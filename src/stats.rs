@@ -0,0 +1,255 @@
+//! Global allocation statistics.
+//!
+//! When the `stats` feature is enabled, every `malloc()`, `realloc()` and `free()` performed by
+//! this crate updates a few global atomic counters, which can be read with `snapshot()` and
+//! exported to a metrics system.
+//!
+//! The sizes are measured with `malloc_usable_size()` or similar, i.e. they include the slack given
+//! by the allocator. On platforms without such a function, the byte counters always stay 0.
+//!
+//! Blocks allocated by C code and freed by this crate are counted as frees but never as
+//! allocations, and conversely for blocks passed to C's `free()` after `into_raw()`. The current
+//! counts saturate at 0 in the former case.
+//!
+//! With the `std` feature, `MemoryGuard` checks that a scope, typically a test, frees everything it
+//! allocates.
+
+use libc::c_void;
+
+#[cfg(feature = "std")]
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::internal::usable_size;
+
+static MALLOCS: AtomicUsize = AtomicUsize::new(0);
+static FREES: AtomicUsize = AtomicUsize::new(0);
+static REALLOCS: AtomicUsize = AtomicUsize::new(0);
+static BYTES_ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static BYTES_FREED: AtomicUsize = AtomicUsize::new(0);
+static PEAK_BYTES_IN_USE: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(feature = "std")]
+thread_local! {
+    /// The number of blocks allocated minus the number of blocks freed by the current thread.
+    static THREAD_LIVE_BLOCKS: Cell<isize> = Cell::new(0);
+}
+
+#[cfg(feature = "std")]
+fn add_thread_live_blocks(delta: isize) {
+    // the thread-local may be already destroyed when freeing from another thread-local destructor.
+    let _ = THREAD_LIVE_BLOCKS.try_with(|live| live.set(live.get().wrapping_add(delta)));
+}
+
+/// A snapshot of the global allocation statistics.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Stats {
+    /// The number of blocks currently allocated.
+    pub current_allocations: usize,
+    /// The number of bytes currently allocated.
+    pub bytes_in_use: usize,
+    /// The highest value `bytes_in_use` has reached.
+    pub peak_bytes_in_use: usize,
+    /// The total number of blocks allocated with `malloc()` or `calloc()`.
+    pub mallocs: usize,
+    /// The total number of blocks freed.
+    pub frees: usize,
+    /// The total number of blocks resized with `realloc()`.
+    pub reallocs: usize,
+}
+
+/// Reads the current statistics.
+///
+/// The counters are updated independently, so a snapshot taken while other threads allocate may be
+/// slightly inconsistent.
+pub fn snapshot() -> Stats {
+    let mallocs = MALLOCS.load(Ordering::Relaxed);
+    let frees = FREES.load(Ordering::Relaxed);
+    Stats {
+        current_allocations: mallocs.saturating_sub(frees),
+        bytes_in_use: bytes_in_use(),
+        peak_bytes_in_use: PEAK_BYTES_IN_USE.load(Ordering::Relaxed),
+        mallocs,
+        frees,
+        reallocs: REALLOCS.load(Ordering::Relaxed),
+    }
+}
+
+fn bytes_in_use() -> usize {
+    // loads the freed bytes first, so a concurrent free never makes the result exceed the truth.
+    let freed = BYTES_FREED.load(Ordering::Relaxed);
+    BYTES_ALLOCATED
+        .load(Ordering::Relaxed)
+        .saturating_sub(freed)
+}
+
+fn add_allocated_bytes(size: usize) {
+    BYTES_ALLOCATED.fetch_add(size, Ordering::Relaxed);
+    let current = bytes_in_use();
+    let mut peak = PEAK_BYTES_IN_USE.load(Ordering::Relaxed);
+    while current > peak {
+        match PEAK_BYTES_IN_USE.compare_exchange_weak(
+            peak,
+            current,
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        ) {
+            Ok(_) => break,
+            Err(actual) => peak = actual,
+        }
+    }
+}
+
+/// Records a block which has just been allocated.
+///
+/// # Safety
+///
+/// The block must have been returned by `malloc()` or similar C functions.
+pub unsafe fn record_malloc(ptr: *mut c_void) {
+    MALLOCS.fetch_add(1, Ordering::Relaxed);
+    #[cfg(feature = "std")]
+    add_thread_live_blocks(1);
+    add_allocated_bytes(usable_size(ptr));
+}
+
+/// Records a block about to be freed.
+///
+/// # Safety
+///
+/// The block must be still allocated.
+pub unsafe fn record_free(ptr: *mut c_void) {
+    FREES.fetch_add(1, Ordering::Relaxed);
+    #[cfg(feature = "std")]
+    add_thread_live_blocks(-1);
+    BYTES_FREED.fetch_add(usable_size(ptr), Ordering::Relaxed);
+}
+
+/// Records a block which has been resized from `old_size` usable bytes.
+///
+/// # Safety
+///
+/// The new block must have been returned by `realloc()` or similar C functions.
+pub unsafe fn record_realloc(old_size: usize, new_ptr: *mut c_void) {
+    REALLOCS.fetch_add(1, Ordering::Relaxed);
+    BYTES_FREED.fetch_add(old_size, Ordering::Relaxed);
+    add_allocated_bytes(usable_size(new_ptr));
+}
+
+/// A guard asserting that the current thread frees as many blocks as it allocates between the
+/// construction and the drop of the guard.
+///
+/// Only the blocks allocated and freed by the current thread are counted, so tests running
+/// concurrently do not disturb each other. The check compares counts, hence a leaked block can be
+/// masked by the free of a block allocated before the guard.
+///
+/// # Panics
+///
+/// Dropping the guard panics if some blocks allocated since it was created are not freed, unless
+/// the thread is already panicking.
+///
+/// ```
+/// # #[cfg(feature = "stats")] {
+/// use mbox::stats::MemoryGuard;
+/// use mbox::MBox;
+///
+/// let guard = MemoryGuard::new();
+/// let boxed = MBox::new(1);
+/// assert_eq!(guard.live_blocks(), 1);
+/// drop(boxed);
+/// // dropping the guard would panic if `boxed` were leaked.
+/// # }
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct MemoryGuard {
+    start: isize,
+}
+
+#[cfg(feature = "std")]
+impl MemoryGuard {
+    /// Starts counting the blocks allocated and freed by the current thread.
+    pub fn new() -> Self {
+        Self {
+            start: THREAD_LIVE_BLOCKS.with(Cell::get),
+        }
+    }
+
+    /// Returns the number of blocks allocated by the current thread since the guard was created,
+    /// minus the number of blocks it freed.
+    pub fn live_blocks(&self) -> isize {
+        THREAD_LIVE_BLOCKS.with(Cell::get).wrapping_sub(self.start)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for MemoryGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for MemoryGuard {
+    fn drop(&mut self) {
+        let live_blocks = self.live_blocks();
+        if live_blocks > 0 && !std::thread::panicking() {
+            panic!(
+                "memory leak detected: {} blocks allocated in this scope are not freed",
+                live_blocks
+            );
+        }
+    }
+}
+
+#[test]
+fn test_stats() {
+    use crate::mbox::MBox;
+
+    // other tests allocate concurrently, so only lower bounds can be checked.
+    let before = snapshot();
+    let mut slice = MBox::from_slice(&[1u64; 100]);
+    slice.extend_from_slice(&[2u64; 100]);
+    let during = snapshot();
+    drop(slice);
+    let after = snapshot();
+
+    assert!(during.mallocs > before.mallocs);
+    assert!(during.reallocs > before.reallocs);
+    assert!(after.frees > during.frees);
+    assert!(after.peak_bytes_in_use >= during.bytes_in_use);
+    assert!(after.peak_bytes_in_use >= before.peak_bytes_in_use);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_memory_guard() {
+    use crate::mbox::MBox;
+
+    let outer = MBox::new(1u8);
+    let guard = MemoryGuard::new();
+    let mut slice = MBox::from_slice(b"abc");
+    slice.extend_from_slice(b"def");
+    assert_eq!(guard.live_blocks(), 1);
+    drop(slice);
+    assert_eq!(guard.live_blocks(), 0);
+    drop(outer);
+    assert_eq!(guard.live_blocks(), -1);
+}
+
+#[cfg(all(feature = "std", not(windows)))]
+#[test]
+fn test_memory_guard_leak() {
+    use crate::mbox::MBox;
+    use std::panic::catch_unwind;
+
+    let error = catch_unwind(|| {
+        let _guard = MemoryGuard::new();
+        MBox::leak(MBox::new(1u32));
+    })
+    .unwrap_err();
+    let message = error.downcast_ref::<String>().unwrap();
+    assert_eq!(
+        message,
+        "memory leak detected: 1 blocks allocated in this scope are not freed"
+    );
+}
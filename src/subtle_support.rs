@@ -0,0 +1,35 @@
+//! `subtle` support.
+
+use subtle::{Choice, ConstantTimeEq};
+
+use crate::mbox::MBox;
+use crate::sentinel::MString;
+
+impl<T: ConstantTimeEq> ConstantTimeEq for MBox<[T]> {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        (**self).ct_eq(&**other)
+    }
+}
+
+impl ConstantTimeEq for MString {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.as_bytes().ct_eq(other.as_bytes())
+    }
+}
+
+#[test]
+fn test_subtle() {
+    let a = MBox::from_slice(b"abc");
+    assert!(bool::from(ConstantTimeEq::ct_eq(
+        &a,
+        &MBox::from_slice(b"abc")
+    )));
+    assert!(!bool::from(ConstantTimeEq::ct_eq(
+        &a,
+        &MBox::from_slice(b"abd")
+    )));
+
+    let s = MString::from("abc");
+    assert!(bool::from(ConstantTimeEq::ct_eq(&s, &MString::from("abc"))));
+    assert!(!bool::from(ConstantTimeEq::ct_eq(&s, &MString::from("ab"))));
+}
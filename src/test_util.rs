@@ -0,0 +1,127 @@
+//! Helpers for testing types which own items through an `MBox`.
+//!
+//! These are the structures used by the tests of this crate, exposed with the `test_util` feature so
+//! that downstream wrappers can check they drop every item exactly once, and stay sound when a
+//! `clone()` panics halfway.
+//!
+//! ```
+//! use mbox::test_util::DropCounter;
+//! use mbox::MBox;
+//!
+//! let counter = DropCounter::default();
+//! let slice = MBox::from_slice(&[counter.clone(), counter.clone()]);
+//! counter.assert_eq(2); // the temporary array is dropped.
+//! drop(slice);
+//! counter.assert_eq(4);
+//! ```
+
+//{{{ Drop counter --------------------------------------------------------------------------------
+
+#[cfg(all(not(windows), not(feature = "std")))]
+use std::ptr::NonNull;
+
+#[cfg(all(not(windows), not(feature = "std")))]
+use crate::internal::gen_malloc;
+
+/// A counter shared by all clones of a `DropCounter`.
+#[cfg(not(windows))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Default))]
+pub struct SharedCounter {
+    #[cfg(feature = "std")]
+    counter: std::rc::Rc<std::cell::Cell<usize>>,
+
+    /// A shared, mutable counter on heap.
+    #[cfg(not(feature = "std"))]
+    counter: NonNull<usize>,
+}
+
+#[cfg(all(not(windows), not(feature = "std")))]
+impl Default for SharedCounter {
+    /// Creates a new counter. Without `std`, the counter is allocated by `malloc()` and never
+    /// freed.
+    fn default() -> Self {
+        let counter = gen_malloc(1);
+        // SAFETY: malloc() returns an uninitialized integer which is then filled in.
+        unsafe {
+            std::ptr::write(counter.as_ptr(), 0);
+            Self { counter }
+        }
+    }
+}
+
+#[cfg(not(windows))]
+impl SharedCounter {
+    /// Gets the counter value.
+    pub fn get(&self) -> usize {
+        #[cfg(feature = "std")]
+        {
+            self.counter.get()
+        }
+        // SAFETY: `self.counter` is malloc()'ed, initialized and never freed.
+        #[cfg(not(feature = "std"))]
+        unsafe {
+            *self.counter.as_ref()
+        }
+    }
+
+    /// Asserts the counter value equals to the input. Panics when different.
+    pub fn assert_eq(&self, value: usize) {
+        assert_eq!(self.get(), value);
+    }
+
+    /// Increases the counter by 1.
+    fn inc(&self) {
+        #[cfg(feature = "std")]
+        {
+            self.counter.set(self.counter.get() + 1);
+        }
+        // SAFETY: `self.counter` is malloc()'ed, initialized and never freed.
+        // Since `SharedCounter` is not Sync nor Send, we are sure the
+        // modification happens in the single thread, so we don't worry about
+        // the interior mutation.
+        #[cfg(not(feature = "std"))]
+        unsafe {
+            *self.counter.as_ptr() += 1;
+        }
+    }
+}
+
+/// A test structure to count how many times the value has been dropped.
+///
+/// All clones share the same counter, which can be read through `Deref`.
+#[cfg(not(windows))]
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct DropCounter(SharedCounter);
+
+#[cfg(not(windows))]
+impl std::ops::Deref for DropCounter {
+    type Target = SharedCounter;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(not(windows))]
+impl Drop for DropCounter {
+    fn drop(&mut self) {
+        self.0.inc();
+    }
+}
+
+//}}}
+
+//{{{ Panic-on-clone ------------------------------------------------------------------------------
+
+/// A test structure which panics when it is cloned.
+#[derive(Debug, Default)]
+#[repr(C)] // silence the dead code warning, we don't want a ZST here to complicate things.
+pub struct PanicOnClone(u8);
+
+impl Clone for PanicOnClone {
+    fn clone(&self) -> Self {
+        panic!("panic on clone");
+    }
+}
+
+//}}}
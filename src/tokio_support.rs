@@ -0,0 +1,69 @@
+//! tokio support.
+//!
+//! `AsyncRead` implementations are given a `ReadBuf` over the uninitialized spare capacity of an
+//! `MSliceBuilder<u8>`, so large reads go straight into the malloc'd buffer without zero-filling it
+//! first.
+
+use tokio::io::{AsyncRead, ReadBuf};
+
+use std::future::poll_fn;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::mbox::{MBox, MSliceBuilder};
+
+/// Reads once from `reader` into the spare capacity of `builder`, growing the builder with
+/// `realloc()` first if it is full. Returns the number of bytes read, where 0 means EOF.
+pub fn poll_read_buf<R: AsyncRead + ?Sized>(
+    reader: Pin<&mut R>,
+    cx: &mut Context,
+    builder: &mut MSliceBuilder<u8>,
+) -> Poll<io::Result<usize>> {
+    let mut buf = ReadBuf::uninit(builder.spare_capacity());
+    match reader.poll_read(cx, &mut buf) {
+        Poll::Ready(Ok(())) => {}
+        Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+        Poll::Pending => return Poll::Pending,
+    }
+    let len = buf.filled().len();
+    // SAFETY: `ReadBuf` ensures the filled part at the start of the spare capacity is initialized.
+    unsafe { builder.commit_spare(len) };
+    Poll::Ready(Ok(len))
+}
+
+/// Reads all bytes until EOF from `reader` into a new buffer, like `AsyncReadExt::read_to_end()`.
+///
+/// In case of error, the partially read buffer is discarded.
+pub async fn read_to_end_mbox<R: AsyncRead + Unpin + ?Sized>(
+    reader: &mut R,
+) -> io::Result<MBox<[u8]>> {
+    let mut builder = MSliceBuilder::with_capacity(32);
+    while poll_fn(|cx| poll_read_buf(Pin::new(&mut *reader), cx, &mut builder)).await? != 0 {}
+    Ok(builder.into_mboxed_slice())
+}
+
+#[test]
+fn test_read_to_end_mbox() {
+    use std::future::Future;
+    use std::task::{RawWaker, RawWakerVTable, Waker};
+
+    unsafe fn clone(_: *const ()) -> RawWaker {
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+    unsafe fn noop(_: *const ()) {}
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+
+    // SAFETY: the vtable does nothing and never touches the data pointer.
+    let waker = unsafe { Waker::from_raw(clone(std::ptr::null())) };
+    let mut context = Context::from_waker(&waker);
+
+    // reading from a slice never returns `Pending`.
+    let data = (0..1000).map(|i| i as u8).collect::<Vec<_>>();
+    let mut reader = &data[..];
+    let mut future = Box::pin(read_to_end_mbox(&mut reader));
+    match future.as_mut().poll(&mut context) {
+        Poll::Ready(result) => assert_eq!(&*result.unwrap(), &*data),
+        Poll::Pending => panic!("reading from a slice should not be pending"),
+    }
+}
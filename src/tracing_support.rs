@@ -0,0 +1,100 @@
+//! `tracing` support.
+//!
+//! Every `malloc()`, `realloc()` and `free()` performed by this crate emits a `TRACE` event with the
+//! `mbox` target, carrying the address, the size in bytes and the type name of the block. Events
+//! are cheap when no subscriber is interested in them.
+
+use libc::c_void;
+
+use std::any::type_name;
+
+/// Emits an event for a block of `count` items of type `T` which has just been allocated.
+pub fn malloc<T>(ptr: *mut c_void, count: usize) {
+    tracing::trace!(
+        target: "mbox",
+        ptr = ?ptr,
+        size = count.wrapping_mul(std::mem::size_of::<T>()),
+        type_name = type_name::<T>(),
+        "malloc"
+    );
+}
+
+/// Emits an event for a block which has been resized to `new_count` items of type `T`.
+pub fn realloc<T>(old_ptr: *mut c_void, new_ptr: *mut c_void, new_count: usize) {
+    tracing::trace!(
+        target: "mbox",
+        old_ptr = ?old_ptr,
+        ptr = ?new_ptr,
+        size = new_count.wrapping_mul(std::mem::size_of::<T>()),
+        type_name = type_name::<T>(),
+        "realloc"
+    );
+}
+
+/// Emits an event for a block of type `T` about to be freed.
+pub fn free<T>(ptr: *mut c_void) {
+    tracing::trace!(
+        target: "mbox",
+        ptr = ?ptr,
+        type_name = type_name::<T>(),
+        "free"
+    );
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_tracing() {
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    use crate::mbox::MBox;
+
+    /// Collects the `message` and `size` of the events.
+    #[derive(Default)]
+    struct Collector(Arc<Mutex<Vec<String>>>);
+
+    struct EventVisitor(String);
+
+    impl Visit for EventVisitor {
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            if field.name() == "size" {
+                self.0 += &format!(" {}", value);
+            }
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            if field.name() == "message" {
+                self.0 += &format!("{:?}", value);
+            }
+        }
+    }
+
+    impl Subscriber for Collector {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            metadata.target() == "mbox"
+        }
+        fn new_span(&self, _: &Attributes) -> Id {
+            Id::from_u64(1)
+        }
+        fn record(&self, _: &Id, _: &Record) {}
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, event: &Event) {
+            let mut visitor = EventVisitor(String::new());
+            event.record(&mut visitor);
+            self.0.lock().unwrap().push(visitor.0);
+        }
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    let collector = Collector::default();
+    let events = collector.0.clone();
+    tracing::subscriber::with_default(collector, || {
+        let mut slice = MBox::from_slice(&[1u32, 2, 3]);
+        slice.extend_from_slice(&[4, 5]);
+        drop(slice);
+    });
+    assert_eq!(*events.lock().unwrap(), ["malloc 12", "realloc 20", "free"]);
+}
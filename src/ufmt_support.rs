@@ -0,0 +1,71 @@
+//! `ufmt` support.
+//!
+//! `MString` can be written into with `uwrite!()`, so a message can be formatted directly into a
+//! malloc'd string which is then handed to C code. The boxes are formatted like their content.
+//! Like `str`, `MString` only implements `uDisplay`, since `ufmt` avoids the escaping needed by
+//! `uDebug`.
+
+use std::convert::Infallible;
+
+use ufmt::{uDebug, uDisplay, uWrite, Formatter};
+
+use crate::free::Free;
+use crate::mbox::MBox;
+use crate::sentinel::{MArray, MString, Sentinel};
+
+impl uWrite for MString {
+    type Error = Infallible;
+
+    fn write_str(&mut self, string: &str) -> Result<(), Infallible> {
+        self.push_str(string);
+        Ok(())
+    }
+}
+
+impl<T: ?Sized + Free + uDisplay> uDisplay for MBox<T> {
+    fn fmt<W: uWrite + ?Sized>(&self, formatter: &mut Formatter<W>) -> Result<(), W::Error> {
+        <T as uDisplay>::fmt(self, formatter)
+    }
+}
+
+impl<T: ?Sized + Free + uDebug> uDebug for MBox<T> {
+    fn fmt<W: uWrite + ?Sized>(&self, formatter: &mut Formatter<W>) -> Result<(), W::Error> {
+        <T as uDebug>::fmt(self, formatter)
+    }
+}
+
+impl<T: Sentinel + uDebug> uDebug for MArray<T> {
+    fn fmt<W: uWrite + ?Sized>(&self, formatter: &mut Formatter<W>) -> Result<(), W::Error> {
+        <[T] as uDebug>::fmt(self, formatter)
+    }
+}
+
+impl uDisplay for MString {
+    fn fmt<W: uWrite + ?Sized>(&self, formatter: &mut Formatter<W>) -> Result<(), W::Error> {
+        <str as uDisplay>::fmt(self, formatter)
+    }
+}
+
+#[test]
+fn test_ufmt() {
+    let mut string = MString::new_empty();
+    ufmt::uwrite!(
+        string,
+        "{}={}",
+        MBox::<str>::from("key"),
+        MString::from("value")
+    )
+    .unwrap();
+    assert_eq!(string.as_bytes_with_sentinel(), b"key=value\0");
+
+    let mut string = MString::default();
+    ufmt::uwrite!(
+        string,
+        "{:?} {:?}",
+        MBox::from_slice(&[1u8, 2]),
+        MArray::from_slice(&[3u16])
+    )
+    .unwrap();
+    ufmt::uwrite!(string, " {}", MBox::new(4u32)).unwrap();
+    assert_eq!(&*string, "[1, 2] [3] 4");
+}
@@ -0,0 +1,61 @@
+//! `zeroize` support.
+//!
+//! Wrapping a box in `Zeroizing` wipes its content before it is freed, see `ZeroizingMBox` and
+//! `ZeroizingMString`.
+
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::free::Free;
+use crate::mbox::MBox;
+use crate::sentinel::MString;
+
+/// An `MBox` whose content is zeroed before calling `free()`.
+pub type ZeroizingMBox<T> = Zeroizing<MBox<T>>;
+
+/// An `MString` whose content is zeroed before calling `free()`.
+pub type ZeroizingMString = Zeroizing<MString>;
+
+impl<T: ?Sized + Free + Zeroize> Zeroize for MBox<T> {
+    fn zeroize(&mut self) {
+        (**self).zeroize();
+    }
+}
+
+/// Zeroes the content of the string, and then truncates it to be empty, same as `String`.
+impl Zeroize for MString {
+    fn zeroize(&mut self) {
+        (**self).zeroize();
+        self.truncate(0);
+    }
+}
+
+#[test]
+fn test_zeroize_mbox() {
+    let mut value = MBox::new(0x1234_5678u32);
+    value.zeroize();
+    assert_eq!(*value, 0);
+
+    let mut slice = MBox::from_slice(b"secret");
+    slice.zeroize();
+    assert_eq!(&*slice, &[0; 6]);
+
+    let mut string = MBox::<str>::from("secret");
+    string.zeroize();
+    assert_eq!(&*string, "\0\0\0\0\0\0");
+}
+
+#[test]
+fn test_zeroize_mstring() {
+    let mut string = MString::from("hunter2");
+    string.zeroize();
+    assert_eq!(&*string, "");
+    assert_eq!(string.as_bytes_with_sentinel(), b"\0");
+}
+
+#[test]
+fn test_zeroizing_mbox() {
+    let key: ZeroizingMBox<[u8]> = Zeroizing::new(MBox::from_slice(b"key"));
+    assert_eq!(&**key, b"key");
+    let passphrase: ZeroizingMString = Zeroizing::new(MString::from("passphrase"));
+    assert_eq!(&**passphrase, "passphrase");
+}